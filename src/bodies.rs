@@ -0,0 +1,537 @@
+//! The planets and moons, J2 drift, ground tracks, and patched conics.
+
+use tau::TAU;
+use na::{Matrix3, Rotation3, Vector3};
+
+use orbits::{Trajectory, Plane, PlanarTrajectory};
+use self::ReferencePlane::{Ecliptic, ParentEquator};
+
+/// A central body for things to orbit around.
+///
+/// Trajectories around a body are relative to its equator, which `pole` sets,
+/// so `axial_tilt` (relative to the body's own orbit) is only for reference.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Body {
+    pub name: String,
+    pub radius: f64, // km (equatorial)
+    pub mu: f64, // km³/s²
+    pub rotation_rate: f64, // rad/s (sidereal)
+    pub axial_tilt: f64, // rad
+    /// Which way the north pole points in the ICRF (Earth's mean equator and equinox of J2000).
+    /// This is the pole the body turns counterclockwise around,
+    /// so it's the IAU's south pole for retrograde rotators.
+    pub pole: Vector3<f64>,
+    pub j2: f64,
+    pub orbit: Option<BodyOrbit>,
+}
+
+/// Where a body is relative to the body it orbits.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct BodyOrbit {
+    pub parent: usize, // index into the list of bodies this is part of
+    pub reference: ReferencePlane, // what `trajectory`'s plane is relative to
+    /// The body is at `mean_anom0` at time zero (J2000 for the presets).
+    pub trajectory: Trajectory,
+}
+
+/// The axes a body's orbital elements are relative to.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ReferencePlane {
+    /// The mean ecliptic and equinox of J2000.
+    Ecliptic,
+    /// The parent's equator, like trajectories around the parent.
+    ParentEquator,
+}
+
+impl BodyOrbit {
+    /// Computes the rotation from the axes `trajectory` is relative to
+    /// to the parent's equatorial axes.
+    pub fn to_parent(&self, bodies: &[Body]) -> Rotation3<f64> {
+        match self.reference {
+            Ecliptic => bodies[self.parent].equator().inverse() * ecliptic(),
+            ParentEquator => Rotation3::identity(),
+        }
+    }
+}
+
+/// The angle between the ICRF's equator and the J2000 mean ecliptic.
+const OBLIQUITY: f64 = 23.4392911; // deg
+
+/// Computes the rotation from the J2000 mean ecliptic's axes to the ICRF's.
+pub fn ecliptic() -> Rotation3<f64> {
+    Rotation3::from_axis_angle(&Vector3::x_axis(), OBLIQUITY.to_radians())
+}
+
+impl Body {
+    fn new(name: &str,
+           radius: f64,
+           mu: f64,
+           rotation_period: f64, // h (sidereal)
+           axial_tilt: f64, // deg
+           j2: f64)
+           -> Body {
+        Body {
+            name: name.to_owned(),
+            radius: radius,
+            mu: mu,
+            rotation_rate: TAU / (rotation_period * 3600.0),
+            axial_tilt: axial_tilt.to_radians(),
+            pole: Vector3::z(),
+            j2: j2,
+            orbit: None,
+        }
+    }
+
+    /// Points this body's north pole at the given right ascension and declination (deg).
+    fn pole(mut self, right_ascension: f64, declination: f64) -> Body {
+        let (ra, dec) = (right_ascension.to_radians(), declination.to_radians());
+        self.pole = Vector3::new(dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin());
+        self
+    }
+
+    /// Puts this body in orbit around `parent` (angles in degrees, relative to `reference`).
+    fn orbiting(mut self,
+                parent: usize,
+                reference: ReferencePlane,
+                semi_major_axis: f64, // km
+                eccentr: f64,
+                inclination: f64,
+                lon_asc_node: f64,
+                arg_peri: f64,
+                mean_anom: f64)
+                -> Body {
+        self.orbit = Some(BodyOrbit {
+            parent: parent,
+            reference: reference,
+            trajectory: Trajectory {
+                p: Plane {
+                    lon_asc_node: lon_asc_node.to_radians(),
+                    inclination: inclination.to_radians(),
+                    arg_peri: arg_peri.to_radians(),
+                },
+                t: PlanarTrajectory {
+                    periapsis: semi_major_axis * (1.0 - eccentr),
+                    eccentr: eccentr,
+                    mean_anom0: mean_anom.to_radians(),
+                    mean_anom1: mean_anom.to_radians() + TAU,
+                },
+            },
+        });
+        self
+    }
+
+    /// Computes the rotation from this body's equatorial axes to the ICRF's.
+    /// The x-axis is where the equator crosses Earth's going north
+    /// (or the ICRF's x-axis, for Earth itself).
+    pub fn equator(&self) -> Rotation3<f64> {
+        let node = Vector3::z().cross(&self.pole);
+        if node.norm() <= 1e-12 {
+            return Rotation3::identity();
+        }
+        let x = node.normalize();
+        let y = self.pole.cross(&x);
+        Rotation3::from_matrix_unchecked(Matrix3::from_columns(&[x, y, self.pole]))
+    }
+
+    /// Computes the radius (km) of this body's sphere of influence (using Laplace's formula)
+    /// if it orbits one of `bodies`.
+    pub fn soi_radius(&self, bodies: &[Body]) -> Option<f64> {
+        self.orbit.map(|orbit| {
+            let parent_mu = bodies[orbit.parent].mu;
+            orbit.trajectory.t.semi_major_axis() * (self.mu / parent_mu).powf(2.0 / 5.0)
+        })
+    }
+
+    /// Computes `traj` (around this body) `dt` seconds later under the secular effects of J2:
+    /// the plane and periapsis precess and the mean motion changes a bit.
+    /// The mean anomalies move forward by however far the craft goes in that time,
+    /// so it's still at `mean_anom0`.
+    ///
+    /// Open trajectories don't precess (since they don't stick around long enough to).
+    pub fn j2_drift(&self, traj: &Trajectory, dt: f64) -> Trajectory {
+        let t = &traj.t;
+        let n = t.mean_motion(self.mu);
+        let (node_rate, peri_rate, mean_anom_rate) = if t.eccentr < 1.0 {
+            let k = self.j2 * (self.radius / t.semi_latus_rectum()).powi(2) * n;
+            let cos_i = traj.p.inclination.cos();
+            (-1.5 * k * cos_i,
+             0.75 * k * (5.0 * cos_i * cos_i - 1.0),
+             n + 0.75 * k * (1.0 - t.eccentr * t.eccentr).sqrt() * (3.0 * cos_i * cos_i - 1.0))
+        } else {
+            (0.0, 0.0, n)
+        };
+
+        let mut drifted = *traj;
+        drifted.p.lon_asc_node += node_rate * dt;
+        drifted.p.arg_peri += peri_rate * dt;
+        drifted.t.mean_anom0 += mean_anom_rate * dt;
+        drifted.t.mean_anom1 += mean_anom_rate * dt;
+        drifted
+    }
+
+    /// Computes how far this body has turned (rad) since time zero.
+    pub fn rotation_angle(&self, time: f64) -> f64 {
+        self.rotation_rate * time
+    }
+
+    /// Computes the latitude and longitude (rad) of the point on this body right under
+    /// `pos` (km, relative to this body) once it's turned `angle` (rad).
+    /// Longitudes are relative to the meridian that was on the x-axis at angle zero.
+    pub fn subpoint(&self, pos: &Vector3<f64>, angle: f64) -> (f64, f64) {
+        let lat = (pos.z / pos.norm()).asin();
+        let lon = pos.y.atan2(pos.x) - angle;
+        (lat, wrap_angle(lon))
+    }
+
+    /// Computes the ground track of `traj` (around this body) from `start_time` to `end_time`
+    /// as `samples` points, each with the time and the latitude and longitude under the craft.
+    /// The craft is at `traj.t.mean_anom0` at time zero, when this body has turned `angle0` (rad).
+    pub fn ground_track(&self,
+                        traj: &Trajectory,
+                        angle0: f64,
+                        start_time: f64,
+                        end_time: f64,
+                        samples: usize)
+                        -> Vec<(f64, f64, f64)> {
+        (0..samples)
+            .map(|i| {
+                let time = start_time +
+                           (end_time - start_time) * i as f64 / (samples.max(2) - 1) as f64;
+                let pos = traj.propagate(self.mu, 0.0, time).position;
+                let (lat, lon) = self.subpoint(&pos, angle0 + self.rotation_angle(time));
+                (time, lat, lon)
+            })
+            .collect()
+    }
+}
+
+impl Default for Body {
+    /// Earth
+    fn default() -> Body {
+        PRESETS[EARTH].clone()
+    }
+}
+
+impl Default for PlanarTrajectory {
+    /// A low circular orbit around the default body.
+    fn default() -> Self {
+        PlanarTrajectory {
+            periapsis: Body::default().radius + DEFAULT_ALTITUDE,
+            eccentr: 0.0,
+            mean_anom0: 0.0,
+            mean_anom1: TAU,
+        }
+    }
+}
+
+/// How high above the surface new orbits start.
+pub const DEFAULT_ALTITUDE: f64 = 200.0; // km
+
+/// Indices into `PRESETS`.
+pub const SUN: usize = 0;
+pub const EARTH: usize = 3;
+const MARS: usize = 5;
+const JUPITER: usize = 8;
+const SATURN: usize = 13;
+const URANUS: usize = 15;
+const NEPTUNE: usize = 18;
+
+const AU: f64 = 149597870.7; // km
+
+lazy_static! {
+    /// The Sun, the planets, and their major moons.
+    ///
+    /// Retrograde rotators (Venus, Uranus) have an axial tilt over 90° instead of a negative
+    /// rotation rate. Bodies whose J2 hasn't been measured have it set to zero.
+    /// Poles are the IAU's J2000 values without the terms that make them wobble, except for the
+    /// Moon's, which goes around with its node (so it's where it was at J2000).
+    /// Planets' orbits are the mean J2000 elements relative to the ecliptic
+    /// (Earth's is really the Earth-Moon barycenter's).
+    /// Moons' orbits are mean elements relative to their planet's equator
+    /// (or the ecliptic, for the Moon), with the angles we don't care much about set to zero.
+    pub static ref PRESETS: Vec<Body> = vec![
+        Body::new("Sun", 695700.0, 1.32712440018e11, 609.12, 7.25, 2.2e-7)
+            .pole(286.13, 63.87),
+        Body::new("Mercury", 2440.53, 22031.86855, 1407.5, 0.034, 5.03e-5)
+            .pole(281.0103, 61.4155)
+            .orbiting(SUN, Ecliptic, 0.38709927 * AU, 0.20563593, 7.00497902,
+                      48.33076593, 29.12703035, 174.79252722),
+        Body::new("Venus", 6051.8, 324858.592, 5832.6, 177.36, 4.458e-6)
+            .pole(92.76, -67.16)
+            .orbiting(SUN, Ecliptic, 0.72333566 * AU, 0.00677672, 3.39467605,
+                      76.67984255, 54.92262463, 50.37663232),
+        Body::new("Earth", 6378.137, 398600.4418, 23.9344696, 23.44, 1.08263e-3)
+            .orbiting(SUN, Ecliptic, 1.00000261 * AU, 0.01671123, -0.00001531,
+                      0.0, 102.93768193, -2.47311027),
+        Body::new("Moon", 1738.1, 4902.800066, 655.72, 6.68, 2.0323e-4)
+            .pole(266.86, 65.64)
+            .orbiting(EARTH, Ecliptic, 384400.0, 0.0549, 5.145, 125.08, 318.15, 135.27),
+        Body::new("Mars", 3396.19, 42828.37, 24.6229, 25.19, 1.96045e-3)
+            .pole(317.68143, 52.8865)
+            .orbiting(SUN, Ecliptic, 1.52371034 * AU, 0.09339410, 1.84969142,
+                      49.55953891, 286.4968315, 19.39019754),
+        Body::new("Phobos", 13.0, 7.087e-4, 7.6533, 0.0, 0.0)
+            .pole(317.68, 52.9)
+            .orbiting(MARS, ParentEquator, 9376.0, 0.0151, 1.093, 0.0, 0.0, 0.0),
+        Body::new("Deimos", 7.8, 9.62e-5, 30.312, 0.0, 0.0)
+            .pole(316.65, 53.52)
+            .orbiting(MARS, ParentEquator, 23463.2, 0.00033, 0.93, 0.0, 0.0, 0.0),
+        Body::new("Jupiter", 71492.0, 126686534.0, 9.925, 3.13, 1.4736e-2)
+            .pole(268.056595, 64.495303)
+            .orbiting(SUN, Ecliptic, 5.20288700 * AU, 0.04838624, 1.30439695,
+                      100.47390909, 274.25457074, 19.66796068),
+        Body::new("Io", 1821.6, 5959.916, 42.459, 0.0, 1.8459e-3)
+            .pole(268.05, 64.5)
+            .orbiting(JUPITER, ParentEquator, 421700.0, 0.0041, 0.05, 0.0, 0.0, 0.0),
+        Body::new("Europa", 1560.8, 3202.739, 85.228, 0.1, 4.355e-4)
+            .pole(268.08, 64.51)
+            .orbiting(JUPITER, ParentEquator, 671034.0, 0.009, 0.47, 0.0, 0.0, 0.0),
+        Body::new("Ganymede", 2631.2, 9887.834, 171.709, 0.33, 1.2765e-4)
+            .pole(268.2, 64.57)
+            .orbiting(JUPITER, ParentEquator, 1070412.0, 0.0013, 0.2, 0.0, 0.0, 0.0),
+        Body::new("Callisto", 2410.3, 7179.289, 400.536, 0.0, 3.27e-5)
+            .pole(268.72, 64.83)
+            .orbiting(JUPITER, ParentEquator, 1882709.0, 0.0074, 0.192, 0.0, 0.0, 0.0),
+        Body::new("Saturn", 60268.0, 37931187.0, 10.656, 26.73, 1.6298e-2)
+            .pole(40.589, 83.537)
+            .orbiting(SUN, Ecliptic, 9.53667594 * AU, 0.05386179, 2.48599187,
+                      113.66242448, 338.93645383, -42.64463408),
+        Body::new("Titan", 2574.73, 8978.14, 382.68, 0.3, 3.15e-5)
+            .pole(39.4827, 83.4279)
+            .orbiting(SATURN, ParentEquator, 1221870.0, 0.0288, 0.34854, 0.0, 0.0, 0.0),
+        Body::new("Uranus", 25559.0, 5793939.0, 17.24, 97.77, 3.34343e-3)
+            .pole(77.311, 15.175)
+            .orbiting(SUN, Ecliptic, 19.18916464 * AU, 0.04725744, 0.77263783,
+                      74.01692503, 96.93735127, 142.28382821),
+        Body::new("Titania", 788.9, 228.2, 208.94, 0.0, 0.0)
+            .pole(77.43, 15.1)
+            .orbiting(URANUS, ParentEquator, 435910.0, 0.0011, 0.34, 0.0, 0.0, 0.0),
+        Body::new("Oberon", 761.4, 192.4, 323.12, 0.0, 0.0)
+            .pole(77.43, 15.1)
+            .orbiting(URANUS, ParentEquator, 583520.0, 0.0014, 0.058, 0.0, 0.0, 0.0),
+        Body::new("Neptune", 24764.0, 6836529.0, 16.11, 28.32, 3.411e-3)
+            .pole(299.36, 43.46)
+            .orbiting(SUN, Ecliptic, 30.06992276 * AU, 0.00859048, 1.77004347,
+                      131.78422574, 273.18053653, -100.08479196),
+        Body::new("Triton", 1353.4, 1427.6, 141.04, 0.0, 0.0)
+            .pole(119.36, -41.17)
+            .orbiting(NEPTUNE, ParentEquator, 354759.0, 0.000016, 156.885, 0.0, 0.0, 0.0),
+    ];
+}
+
+/// Finds the index of the body called `name` (ignoring case) in `bodies`.
+pub fn find(bodies: &[Body], name: &str) -> Option<usize> {
+    bodies.iter().position(|body| body.name.eq_ignore_ascii_case(name))
+}
+
+/// Computes the position (km) and velocity (km/s) of the `i`th of `bodies`
+/// relative to its parent (and its parent's equator) at `time`
+/// (or zero if it doesn't orbit anything).
+pub fn state(bodies: &[Body], i: usize, time: f64) -> (Vector3<f64>, Vector3<f64>) {
+    match bodies[i].orbit {
+        Some(orbit) => {
+            let mu = bodies[orbit.parent].mu;
+            let mean_anom = orbit.trajectory.propagate(mu, 0.0, time).mean_anom;
+            let (pos, vel) = orbit.trajectory.to_state(mu, mean_anom);
+            let rot = orbit.to_parent(bodies);
+            (rot * pos, rot * vel)
+        }
+        None => (Vector3::zeros(), Vector3::zeros()),
+    }
+}
+
+/// Computes the position (km) of the `to`th of `bodies` relative to the `from`th
+/// (and its equator) at `time`.
+pub fn relative_position(bodies: &[Body], from: usize, to: usize, time: f64) -> Vector3<f64> {
+    let pos = absolute_position(bodies, to, time) - absolute_position(bodies, from, time);
+    bodies[from].equator().inverse() * pos
+}
+
+/// Computes the rotation from the `from`th of `bodies`' equatorial axes to the `to`th's.
+pub fn change_of_axes(bodies: &[Body], from: usize, to: usize) -> Rotation3<f64> {
+    bodies[to].equator().inverse() * bodies[from].equator()
+}
+
+/// Computes the position of the `i`th of `bodies` relative to the root of its hierarchy,
+/// in the ICRF's axes.
+fn absolute_position(bodies: &[Body], mut i: usize, time: f64) -> Vector3<f64> {
+    let mut pos = Vector3::zeros();
+    while let Some(orbit) = bodies[i].orbit {
+        pos += bodies[orbit.parent].equator() * state(bodies, i, time).0;
+        i = orbit.parent;
+    }
+    pos
+}
+
+/// Wraps `angle` (rad) into [-π, π).
+pub fn wrap_angle(angle: f64) -> f64 {
+    (angle + TAU / 2.0).rem_euclid(TAU) - TAU / 2.0
+}
+
+/// A piece of a patched-conic trajectory that stays within one body's sphere of influence.
+#[derive(Clone,Copy,Debug)]
+pub struct Leg {
+    pub body: usize, // index into the list of bodies
+    /// Relative to `body`, with mean anomalies covering just this leg.
+    pub trajectory: Trajectory,
+    pub start_time: f64, // s (when the craft is at `trajectory.t.mean_anom0`)
+    pub end_time: f64, // s
+}
+
+impl Leg {
+    /// Computes the position (km) and velocity (km/s) relative to `body` at `time`.
+    pub fn state(&self, bodies: &[Body], time: f64) -> (Vector3<f64>, Vector3<f64>) {
+        let mu = bodies[self.body].mu;
+        let mean_anom = self.trajectory.propagate(mu, self.start_time, time).mean_anom;
+        self.trajectory.to_state(mu, mean_anom)
+    }
+}
+
+const MAX_LEGS: usize = 16;
+/// We look for sphere of influence entries this often (relative to the time it'd take
+/// to cross the sphere at the highest possible relative speed)...
+const SOI_SAMPLE_FRACTION: f64 = 0.5;
+/// ...but no more than this many times per leg per body.
+const MAX_SOI_SAMPLES: f64 = 10000.0;
+const SOI_BISECTIONS: u32 = 60;
+
+/// Follows `traj` around the `body`th of `bodies` from `start_time` (when the craft is at
+/// `traj.t.mean_anom0`) until `end_time`, switching to a new leg whenever the craft leaves
+/// its current body's sphere of influence or enters the sphere of one of that body's moons.
+pub fn propagate_patched(bodies: &[Body],
+                         body: usize,
+                         traj: &Trajectory,
+                         start_time: f64,
+                         end_time: f64)
+                         -> Vec<Leg> {
+    let mut legs = Vec::new();
+    // the moon the craft just left (if it did), which it's still on the edge of
+    let mut left = None;
+    let mut leg = Leg {
+        body: body,
+        trajectory: *traj,
+        start_time: start_time,
+        end_time: end_time,
+    };
+    loop {
+        let mu = bodies[leg.body].mu;
+        let exit_time = soi_exit_time(bodies, &leg).filter(|&t| t < end_time);
+        let limit = exit_time.unwrap_or(end_time);
+        let entry = soi_entry(bodies, &leg, limit, left);
+
+        leg.end_time = entry.map_or(limit, |(_, t)| t);
+        leg.trajectory.t.mean_anom1 = leg.trajectory.t.mean_anom0 +
+                                      leg.trajectory.t.mean_motion(mu) *
+                                      (leg.end_time - leg.start_time);
+        legs.push(leg);
+        if legs.len() >= MAX_LEGS {
+            break;
+        }
+
+        let (pos, vel) = leg.state(bodies, leg.end_time);
+        left = match (entry, exit_time) {
+            (None, Some(_)) => Some(leg.body),
+            _ => None,
+        };
+        let (new_body, pos, vel) = match (entry, exit_time) {
+            (Some((moon, time)), _) => {
+                let (moon_pos, moon_vel) = state(bodies, moon, time);
+                let rot = change_of_axes(bodies, leg.body, moon);
+                (moon, rot * (pos - moon_pos), rot * (vel - moon_vel))
+            }
+            (None, Some(time)) => {
+                let parent = bodies[leg.body].orbit.unwrap().parent;
+                let (body_pos, body_vel) = state(bodies, leg.body, time);
+                let rot = change_of_axes(bodies, leg.body, parent);
+                (parent, rot * pos + body_pos, rot * vel + body_vel)
+            }
+            (None, None) => break,
+        };
+        let (trajectory, mean_anom) = Trajectory::from_state(bodies[new_body].mu, pos, vel);
+        leg = Leg {
+            body: new_body,
+            trajectory: trajectory,
+            start_time: leg.end_time,
+            end_time: end_time,
+        };
+        leg.trajectory.t.mean_anom0 = mean_anom;
+    }
+    legs
+}
+
+/// Computes when the craft on `leg` leaves its body's sphere of influence (if it ever does).
+/// It only leaves on the way out, since a craft that's just come in starts on the edge
+/// (or a hair outside, after rounding).
+fn soi_exit_time(bodies: &[Body], leg: &Leg) -> Option<f64> {
+    let soi = bodies[leg.body].soi_radius(bodies)?;
+    let t = &leg.trajectory.t;
+    let mean_motion = t.mean_motion(bodies[leg.body].mu);
+    let true_anom0 = t.true_anom(t.mean_anom0);
+    if t.position(true_anom0).norm() >= soi && t.eccentr * true_anom0.sin() >= 0.0 {
+        return Some(leg.start_time);
+    }
+    if t.eccentr < 1.0 && t.semi_major_axis() * (1.0 + t.eccentr) < soi {
+        return None;
+    }
+    // where `r = soi` on the way out
+    let cos_true_anom = (t.semi_latus_rectum() / soi - 1.0) / t.eccentr;
+    let mut mean_anom = t.mean_anom(cos_true_anom.clamp(-1.0, 1.0).acos());
+    if t.eccentr < 1.0 {
+        mean_anom += TAU * ((t.mean_anom0 - mean_anom) / TAU).ceil();
+    }
+    Some(leg.start_time + (mean_anom - t.mean_anom0) / mean_motion)
+}
+
+/// Finds the first moon of `leg`'s body whose sphere of influence the craft enters
+/// before `limit`, and when. The craft starts on the edge of the sphere of the moon it `left`
+/// (if any), so that one only counts once the craft's been seen outside it.
+fn soi_entry(bodies: &[Body],
+             leg: &Leg,
+             limit: f64,
+             left: Option<usize>)
+             -> Option<(usize, f64)> {
+    let mu = bodies[leg.body].mu;
+    let craft_speed = max_speed(mu, &leg.trajectory.t);
+    bodies.iter()
+        .enumerate()
+        .filter(|&(_, moon)| moon.orbit.is_some_and(|orbit| orbit.parent == leg.body))
+        .filter_map(|(i, moon)| {
+            let soi = moon.soi_radius(bodies).unwrap();
+            let moon_speed = max_speed(mu, &moon.orbit.unwrap().trajectory.t);
+            let span = limit - leg.start_time;
+            let step = (SOI_SAMPLE_FRACTION * soi / (craft_speed + moon_speed))
+                .max(span / MAX_SOI_SAMPLES);
+            let inside = |time: f64| {
+                (leg.state(bodies, time).0 - state(bodies, i, time).0).norm() < soi
+            };
+
+            let mut prev = leg.start_time;
+            let mut outside = left != Some(i);
+            while prev < limit {
+                let next = (prev + step).min(limit);
+                if !inside(next) {
+                    outside = true;
+                } else if outside {
+                    let (mut lo, mut hi) = (prev, next);
+                    for _ in 0..SOI_BISECTIONS {
+                        let mid = (lo + hi) / 2.0;
+                        if inside(mid) {
+                            hi = mid;
+                        } else {
+                            lo = mid;
+                        }
+                    }
+                    return Some((i, hi));
+                }
+                prev = next;
+            }
+            None
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+/// Computes the fastest something on `traj` goes (i.e. at the periapsis),
+/// given a standard gravitational parameter `mu`.
+fn max_speed(mu: f64, traj: &PlanarTrajectory) -> f64 {
+    (mu * (1.0 + traj.eccentr) / traj.periapsis).sqrt()
+}
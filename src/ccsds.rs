@@ -0,0 +1,696 @@
+//! CCSDS Orbit Parameter Messages (OPM) and Orbit Ephemeris Messages (OEM), in the KVN
+//! ("keyword = value") format.
+//!
+//! See CCSDS 502.0-B-2, "Orbit Data Messages". Only the parts that fit a Keplerian trajectory
+//! are read: the state vector of an OPM and the states of each OEM segment. Everything else
+//! (covariances, maneuvers, spacecraft parameters) is skipped. Dates are read as they're written,
+//! so whoever uses the states has to check `TIME_SYSTEM` and `REF_FRAME`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use na::Vector3;
+use tau::TAU;
+
+use orbits::Trajectory;
+
+const OPM_VERSION: &str = "CCSDS_OPM_VERS";
+const OEM_VERSION: &str = "CCSDS_OEM_VERS";
+const SECONDS_PER_DAY: f64 = 86400.0;
+/// Days from 1970-01-01 to 2000-01-01.
+const UNIX_DAYS_TO_2000: i64 = 10957;
+
+/// The fields that say what a message is about and how to read it.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Metadata {
+    pub object_name: String,
+    /// Usually the international designator, like `2000-028A`.
+    pub object_id: String,
+    /// The body the states are relative to, like `EARTH`.
+    pub center_name: String,
+    /// The orientation of the axes, like `EME2000` or `TEME`.
+    pub ref_frame: String,
+    pub time_system: String,
+}
+
+impl Default for Metadata {
+    fn default() -> Metadata {
+        Metadata {
+            object_name: "UNKNOWN".to_owned(),
+            object_id: "UNKNOWN".to_owned(),
+            center_name: "EARTH".to_owned(),
+            ref_frame: "EME2000".to_owned(),
+            time_system: "UTC".to_owned(),
+        }
+    }
+}
+
+/// Who made a message, and when.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Header {
+    pub creation_date: f64, // s since J2000
+    pub originator: String,
+}
+
+impl Default for Header {
+    fn default() -> Header {
+        Header {
+            creation_date: 0.0,
+            originator: "orbit-sim".to_owned(),
+        }
+    }
+}
+
+/// A position and velocity at some date.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct StateVector {
+    pub epoch: f64, // s since J2000
+    pub position: Vector3<f64>, // km
+    pub velocity: Vector3<f64>, // km/s
+}
+
+impl StateVector {
+    /// Computes where something on `traj` is at `date`, given that it's at `mean_anom0` at
+    /// `epoch` (both in s since J2000), and a standard gravitational parameter `mu`.
+    pub fn on(traj: &Trajectory, mu: f64, epoch: f64, date: f64) -> StateVector {
+        let mean_anom = traj.propagate(mu, epoch, date).mean_anom;
+        let (position, velocity) = traj.to_state(mu, mean_anom);
+        StateVector {
+            epoch: date,
+            position: position,
+            velocity: velocity,
+        }
+    }
+
+    /// Computes the trajectory through this state given a standard gravitational parameter `mu`,
+    /// such that it's at `mean_anom0` at `epoch` (s since J2000).
+    pub fn trajectory(&self, mu: f64, epoch: f64) -> Trajectory {
+        let (mut traj, mean_anom) = Trajectory::from_state(mu, self.position, self.velocity);
+        let mean_anom0 = mean_anom - traj.t.mean_motion(mu) * (self.epoch - epoch);
+        traj.t.mean_anom0 = mean_anom0;
+        traj.t.mean_anom1 = mean_anom0 + TAU;
+        traj
+    }
+}
+
+/// An Orbit Parameter Message: one state vector, and optionally the Keplerian elements.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Opm {
+    pub header: Header,
+    pub meta: Metadata,
+    pub state: StateVector,
+    /// The standard gravitational parameter for the Keplerian elements.
+    /// Without it, the elements aren't written.
+    pub gm: Option<f64>, // km³/s²
+}
+
+impl Opm {
+    /// Describes where something on `traj` is at `date`, given that it's at `mean_anom0` at
+    /// `epoch` (both in s since J2000), and a standard gravitational parameter `mu`.
+    pub fn from_trajectory(meta: Metadata,
+                           traj: &Trajectory,
+                           mu: f64,
+                           epoch: f64,
+                           date: f64)
+                           -> Opm {
+        Opm {
+            header: Header::default(),
+            meta: meta,
+            state: StateVector::on(traj, mu, epoch, date),
+            gm: Some(mu),
+        }
+    }
+
+    /// Reads an OPM in KVN format.
+    pub fn parse(text: &str) -> Result<Opm, CcsdsError> {
+        let lines = tokenize(text)?;
+        check_version(OPM_VERSION, &lines)?;
+        let mut pairs = Pairs::new();
+        for line in &lines {
+            match *line {
+                Line::Pair(pair) => pairs.insert(pair),
+                Line::Word(n, _) | Line::Data(n, _) => return Err(CcsdsError::BadLine { line: n }),
+            }
+        }
+        let header = pairs.header()?;
+        let meta = pairs.metadata()?;
+        let epoch = pairs.date("EPOCH")?;
+        let mut position = Vector3::zeros();
+        let mut velocity = Vector3::zeros();
+        let axes = [("X", "X_DOT"), ("Y", "Y_DOT"), ("Z", "Z_DOT")];
+        for (i, &(pos_key, vel_key)) in axes.iter().enumerate() {
+            position[i] = pairs.number(pos_key, "km")?;
+            velocity[i] = pairs.number(vel_key, "km/s")?;
+        }
+        let gm = if pairs.get("GM").is_some() {
+            Some(pairs.number("GM", "km**3/s**2")?)
+        } else {
+            None
+        };
+        Ok(Opm {
+            header: header,
+            meta: meta,
+            state: StateVector {
+                epoch: epoch,
+                position: position,
+                velocity: velocity,
+            },
+            gm: gm,
+        })
+    }
+
+    /// Computes the trajectory through the state vector given a standard gravitational
+    /// parameter `mu`, such that it's at `mean_anom0` at `epoch` (s since J2000).
+    pub fn trajectory(&self, mu: f64, epoch: f64) -> Trajectory {
+        self.state.trajectory(mu, epoch)
+    }
+
+    /// Writes this in KVN format. The Keplerian elements are only written for closed orbits.
+    pub fn to_kvn(&self) -> String {
+        let mut out = String::new();
+        write_header(&mut out, OPM_VERSION, &self.header);
+        write_metadata(&mut out, &self.meta);
+        let s = &self.state;
+        writeln!(out, "\nCOMMENT State vector").unwrap();
+        writeln!(out, "EPOCH = {}", format_epoch(s.epoch)).unwrap();
+        for (i, axis) in ["X", "Y", "Z"].iter().enumerate() {
+            writeln!(out, "{} = {:.6} [km]", axis, s.position[i]).unwrap();
+        }
+        for (i, axis) in ["X", "Y", "Z"].iter().enumerate() {
+            writeln!(out, "{}_DOT = {:.9} [km/s]", axis, s.velocity[i]).unwrap();
+        }
+
+        let gm = match self.gm {
+            Some(gm) => gm,
+            None => return out,
+        };
+        let (traj, mean_anom) = Trajectory::from_state(gm, s.position, s.velocity);
+        if traj.t.eccentr >= 1.0 {
+            return out;
+        }
+        writeln!(out, "\nCOMMENT Keplerian elements").unwrap();
+        let semi_major_axis = traj.t.periapsis / (1.0 - traj.t.eccentr);
+        writeln!(out, "SEMI_MAJOR_AXIS = {:.6} [km]", semi_major_axis).unwrap();
+        writeln!(out, "ECCENTRICITY = {:.9}", traj.t.eccentr).unwrap();
+        let angles = [("INCLINATION", traj.p.inclination),
+                      ("RA_OF_ASC_NODE", traj.p.lon_asc_node.rem_euclid(TAU)),
+                      ("ARG_OF_PERICENTER", traj.p.arg_peri.rem_euclid(TAU)),
+                      ("MEAN_ANOMALY", mean_anom.rem_euclid(TAU))];
+        for &(keyword, angle) in &angles {
+            writeln!(out, "{} = {:.9} [deg]", keyword, angle.to_degrees()).unwrap();
+        }
+        writeln!(out, "GM = {} [km**3/s**2]", gm).unwrap();
+        out
+    }
+}
+
+/// An Orbit Ephemeris Message: tables of states, each with its own metadata.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Oem {
+    pub header: Header,
+    pub segments: Vec<OemSegment>,
+}
+
+/// One table of states in an OEM.
+#[derive(Clone,Debug,PartialEq)]
+pub struct OemSegment {
+    pub meta: Metadata,
+    pub states: Vec<StateVector>,
+}
+
+impl OemSegment {
+    /// Computes the trajectory through the first state given a standard gravitational
+    /// parameter `mu`, such that it's at `mean_anom0` at `epoch` (s since J2000).
+    /// Segments without any states don't have one.
+    pub fn trajectory(&self, mu: f64, epoch: f64) -> Option<Trajectory> {
+        self.states.first().map(|state| state.trajectory(mu, epoch))
+    }
+}
+
+impl Oem {
+    /// Tabulates the states along `traj` at each of `dates`, given that it's at `mean_anom0` at
+    /// `epoch` (both in s since J2000), and a standard gravitational parameter `mu`.
+    pub fn from_trajectory(meta: Metadata,
+                           traj: &Trajectory,
+                           mu: f64,
+                           epoch: f64,
+                           dates: &[f64])
+                           -> Oem {
+        Oem {
+            header: Header::default(),
+            segments: vec![OemSegment {
+                               meta: meta,
+                               states: dates.iter()
+                                   .map(|&date| StateVector::on(traj, mu, epoch, date))
+                                   .collect(),
+                           }],
+        }
+    }
+
+    /// Reads an OEM in KVN format.
+    pub fn parse(text: &str) -> Result<Oem, CcsdsError> {
+        let lines = tokenize(text)?;
+        check_version(OEM_VERSION, &lines)?;
+        let mut lines_iter = lines.iter().peekable();
+        let mut header = Pairs::new();
+        while let Some(&&Line::Pair(pair)) = lines_iter.peek() {
+            header.insert(pair);
+            lines_iter.next();
+        }
+        let mut oem = Oem {
+            header: header.header()?,
+            segments: Vec::new(),
+        };
+
+        while let Some(line) = lines_iter.next() {
+            match *line {
+                Line::Word(_, "META_START") => {}
+                Line::Word(n, _) | Line::Data(n, _) | Line::Pair(Pair { line: n, .. }) => {
+                    return Err(CcsdsError::BadLine { line: n })
+                }
+            }
+            let mut meta = Pairs::new();
+            loop {
+                match lines_iter.next() {
+                    Some(&Line::Pair(pair)) => meta.insert(pair),
+                    Some(&Line::Word(_, "META_STOP")) => break,
+                    Some(&Line::Word(n, _)) |
+                    Some(&Line::Data(n, _)) => return Err(CcsdsError::BadLine { line: n }),
+                    None => return Err(CcsdsError::Missing { keyword: "META_STOP" }),
+                }
+            }
+            let mut segment = OemSegment {
+                meta: meta.metadata()?,
+                states: Vec::new(),
+            };
+
+            while let Some(&line) = lines_iter.peek() {
+                match *line {
+                    Line::Data(n, text) => segment.states.push(parse_state(n, text)?),
+                    Line::Word(_, "COVARIANCE_START") => {
+                        // skip everything up to the end of the covariances
+                        let stop = lines_iter.by_ref()
+                            .any(|line| matches!(*line, Line::Word(_, "COVARIANCE_STOP")));
+                        if !stop {
+                            return Err(CcsdsError::Missing { keyword: "COVARIANCE_STOP" });
+                        }
+                        continue;
+                    }
+                    _ => break,
+                }
+                lines_iter.next();
+            }
+            oem.segments.push(segment);
+        }
+        Ok(oem)
+    }
+
+    /// Writes this in KVN format.
+    pub fn to_kvn(&self) -> String {
+        let mut out = String::new();
+        write_header(&mut out, OEM_VERSION, &self.header);
+        for segment in &self.segments {
+            writeln!(out, "\nMETA_START").unwrap();
+            write_metadata(&mut out, &segment.meta);
+            if let (Some(first), Some(last)) = (segment.states.first(), segment.states.last()) {
+                writeln!(out, "START_TIME = {}", format_epoch(first.epoch)).unwrap();
+                writeln!(out, "STOP_TIME = {}", format_epoch(last.epoch)).unwrap();
+            }
+            writeln!(out, "META_STOP\n").unwrap();
+            for s in &segment.states {
+                writeln!(out,
+                         "{} {:.6} {:.6} {:.6} {:.9} {:.9} {:.9}",
+                         format_epoch(s.epoch),
+                         s.position.x,
+                         s.position.y,
+                         s.position.z,
+                         s.velocity.x,
+                         s.velocity.y,
+                         s.velocity.z)
+                    .unwrap();
+            }
+        }
+        out
+    }
+}
+
+/// Either kind of message.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Message {
+    Opm(Opm),
+    Oem(Oem),
+}
+
+impl Message {
+    /// Reads an OPM or OEM in KVN format, depending on which version line it starts with.
+    pub fn parse(text: &str) -> Result<Message, CcsdsError> {
+        match tokenize(text)?.first() {
+            Some(&Line::Pair(Pair { key: OPM_VERSION, .. })) => Opm::parse(text).map(Message::Opm),
+            Some(&Line::Pair(Pair { key: OEM_VERSION, .. })) => Oem::parse(text).map(Message::Oem),
+            first => {
+                Err(CcsdsError::WrongMessage {
+                    line: first.map_or(1, Line::number),
+                    expected: "CCSDS_OPM_VERS or CCSDS_OEM_VERS",
+                })
+            }
+        }
+    }
+
+    /// Returns the metadata and first state of the OPM or of each segment of the OEM.
+    pub fn states(&self) -> Vec<(&Metadata, StateVector)> {
+        match *self {
+            Message::Opm(ref opm) => vec![(&opm.meta, opm.state)],
+            Message::Oem(ref oem) => {
+                oem.segments
+                    .iter()
+                    .filter_map(|seg| seg.states.first().map(|&state| (&seg.meta, state)))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// What was wrong with a message. Lines are numbered from 1.
+#[derive(Clone,Debug,PartialEq)]
+pub enum CcsdsError {
+    /// The message didn't start with the version line it should have.
+    WrongMessage { line: usize, expected: &'static str },
+    /// The line wasn't a keyword and value, a comment, or a row of data where one could be.
+    BadLine { line: usize },
+    /// A value couldn't be read, or had the wrong units.
+    BadValue {
+        line: usize,
+        keyword: String,
+        text: String,
+    },
+    /// A keyword that has to be there wasn't.
+    Missing { keyword: &'static str },
+}
+
+impl fmt::Display for CcsdsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CcsdsError::WrongMessage { line, expected } => {
+                write!(f, "line {}: expected {}", line, expected)
+            }
+            CcsdsError::BadLine { line } => write!(f, "line {}: didn't expect this here", line),
+            CcsdsError::BadValue { line, ref keyword, ref text } => {
+                write!(f, "line {}: couldn't read the {} from {:?}", line, keyword, text)
+            }
+            CcsdsError::Missing { keyword } => write!(f, "there's no {}", keyword),
+        }
+    }
+}
+
+impl Error for CcsdsError {}
+
+/// Formats `date` (s since J2000, ignoring leap seconds) like `2000-01-01T12:00:00.000000`.
+pub fn format_epoch(date: f64) -> String {
+    let micros = ((date + SECONDS_PER_DAY / 2.0) * 1e6).round() as i64;
+    let micros_per_day = SECONDS_PER_DAY as i64 * 1_000_000;
+    let (year, month, day) = civil_from_days(micros.div_euclid(micros_per_day) +
+                                             UNIX_DAYS_TO_2000);
+    let micros = micros.rem_euclid(micros_per_day);
+    let seconds = micros / 1_000_000;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            micros % 1_000_000)
+}
+
+/// Reads a date like `2000-01-01T12:00:00.000` or `2000-001T12:00:00` (by day of the year)
+/// as s since J2000.
+pub fn parse_epoch(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (date, time) = text.split_once('T')?;
+    let time = time.strip_suffix('Z').unwrap_or(time);
+
+    let date_parts: Vec<&str> = date.split('-').collect();
+    let days = match date_parts[..] {
+        [year, month, day] => {
+            let (year, month, day) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+            if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+                return None;
+            }
+            days_from_civil(year, month, day)
+        }
+        [year, day] => {
+            let (year, day): (i64, i64) = (year.parse().ok()?, day.parse().ok()?);
+            let new_year = days_from_civil(year, 1, 1);
+            if !(1..=days_from_civil(year + 1, 1, 1) - new_year).contains(&day) {
+                return None;
+            }
+            new_year + day - 1
+        }
+        _ => return None,
+    };
+
+    let time_parts: Vec<&str> = time.split(':').collect();
+    let (hours, minutes, seconds): (i64, i64, f64) = match time_parts[..] {
+        [h, m, s] => (h.parse().ok()?, m.parse().ok()?, s.parse().ok()?),
+        _ => return None,
+    };
+    // (60 seconds is a leap second)
+    if !(0..24).contains(&hours) || !(0..60).contains(&minutes) || !(0.0..61.0).contains(&seconds) {
+        return None;
+    }
+    let day_seconds = (hours * 60 + minutes) as f64 * 60.0 + seconds;
+    Some((days - UNIX_DAYS_TO_2000) as f64 * SECONDS_PER_DAY + day_seconds - SECONDS_PER_DAY / 2.0)
+}
+
+/// Counts the days in the given month (from 1) of the proleptic Gregorian calendar.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Counts the days from 1970-01-01 to the given date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // (from http://howardhinnant.github.io/date_algorithms.html, with years starting in March)
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Finds the year, month and day `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 -
+                       day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+fn write_header(out: &mut String, version: &str, header: &Header) -> () {
+    writeln!(out, "{} = 2.0", version).unwrap();
+    writeln!(out, "CREATION_DATE = {}", format_epoch(header.creation_date)).unwrap();
+    writeln!(out, "ORIGINATOR = {}", header.originator).unwrap();
+}
+
+fn write_metadata(out: &mut String, meta: &Metadata) -> () {
+    writeln!(out, "OBJECT_NAME = {}", meta.object_name).unwrap();
+    writeln!(out, "OBJECT_ID = {}", meta.object_id).unwrap();
+    writeln!(out, "CENTER_NAME = {}", meta.center_name).unwrap();
+    writeln!(out, "REF_FRAME = {}", meta.ref_frame).unwrap();
+    writeln!(out, "TIME_SYSTEM = {}", meta.time_system).unwrap();
+}
+
+/// A `KEYWORD = value [units]` line.
+#[derive(Clone,Copy,Debug)]
+struct Pair<'a> {
+    line: usize,
+    key: &'a str,
+    value: &'a str,
+    units: Option<&'a str>,
+}
+
+/// A line of a message that isn't blank or a comment.
+#[derive(Clone,Copy,Debug)]
+enum Line<'a> {
+    Pair(Pair<'a>),
+    /// A keyword on its own, like `META_START`.
+    Word(usize, &'a str),
+    /// A row of an ephemeris (or of a covariance matrix).
+    Data(usize, &'a str),
+}
+
+impl<'a> Line<'a> {
+    fn number(&self) -> usize {
+        match *self {
+            Line::Pair(Pair { line, .. }) |
+            Line::Word(line, _) |
+            Line::Data(line, _) => line,
+        }
+    }
+}
+
+/// Splits `text` into lines, leaving out blank lines and comments.
+fn tokenize(text: &str) -> Result<Vec<Line<'_>>, CcsdsError> {
+    let mut lines = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let n = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line == "COMMENT" || line.starts_with("COMMENT ") {
+            continue;
+        }
+        if let Some((key, rest)) = line.split_once('=') {
+            let rest = rest.trim();
+            let (value, units) = match rest.strip_suffix(']').and_then(|r| r.rsplit_once('[')) {
+                Some((value, units)) => (value.trim(), Some(units.trim())),
+                None => (rest, None),
+            };
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err(CcsdsError::BadLine { line: n });
+            }
+            lines.push(Line::Pair(Pair {
+                line: n,
+                key: key,
+                value: value,
+                units: units,
+            }));
+        } else if line.bytes().all(|b| b.is_ascii_uppercase() || b == b'_') {
+            lines.push(Line::Word(n, line));
+        } else {
+            lines.push(Line::Data(n, line));
+        }
+    }
+    Ok(lines)
+}
+
+/// Makes sure the first line is `version = ...`.
+fn check_version(version: &'static str, lines: &[Line]) -> Result<(), CcsdsError> {
+    match lines.first() {
+        Some(&Line::Pair(Pair { key, .. })) if key == version => Ok(()),
+        first => {
+            Err(CcsdsError::WrongMessage {
+                line: first.map_or(1, Line::number),
+                expected: version,
+            })
+        }
+    }
+}
+
+/// The keywords and values in a section of a message.
+struct Pairs<'a> {
+    map: HashMap<&'a str, Pair<'a>>,
+}
+
+impl<'a> Pairs<'a> {
+    fn new() -> Pairs<'a> {
+        Pairs { map: HashMap::new() }
+    }
+
+    /// Adds `pair` unless there's already a value for its keyword
+    /// (e.g. the second of several maneuvers).
+    fn insert(&mut self, pair: Pair<'a>) -> () {
+        self.map.entry(pair.key).or_insert(pair);
+    }
+
+    fn get(&self, key: &str) -> Option<&Pair<'a>> {
+        self.map.get(key)
+    }
+
+    fn text(&self, key: &'static str) -> Result<String, CcsdsError> {
+        self.get(key).map(|pair| pair.value.to_owned()).ok_or(CcsdsError::Missing { keyword: key })
+    }
+
+    /// Reads a number whose units (if they're given) have to be `units`.
+    fn number(&self, key: &'static str, units: &str) -> Result<f64, CcsdsError> {
+        let pair = self.get(key).ok_or(CcsdsError::Missing { keyword: key })?;
+        let bad = || {
+            CcsdsError::BadValue {
+                line: pair.line,
+                keyword: key.to_owned(),
+                text: pair.value.to_owned(),
+            }
+        };
+        if pair.units.is_some_and(|u| !u.eq_ignore_ascii_case(units)) {
+            return Err(CcsdsError::BadValue {
+                line: pair.line,
+                keyword: format!("{} in {}", key, units),
+                text: format!("{} [{}]", pair.value, pair.units.unwrap_or("")),
+            });
+        }
+        match pair.value.parse::<f64>() {
+            Ok(x) if x.is_finite() => Ok(x),
+            _ => Err(bad()),
+        }
+    }
+
+    fn date(&self, key: &'static str) -> Result<f64, CcsdsError> {
+        let pair = self.get(key).ok_or(CcsdsError::Missing { keyword: key })?;
+        parse_epoch(pair.value).ok_or_else(|| {
+            CcsdsError::BadValue {
+                line: pair.line,
+                keyword: key.to_owned(),
+                text: pair.value.to_owned(),
+            }
+        })
+    }
+
+    fn header(&self) -> Result<Header, CcsdsError> {
+        Ok(Header {
+            creation_date: self.date("CREATION_DATE")?,
+            originator: self.text("ORIGINATOR")?,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata, CcsdsError> {
+        Ok(Metadata {
+            object_name: self.text("OBJECT_NAME")?,
+            object_id: self.text("OBJECT_ID")?,
+            center_name: self.text("CENTER_NAME")?,
+            ref_frame: self.text("REF_FRAME")?,
+            time_system: self.text("TIME_SYSTEM")?,
+        })
+    }
+}
+
+/// Reads a row of an ephemeris: the epoch, position and velocity (and maybe acceleration).
+fn parse_state(n: usize, text: &str) -> Result<StateVector, CcsdsError> {
+    let bad = || {
+        CcsdsError::BadValue {
+            line: n,
+            keyword: "ephemeris row".to_owned(),
+            text: text.to_owned(),
+        }
+    };
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    if fields.len() != 7 && fields.len() != 10 {
+        return Err(bad());
+    }
+    let epoch = parse_epoch(fields[0]).ok_or_else(bad)?;
+    let mut numbers = [0.0; 6];
+    for (x, field) in numbers.iter_mut().zip(&fields[1..7]) {
+        *x = field.parse().ok().filter(|x: &f64| x.is_finite()).ok_or_else(bad)?;
+    }
+    Ok(StateVector {
+        epoch: epoch,
+        position: Vector3::new(numbers[0], numbers[1], numbers[2]),
+        velocity: Vector3::new(numbers[3], numbers[4], numbers[5]),
+    })
+}
@@ -0,0 +1,447 @@
+use na::Vector3;
+use serde_json;
+use tau::TAU;
+
+use orbit_sim::bodies::{self, EARTH, PRESETS};
+use orbit_sim::orbits::{self, Plane, PlanarTrajectory, Trajectory};
+
+/// The subcommands `main` knows about.
+pub const COMMANDS: &[&str] = &["elements", "propagate", "transfer", "period", "kepler", "help"];
+
+pub const USAGE: &str = "\
+Usage: orbit-sim COMMAND [OPTIONS]
+
+Does orbit calculations without opening any windows.
+Distances are in km, times in s, and angles in degrees.
+
+Commands:
+    elements   convert a state vector (--pos, --vel) to orbital elements
+    propagate  print the position and velocity along an orbit over time
+    transfer   compute a Hohmann transfer between two circular orbits
+    period     compute the period of an orbit
+    kepler     solve Kepler's equation for a given --eccentricity
+
+Options for every command:
+    --body NAME       which body is being orbited [default: Earth]
+    --mu KM3_S2       the standard gravitational parameter, instead of a body's
+    --format FORMAT   text, csv or json [default: text]
+
+Orbits (for propagate and period) are given by a state vector:
+    --pos X,Y,Z       the position, relative to the body's equator
+    --vel X,Y,Z       the velocity, in km/s
+or by elements:
+    --periapsis KM    the distance of the periapsis from the center
+    --semi-major-axis KM
+                      (instead of --periapsis, for closed orbits)
+    --eccentricity E  [default: 0]
+    --inclination DEG, --lan DEG (longitude of the ascending node),
+    --arg-peri DEG, --mean-anom DEG (where it is at time zero) [default: 0]
+
+propagate:
+    --start S         the first time to print [default: 0]
+    --end S           the last time to print [default: one period]
+    --step S          the time between rows [default: 60]
+
+transfer:
+    --from KM         the radius of the starting orbit
+    --to KM           the radius of the final orbit
+    --inclination DEG the change in inclination [default: 0]
+
+kepler takes one of:
+    --mean-anom DEG, --ecc-anom DEG (hyperbolic or parabolic anomaly for open
+    trajectories, not in degrees), or --true-anom DEG";
+
+/// How to print the results.
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+/// A named number in the output. The unit is part of the name.
+type Field = (&'static str, f64);
+
+/// The options shared by every command. Anything not known here is left for the command.
+struct Common {
+    mu: f64,
+    format: Format,
+    /// Every other option and its value.
+    options: Vec<(String, String)>,
+}
+
+impl Common {
+    fn parse(args: &[String]) -> Result<Common, String> {
+        let mut body = EARTH;
+        let mut mu = None;
+        let mut format = Format::Text;
+        let mut options = Vec::new();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            match arg.as_str() {
+                "--body" => {
+                    body = bodies::find(&PRESETS, value)
+                        .ok_or_else(|| format!("there's no body called {}", value))?;
+                }
+                "--mu" => mu = Some(parse_number(value)?),
+                "--format" => {
+                    format = match value.as_str() {
+                        "text" => Format::Text,
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        _ => {
+                            return Err(format!("unknown format: {} (try text, csv or json)",
+                                               value))
+                        }
+                    }
+                }
+                _ if arg.starts_with("--") => options.push((arg.clone(), value.clone())),
+                _ => return Err(format!("unknown argument: {}\n\n{}", arg, USAGE)),
+            }
+        }
+        let mu = mu.unwrap_or(PRESETS[body].mu);
+        if mu <= 0.0 {
+            return Err(format!("--mu must be positive: {}", mu));
+        }
+        Ok(Common {
+            mu: mu,
+            format: format,
+            options: options,
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev().find(|&(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    fn number(&self, name: &str) -> Result<Option<f64>, String> {
+        self.get(name).map(parse_number).transpose()
+    }
+
+    fn angle(&self, name: &str) -> Result<f64, String> {
+        Ok(self.number(name)?.unwrap_or(0.0).to_radians())
+    }
+
+    fn vector(&self, name: &str) -> Result<Option<Vector3<f64>>, String> {
+        self.get(name).map(parse_vector).transpose()
+    }
+
+    /// Complains about any options that `known` doesn't have.
+    fn check(&self, known: &[&str]) -> Result<(), String> {
+        match self.options.iter().find(|&(n, _)| !known.contains(&n.as_str())) {
+            Some((n, _)) => Err(format!("unknown argument: {}\n\n{}", n, USAGE)),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads an orbit from `--pos` and `--vel`, if they're given,
+    /// along with the mean anomaly there.
+    fn state_vector(&self) -> Result<Option<(Trajectory, f64)>, String> {
+        let (pos, vel) = match (self.vector("--pos")?, self.vector("--vel")?) {
+            (Some(pos), Some(vel)) => (pos, vel),
+            (None, None) => return Ok(None),
+            _ => return Err("--pos and --vel go together".to_owned()),
+        };
+        if pos.cross(&vel).norm() == 0.0 {
+            return Err("--pos and --vel are parallel, so there's no orbital plane".to_owned());
+        }
+        Ok(Some(Trajectory::from_state(self.mu, pos, vel)))
+    }
+
+    /// Reads an orbit from either a state vector or elements.
+    /// The trajectory starts at time zero.
+    fn trajectory(&self) -> Result<Trajectory, String> {
+        if let Some((mut traj, mean_anom)) = self.state_vector()? {
+            traj.t.mean_anom0 = mean_anom;
+            return Ok(traj);
+        }
+
+        let eccentr = self.number("--eccentricity")?.unwrap_or(0.0);
+        if eccentr < 0.0 {
+            return Err(format!("--eccentricity can't be negative: {}", eccentr));
+        }
+        let periapsis = match (self.number("--periapsis")?, self.number("--semi-major-axis")?) {
+            (Some(q), None) => q,
+            (None, Some(a)) if eccentr < 1.0 => a * (1.0 - eccentr),
+            (None, Some(_)) => {
+                return Err("--semi-major-axis only works for closed orbits; use --periapsis"
+                    .to_owned())
+            }
+            (None, None) => return Err("which orbit? (see `orbit-sim help`)".to_owned()),
+            (Some(_), Some(_)) => {
+                return Err("--periapsis and --semi-major-axis can't go together".to_owned())
+            }
+        };
+        if periapsis <= 0.0 {
+            return Err(format!("the periapsis must be above the center: {}", periapsis));
+        }
+        let mean_anom = self.angle("--mean-anom")?;
+        Ok(Trajectory {
+            p: Plane {
+                lon_asc_node: self.angle("--lan")?,
+                inclination: self.angle("--inclination")?,
+                arg_peri: self.angle("--arg-peri")?,
+            },
+            t: PlanarTrajectory {
+                periapsis: periapsis,
+                eccentr: eccentr,
+                mean_anom0: mean_anom,
+                mean_anom1: mean_anom + TAU,
+            },
+        })
+    }
+}
+
+const ORBIT_OPTIONS: &[&str] = &["--pos",
+                                 "--vel",
+                                 "--periapsis",
+                                 "--semi-major-axis",
+                                 "--eccentricity",
+                                 "--inclination",
+                                 "--lan",
+                                 "--arg-peri",
+                                 "--mean-anom"];
+
+/// Runs the command in `args[0]` with the rest of `args` as its options.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Err(USAGE.to_owned()),
+    };
+    if command == "help" || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let c = Common::parse(args)?;
+    let rows = match command {
+        "elements" => elements(&c)?,
+        "propagate" => propagate(&c)?,
+        "transfer" => transfer(&c)?,
+        "period" => period(&c)?,
+        "kepler" => kepler(&c)?,
+        _ => return Err(format!("unknown command: {}\n\n{}", command, USAGE)),
+    };
+    // only `propagate` makes a table; everything else is a single record
+    print!("{}", format_rows(&rows, c.format, command == "propagate"));
+    Ok(())
+}
+
+fn elements(c: &Common) -> Result<Vec<Vec<Field>>, String> {
+    c.check(&["--pos", "--vel"])?;
+    let (traj, mean_anom) = c.state_vector()?.ok_or("elements needs --pos and --vel")?;
+    let t = traj.t;
+    let apoapsis = t.apoapsis().map_or(f64::NAN, |apo| apo.norm());
+    Ok(vec![vec![("periapsis_km", t.periapsis),
+                 ("apoapsis_km", apoapsis),
+                 ("semi_major_axis_km", t.semi_major_axis()),
+                 ("eccentricity", t.eccentr),
+                 ("inclination_deg", traj.p.inclination.to_degrees()),
+                 ("lon_asc_node_deg", traj.p.lon_asc_node.to_degrees()),
+                 ("arg_peri_deg", traj.p.arg_peri.to_degrees()),
+                 ("mean_anom_deg", mean_anom.to_degrees()),
+                 ("true_anom_deg", t.true_anom(mean_anom).to_degrees()),
+                 ("period_s", t.period(c.mu).unwrap_or(f64::NAN))]])
+}
+
+fn propagate(c: &Common) -> Result<Vec<Vec<Field>>, String> {
+    c.check(&[ORBIT_OPTIONS, &["--start", "--end", "--step"]].concat())?;
+    let traj = c.trajectory()?;
+    let start = c.number("--start")?.unwrap_or(0.0);
+    let end = match (c.number("--end")?, traj.t.period(c.mu)) {
+        (Some(end), _) => end,
+        (None, Some(period)) => start + period,
+        (None, None) => return Err("open trajectories need an --end".to_owned()),
+    };
+    let step = c.number("--step")?.unwrap_or(60.0);
+    if step <= 0.0 {
+        return Err(format!("--step must be positive: {}", step));
+    }
+
+    let mut rows = Vec::new();
+    let mut i = 0;
+    loop {
+        let time = start + i as f64 * step;
+        if time > end {
+            break;
+        }
+        let state = traj.propagate(c.mu, 0.0, time);
+        let (pos, vel) = traj.state_at_true_anom(c.mu, state.true_anom);
+        rows.push(vec![("time_s", time),
+                       ("x_km", pos.x),
+                       ("y_km", pos.y),
+                       ("z_km", pos.z),
+                       ("vx_km_s", vel.x),
+                       ("vy_km_s", vel.y),
+                       ("vz_km_s", vel.z)]);
+        i += 1;
+    }
+    Ok(rows)
+}
+
+fn transfer(c: &Common) -> Result<Vec<Vec<Field>>, String> {
+    c.check(&["--from", "--to", "--inclination"])?;
+    let circle = |option: &str, inclination: f64| -> Result<Trajectory, String> {
+        let radius = c.number(option)?.ok_or_else(|| format!("transfer needs {}", option))?;
+        if radius <= 0.0 {
+            return Err(format!("{} must be positive: {}", option, radius));
+        }
+        Ok(Trajectory {
+            p: Plane { inclination: inclination, ..Plane::default() },
+            t: PlanarTrajectory {
+                periapsis: radius,
+                eccentr: 0.0,
+                mean_anom0: 0.0,
+                mean_anom1: TAU,
+            },
+        })
+    };
+    let from = circle("--from", 0.0)?;
+    let to = circle("--to", c.angle("--inclination")?)?;
+    let transfer = orbits::hohmann(c.mu, &from, &to)
+        .ok_or("there's no Hohmann transfer between those orbits")?;
+    Ok(vec![vec![("delta_v1_km_s", transfer.burns[0]),
+                 ("delta_v2_km_s", transfer.burns[1]),
+                 ("total_delta_v_km_s", transfer.total_delta_v()),
+                 ("time_s", transfer.time)]])
+}
+
+fn period(c: &Common) -> Result<Vec<Vec<Field>>, String> {
+    c.check(ORBIT_OPTIONS)?;
+    let traj = c.trajectory()?;
+    let period = traj.t.period(c.mu).ok_or("open trajectories don't have a period")?;
+    Ok(vec![vec![("period_s", period),
+                 ("mean_motion_rad_s", traj.t.mean_motion(c.mu)),
+                 ("semi_major_axis_km", traj.t.semi_major_axis())]])
+}
+
+fn kepler(c: &Common) -> Result<Vec<Vec<Field>>, String> {
+    c.check(&["--eccentricity", "--mean-anom", "--ecc-anom", "--true-anom"])?;
+    let e = c.number("--eccentricity")?.ok_or("kepler needs an --eccentricity")?;
+    if e < 0.0 {
+        return Err(format!("--eccentricity can't be negative: {}", e));
+    }
+    let t = PlanarTrajectory {
+        periapsis: 1.0,
+        eccentr: e,
+        mean_anom0: 0.0,
+        mean_anom1: TAU,
+    };
+    // the eccentric anomaly is an angle only for closed orbits
+    let (from_deg, to_deg) = if e < 1.0 { (TAU / 360.0, 360.0 / TAU) } else { (1.0, 1.0) };
+    let given = (c.number("--mean-anom")?, c.number("--ecc-anom")?, c.number("--true-anom")?);
+    let ecc_anom = match given {
+        (Some(m), None, None) => t.ecc_anom(m.to_radians()),
+        (None, Some(ecc_anom), None) => ecc_anom * from_deg,
+        (None, None, Some(v)) => t.ecc_anom(t.mean_anom(v.to_radians())),
+        _ => return Err("kepler needs one of --mean-anom, --ecc-anom or --true-anom".to_owned()),
+    };
+    let true_anom = t.true_anom_from_ecc(ecc_anom);
+    let mean_anom = if e < 1.0 {
+        orbits::kepler(e, ecc_anom)
+    } else if e == 1.0 {
+        orbits::barker(ecc_anom)
+    } else {
+        orbits::kepler_hyp(e, ecc_anom)
+    };
+    let ecc_anom_name = if e < 1.0 {
+        "ecc_anom_deg"
+    } else if e == 1.0 {
+        "tan_half_true_anom"
+    } else {
+        "hyp_anom"
+    };
+    Ok(vec![vec![("mean_anom_deg", mean_anom.to_degrees()),
+                 (ecc_anom_name, ecc_anom * to_deg),
+                 ("true_anom_deg", true_anom.to_degrees())]])
+}
+
+/// Formats a list of records. Missing (NaN or infinite) numbers are left blank.
+/// In text and JSON, a single record that isn't a `table` is printed on its own
+/// instead of as a list.
+fn format_rows(rows: &[Vec<Field>], format: Format, table: bool) -> String {
+    let names: Vec<&str> = rows.first().map_or(Vec::new(), |row| row.iter().map(|f| f.0).collect());
+    let mut out = String::new();
+    match format {
+        Format::Text if table => {
+            let line = |cells: Vec<String>| {
+                cells.iter().map(|s| format!("{:>16}", s)).collect::<Vec<_>>().join(" ")
+            };
+            out += &line(names.iter().map(|s| s.to_string()).collect());
+            out.push('\n');
+            for row in rows {
+                out += &line(row.iter().map(|f| format_text(f.1, Some(6))).collect());
+                out.push('\n');
+            }
+        }
+        Format::Text => {
+            let width = names.iter().map(|s| s.len()).max().unwrap_or(0);
+            for row in rows {
+                for &(name, value) in row {
+                    let value = format_text(value, None);
+                    out += &format!("{:width$}  {}\n", name, value, width = width);
+                }
+            }
+        }
+        Format::Csv => {
+            out += &names.join(",");
+            out.push('\n');
+            for row in rows {
+                let cells: Vec<String> = row.iter()
+                    .map(|&(_, x)| if x.is_finite() { x.to_string() } else { String::new() })
+                    .collect();
+                out += &cells.join(",");
+                out.push('\n');
+            }
+        }
+        Format::Json => {
+            let objects: Vec<String> = rows.iter()
+                .map(|row| {
+                    let fields: Vec<String> = row.iter()
+                        .map(|&(name, x)| {
+                            // serde_json writes non-finite numbers as null
+                            format!("\"{}\": {}", name, serde_json::to_string(&x).unwrap())
+                        })
+                        .collect();
+                    format!("{{{}}}", fields.join(", "))
+                })
+                .collect();
+            if table || objects.len() != 1 {
+                out += &format!("[\n  {}\n]\n", objects.join(",\n  "));
+            } else {
+                out += &objects[0];
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Formats `x` with `decimals` decimal places, or about ten significant digits.
+fn format_text(x: f64, decimals: Option<usize>) -> String {
+    if !x.is_finite() {
+        return "-".to_owned();
+    }
+    let decimals = decimals.unwrap_or_else(|| if x == 0.0 {
+        0
+    } else {
+        (9.0 - x.abs().log10().floor()).clamp(0.0, 20.0) as usize
+    });
+    format!("{:.*}", decimals, x)
+}
+
+fn parse_number(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(x) if x.is_finite() => Ok(x),
+        _ => Err(format!("not a number: {}", s)),
+    }
+}
+
+fn parse_vector(s: &str) -> Result<Vector3<f64>, String> {
+    let parts = s.split(',').map(|x| parse_number(x.trim())).collect::<Result<Vec<_>, _>>()?;
+    match parts.as_slice() {
+        &[x, y, z] => Ok(Vector3::new(x, y, z)),
+        _ => Err(format!("expected three numbers separated by ',': {}", s)),
+    }
+}
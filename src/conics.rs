@@ -1,5 +1,17 @@
-use na::{Affine2, Matrix3, Point2, Vector2, Transform2};
+//! The conic sections trajectories trace out, in the plane of the trajectory.
 
+use na::{Matrix3, Point2, Rotation2, Vector2, Transform2};
+
+/// Any of the conic sections a trajectory can trace out.
+#[derive(Clone,Copy,Debug)]
+pub enum Conic {
+    Ellipse(Ellipse),
+    Parabola(CanonicalParabolaRepr),
+    Hyperbola(CanonicalHyperbolaRepr),
+}
+
+
+/// An ellipse, in whichever form it was last computed in.
 #[derive(Clone,Copy,Debug)]
 pub enum Ellipse {
     Canonical(CanonicalEllipseRepr),
@@ -7,6 +19,7 @@ pub enum Ellipse {
 }
 
 impl Ellipse {
+    /// Makes a circle centered on the origin.
     pub fn new_circle(radius: f64) -> Ellipse {
         Ellipse::Canonical(CanonicalEllipseRepr {
             semi_axes: Vector2::new(radius, radius),
@@ -14,6 +27,7 @@ impl Ellipse {
             rotation: 0.0,
         })
     }
+    /// Converts to center, semi-axes, and rotation.
     pub fn to_canonical(self) -> CanonicalEllipseRepr {
         match self {
             Ellipse::Canonical(x) => x,
@@ -21,6 +35,7 @@ impl Ellipse {
         }
     }
 
+    /// Converts to the coefficients of the implicit equation.
     pub fn to_implicit(self) -> ImplicitConicSectionRepr {
         match self {
             Ellipse::Canonical(x) => x.to_implicit(),
@@ -28,6 +43,8 @@ impl Ellipse {
         }
     }
 
+    /// Applies an affine transformation,
+    /// or returns `None` if it isn't invertible.
     pub fn transform(self, a: &Transform2<f64>) -> Option<Ellipse> {
         a.try_inverse().map(|a| {
             let a = a.matrix();
@@ -38,12 +55,13 @@ impl Ellipse {
     }
 }
 
+/// The coefficients `(A, B, C, D, E, F)` of `Ax² + Bxy + Cy² + Dx + Ey + F = 0`.
 #[derive(Clone,Copy,Debug)]
 pub struct ImplicitConicSectionRepr(pub f64, pub f64, pub f64, pub f64, pub f64, pub f64);
 
 impl ImplicitConicSectionRepr {
     // TODO: Make this work for other conic sections
-    #[cfg_attr(rustfmt, rustfmt_skip)]
+    #[rustfmt::skip]
     fn to_canonical(self) -> CanonicalEllipseRepr {
         let ImplicitConicSectionRepr(pa, pb, pc, pd, pe, pf) = self;
         let discr = pb*pb - 4.0*pa*pc;
@@ -60,7 +78,7 @@ impl ImplicitConicSectionRepr {
         }
     }
 
-    #[cfg_attr(rustfmt, rustfmt_skip)]
+    #[rustfmt::skip]
     fn to_matrix(self) -> Matrix3<f64> {
         let ImplicitConicSectionRepr(pa, pb, pc, pd, pe, pf) = self;
         Matrix3::new(  pa  , pb/2.0, pd/2.0,
@@ -78,6 +96,7 @@ impl ImplicitConicSectionRepr {
     }
 }
 
+/// An ellipse given by its semi-axes, rotated about its center.
 #[derive(Clone,Copy,Debug)]
 pub struct CanonicalEllipseRepr {
     pub semi_axes: Vector2<f64>,
@@ -86,7 +105,7 @@ pub struct CanonicalEllipseRepr {
 }
 
 impl CanonicalEllipseRepr {
-    #[cfg_attr(rustfmt, rustfmt_skip)]
+    #[rustfmt::skip]
     fn to_implicit(self) -> ImplicitConicSectionRepr {
         let CanonicalEllipseRepr { semi_axes: ea, center: ec, rotation } = self;
         let (sint, cost) = rotation.sin_cos();
@@ -99,7 +118,8 @@ impl CanonicalEllipseRepr {
         ImplicitConicSectionRepr(pa, pb, pc, pd, pe, pf)
     }
 
-    fn point(&self, ecc_anom: f64) -> Point2<f64> {
+    /// Computes the point at eccentric anomaly `ecc_anom`. This ignores `rotation`.
+    pub fn point(&self, ecc_anom: f64) -> Point2<f64> {
         let (s, c) = ecc_anom.sin_cos();
         self.center + Vector2::new(self.semi_axes.x * c, self.semi_axes.y * s)
     }
@@ -114,3 +134,40 @@ impl Default for CanonicalEllipseRepr {
         }
     }
 }
+
+/// One branch of a hyperbola.
+#[derive(Clone,Copy,Debug)]
+pub struct CanonicalHyperbolaRepr {
+    /// The transverse and conjugate semi-axes.
+    pub semi_axes: Vector2<f64>,
+    pub center: Point2<f64>,
+    pub rotation: f64,
+}
+
+impl CanonicalHyperbolaRepr {
+    /// Computes a point on the branch that opens towards the rotated x-axis.
+    pub fn point(&self, hyp_anom: f64) -> Point2<f64> {
+        let v = Vector2::new(self.semi_axes.x * hyp_anom.cosh(),
+                             self.semi_axes.y * hyp_anom.sinh());
+        self.center + Rotation2::new(self.rotation) * v
+    }
+}
+
+/// A parabola given by its vertex and focal length.
+#[derive(Clone,Copy,Debug)]
+pub struct CanonicalParabolaRepr {
+    /// The distance from the vertex to the focus.
+    pub focal_length: f64,
+    pub vertex: Point2<f64>,
+    pub rotation: f64,
+}
+
+impl CanonicalParabolaRepr {
+    /// Computes a point on the parabola, which opens towards the rotated x-axis.
+    /// The focus is `focal_length` from the vertex along that axis (at the origin, for
+    /// trajectories), and `t` = ±1 gives the ends of the latus rectum through it.
+    pub fn point(&self, t: f64) -> Point2<f64> {
+        let v = Vector2::new(self.focal_length * t * t, 2.0 * self.focal_length * t);
+        self.vertex + Rotation2::new(self.rotation) * v
+    }
+}
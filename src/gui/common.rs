@@ -1,18 +1,69 @@
-use std::f64::NAN;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use na::Rotation3;
 use tau::TAU;
+use serde_json;
 
-use orbits::{Trajectory, PlanarTrajectory};
+use orbit_sim::orbits::{Trajectory, Transfer, Maneuver};
+use orbit_sim::bodies::{self, Body, Leg, DEFAULT_ALTITUDE};
+use orbit_sim::tle::{self, Tle};
+use orbit_sim::sgp4::{self, Sgp4, Sgp4Error};
+use orbit_sim::ccsds::{Message, Metadata, Oem, Opm, StateVector};
 
-pub const PLANET_RADIUS: f64 = 6371.0; // km
+pub const BODY_SCREEN_RADIUS: f64 = 160.0; // px (at the default zoom)
+const GROUND_TRACK_SAMPLES_PER_REV: f64 = 180.0;
+const EPHEMERIS_STATES_PER_REV: usize = 180;
+/// Seconds from the Unix epoch to J2000 (ignoring leap seconds).
+const UNIX_TO_J2000: f64 = 946728000.0;
 
-#[derive(Debug)]
+/// Everything about what's on screen.
+/// Only the parts that make sense to share end up in saved scenarios.
+#[derive(Debug,Serialize,Deserialize)]
+#[serde(default)]
 pub struct State {
+    #[serde(rename = "eye_lat_rad")]
     pub eye_lat: f64, // rad
+    #[serde(rename = "eye_lon_rad")]
     pub eye_lon: f64, // rad
+    #[serde(skip)]
     pub p_eye_lat: f64, // rad (used during a drag)
+    #[serde(skip)]
     pub p_eye_lon: f64, // rad
-    pub scale: f64, // km/px
-    pub trajectory: Trajectory,
+    #[serde(rename = "scale_px_per_km")]
+    pub scale: f64, // px/km
+    #[serde(skip)]
+    pub bodies: Vec<Body>,
+    #[serde(with = "preset_name")]
+    pub body: usize, // index into `bodies` of the one at the center
+    pub objects: Vec<SceneObject>,
+    pub selected: usize, // index into `objects` of the one being edited
+    #[serde(rename = "time_s")]
+    pub time: f64, // s (since each spacecraft was at its `mean_anom0`)
+    /// The date at time zero, which the first satellites imported from TLEs set.
+    #[serde(rename = "epoch_s", skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<f64>, // s since J2000 (UTC)
+    pub time_warp: f64,
+    #[serde(skip)]
+    pub playing: bool,
+    pub j2_drift: bool, // whether to show trajectories as they've drifted by `time`
+    pub sgp4: bool, // whether to move satellites from TLEs with SGP4
+    pub ground_track_revs: f64, // how far ahead to show ground tracks
+    #[serde(rename = "graticule_spacing_rad")]
+    pub graticule_spacing: f64, // rad (or zero for no graticule)
+    #[serde(rename = "sun_lat_rad")]
+    pub sun_lat: f64, // rad (the direction sunlight comes from)
+    #[serde(rename = "sun_lon_rad")]
+    pub sun_lon: f64, // rad
+    #[serde(skip)]
+    pub texture: Option<Texture>,
+    #[serde(skip)]
+    pub drag_handle: Option<ManeuverHandle>, // what's being dragged (if not the view)
+    #[serde(skip)]
+    pub p_maneuver: Maneuver, // (used during a drag)
 }
 
 impl Default for State {
@@ -20,21 +71,548 @@ impl Default for State {
         State {
             eye_lat: TAU / 16.0,
             eye_lon: 0.0,
-            p_eye_lat: NAN,
-            p_eye_lon: NAN,
-            scale: 0.025,
+            p_eye_lat: f64::NAN,
+            p_eye_lon: f64::NAN,
+            scale: BODY_SCREEN_RADIUS / Body::default().radius,
+            bodies: bodies::PRESETS.clone(),
+            body: bodies::EARTH,
+            objects: vec![SceneObject::new("Orbit 1".to_owned(), OBJECT_COLORS[0])],
+            selected: 0,
+            time: 0.0,
+            epoch: None,
+            time_warp: 60.0,
+            playing: false,
+            j2_drift: false,
+            sgp4: true,
+            ground_track_revs: 3.0,
+            graticule_spacing: TAU / 12.0,
+            sun_lat: 0.0,
+            sun_lon: 0.0,
+            texture: None,
+            drag_handle: None,
+            p_maneuver: Default::default(),
+        }
+    }
+}
+
+impl State {
+    /// Reads a scenario saved by `save`.
+    /// Anything missing from it is left as it is by default.
+    pub fn load(path: &Path) -> Result<State, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let st: State = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if st.objects.is_empty() {
+            return Err(format!("{}: there has to be at least one object", path.display()));
+        }
+        if st.selected >= st.objects.len() {
+            return Err(format!("{}: there's no object {} to select", path.display(), st.selected));
+        }
+        Ok(st)
+    }
+
+    /// Writes the shareable parts of this state to a scenario file.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self).map_err(|e| e.to_string())?;
+        writer.write_all(b"\n").map_err(|e| e.to_string())
+    }
+
+    pub fn selected(&self) -> &SceneObject {
+        &self.objects[self.selected]
+    }
+
+    pub fn selected_mut(&mut self) -> &mut SceneObject {
+        let i = self.selected;
+        &mut self.objects[i]
+    }
+
+    /// Adds an object in a low circular orbit and returns its index.
+    pub fn add_object(&mut self) -> usize {
+        let i = self.objects.len();
+        let color = OBJECT_COLORS[i % OBJECT_COLORS.len()];
+        let mut obj = SceneObject::new(format!("Orbit {}", i + 1), color);
+        obj.trajectory.t.periapsis = self.body().radius + DEFAULT_ALTITUDE;
+        self.objects.push(obj);
+        i
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.bodies[self.body]
+    }
+
+    /// Computes how far the body has turned (rad) at `time`. Once `epoch` is set, Earth starts
+    /// from its sidereal time then, so its longitudes are the usual ones (as far as its x-axis
+    /// is the vernal equinox).
+    pub fn rotation_angle(&self, time: f64) -> f64 {
+        let angle0 = match self.epoch {
+            Some(epoch) if self.body == bodies::EARTH => sgp4::gmst(epoch),
+            _ => 0.0,
+        };
+        angle0 + self.body().rotation_angle(time)
+    }
+
+    /// Computes the ground track of `obj` for the next `ground_track_revs` revolutions,
+    /// as the time and the latitude and longitude under the craft.
+    /// Only closed trajectories have ground tracks.
+    pub fn ground_track(&self, obj: &SceneObject) -> Vec<(f64, f64, f64)> {
+        let body = self.body();
+        match obj.trajectory.t.period(body.mu) {
+            Some(period) if self.ground_track_revs > 0.0 => {
+                let samples = (self.ground_track_revs * GROUND_TRACK_SAMPLES_PER_REV).ceil();
+                body.ground_track(&obj.trajectory,
+                                  self.rotation_angle(0.0),
+                                  self.time,
+                                  self.time + self.ground_track_revs * period,
+                                  samples as usize + 1)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns `obj` as it is at the current time (if J2 drift or SGP4 is on).
+    /// Its trajectory's mean anomalies are adjusted so that it still starts at `mean_anom0`
+    /// at time zero.
+    pub fn drifted(&self, obj: &SceneObject) -> SceneObject {
+        let mut obj = obj.clone();
+        if let Some(traj) = self.sgp4_trajectory(&obj) {
+            obj.trajectory = traj;
+        } else if self.j2_drift {
+            let body = self.body();
+            let traj = body.j2_drift(&obj.trajectory, self.time);
+            let shift = traj.t.mean_motion(body.mu) * self.time;
+            obj.trajectory = traj;
+            obj.trajectory.t.mean_anom0 -= shift;
+            obj.trajectory.t.mean_anom1 -= shift;
+        }
+        obj
+    }
+
+    /// Computes the osculating trajectory of `obj` at the current time with SGP4,
+    /// if it came from a TLE, SGP4 is on, and it's around Earth.
+    /// SGP4 already includes J2, so this replaces `drifted`'s drift.
+    fn sgp4_trajectory(&self, obj: &SceneObject) -> Option<Trajectory> {
+        let tle = obj.tle.as_ref().filter(|_| self.sgp4 && self.body == bodies::EARTH)?;
+        let since_epoch = self.epoch.unwrap_or(tle.epoch) + self.time - tle.epoch;
+        // (if it's decayed or otherwise gone wrong, it's left as a Keplerian orbit)
+        let (pos, vel) = obj.sgp4(tle, |sat| sat.propagate(since_epoch)).ok()?;
+        let mu = self.body().mu;
+        let (mut traj, mean_anom) = Trajectory::from_state(mu, pos, vel);
+        let mean_anom0 = mean_anom - traj.t.mean_motion(mu) * self.time;
+        traj.t.mean_anom0 = mean_anom0;
+        traj.t.mean_anom1 = mean_anom0 + TAU;
+        Some(traj)
+    }
+
+    /// Computes the legs `obj` flies from time zero until `end_time`, as it is at the current
+    /// time (see `drifted`). Looking for moons' spheres of influence takes a while,
+    /// so these are kept with `obj` until its trajectory or maneuver or the body changes.
+    pub fn legs(&self, obj: &SceneObject, end_time: f64) -> Vec<Leg> {
+        let now = self.drifted(obj);
+        let key = LegsKey {
+            body: self.body,
+            trajectory: now.trajectory,
+            maneuver: now.maneuver,
+            end_time: end_time,
+        };
+        if let Some((ref cached_key, ref legs)) = *obj.legs_cache.borrow() {
+            if *cached_key == key {
+                return legs.clone();
+            }
+        }
+        let legs = now.legs(&self.bodies, self.body, end_time);
+        *obj.legs_cache.borrow_mut() = Some((key, legs.clone()));
+        legs
+    }
+
+    /// Switches to orbiting the `i`th body, zooming so it looks the same size.
+    pub fn set_body(&mut self, i: usize) -> () {
+        self.body = i;
+        self.scale = BODY_SCREEN_RADIUS / self.body().radius;
+    }
+
+    /// Adds an object for each leg of `transfer` and returns the index of the first one.
+    pub fn add_transfer(&mut self, transfer: &Transfer) -> usize {
+        let first = self.objects.len();
+        let color = OBJECT_COLORS[first % OBJECT_COLORS.len()];
+        for (k, leg) in transfer.legs.iter().enumerate() {
+            let mut obj = SceneObject::new(format!("Transfer {} Leg {}", first + 1, k + 1), color);
+            obj.trajectory = *leg;
+            self.objects.push(obj);
+        }
+        first
+    }
+
+    /// Adds an object for each of `tles` around Earth (switching to it if needed)
+    /// and returns the index of the first one.
+    /// Unless an earlier import set `epoch`, the first one's epoch becomes time zero,
+    /// and the rest are moved along to match.
+    pub fn add_tles(&mut self, tles: &[Tle]) -> usize {
+        if self.body != bodies::EARTH {
+            self.set_body(bodies::EARTH);
+        }
+        let first = self.objects.len();
+        let epoch = match (self.epoch, tles.first()) {
+            (Some(epoch), _) => epoch,
+            (None, Some(tle)) => tle.epoch,
+            (None, None) => return first,
+        };
+        self.epoch = Some(epoch);
+        for (k, tle) in tles.iter().enumerate() {
+            let color = OBJECT_COLORS[(first + k) % OBJECT_COLORS.len()];
+            let name = tle.name
+                .clone()
+                .unwrap_or_else(|| format!("Satellite {}", tle.catalog_number));
+            let mut obj = SceneObject::new(name, color);
+            obj.trajectory = tle.trajectory();
+            let t = &mut obj.trajectory.t;
+            t.mean_anom0 = bodies::wrap_angle(t.mean_anom0 + tle.mean_motion * (epoch - tle.epoch));
+            t.mean_anom1 = t.mean_anom0 + TAU;
+            obj.tle = Some(tle.clone());
+            self.objects.push(obj);
+        }
+        first
+    }
+
+    /// Adds an object for the OPM or each segment of the OEM in `message`, starting at its
+    /// first state, around the body they're all around (switching to it if needed).
+    /// Returns the index of the first one.
+    /// Unless an earlier import set `epoch`, the first state's epoch becomes time zero.
+    /// The states have to be in UTC and in axes `ccsds_axes` knows.
+    pub fn add_ccsds(&mut self, message: &Message) -> Result<usize, String> {
+        let states = message.states();
+        let (center, first_state) = match states.first() {
+            Some(&(meta, state)) => (&meta.center_name, state),
+            None => return Err("there aren't any states".to_owned()),
+        };
+        if states.iter().any(|&(meta, _)| !meta.center_name.eq_ignore_ascii_case(center)) {
+            return Err("the states aren't all around the same body".to_owned());
+        }
+        let body = bodies::find(&self.bodies, center)
+            .ok_or_else(|| format!("there's no body called {}", center))?;
+        let mut axes = Vec::new();
+        for &(meta, _) in &states {
+            if !meta.time_system.eq_ignore_ascii_case("UTC") {
+                return Err(format!("{}'s times are in {}, not UTC",
+                                   meta.object_name,
+                                   meta.time_system));
+            }
+            let rotation = self.ccsds_axes(body, &meta.ref_frame).ok_or_else(|| {
+                format!("{}'s axes ({}) can't be used around {}",
+                        meta.object_name,
+                        meta.ref_frame,
+                        self.bodies[body].name)
+            })?;
+            axes.push(rotation.inverse());
+        }
+
+        if self.body != body {
+            self.set_body(body);
+        }
+        let mu = self.body().mu;
+        let epoch = *self.epoch.get_or_insert(first_state.epoch);
+        let first = self.objects.len();
+        for (k, (&(meta, state), rotation)) in states.iter().zip(&axes).enumerate() {
+            let color = OBJECT_COLORS[(first + k) % OBJECT_COLORS.len()];
+            let mut obj = SceneObject::new(meta.object_name.clone(), color);
+            obj.trajectory = rotate(rotation, state).trajectory(mu, epoch);
+            self.objects.push(obj);
+        }
+        Ok(first)
+    }
+
+    /// Describes `obj` as it is at the current time as an OPM.
+    pub fn to_opm(&self, obj: &SceneObject) -> Opm {
+        let epoch = self.epoch.unwrap_or(0.0);
+        let (meta, axes) = self.ccsds_metadata(obj);
+        let traj = self.drifted(obj).trajectory;
+        let mut opm = Opm::from_trajectory(meta, &traj, self.body().mu, epoch, epoch + self.time);
+        opm.state = rotate(&axes, opm.state);
+        opm.header.creation_date = now();
+        opm
+    }
+
+    /// Tabulates one revolution of `obj` from the current time as an OEM.
+    /// Only closed trajectories can be.
+    pub fn to_oem(&self, obj: &SceneObject) -> Result<Oem, String> {
+        let mu = self.body().mu;
+        let epoch = self.epoch.unwrap_or(0.0);
+        let traj = self.drifted(obj).trajectory;
+        let period = traj.t.period(mu).ok_or("only closed orbits can be tabulated")?;
+        let start = epoch + self.time;
+        let dates: Vec<f64> = (0..EPHEMERIS_STATES_PER_REV + 1)
+            .map(|i| start + period * i as f64 / EPHEMERIS_STATES_PER_REV as f64)
+            .collect();
+        let (meta, axes) = self.ccsds_metadata(obj);
+        let mut oem = Oem::from_trajectory(meta, &traj, mu, epoch, &dates);
+        for state in &mut oem.segments[0].states {
+            *state = rotate(&axes, *state);
+        }
+        oem.header.creation_date = now();
+        Ok(oem)
+    }
+
+    /// Says what `obj` is and what it's around for a CCSDS message, and returns the rotation
+    /// from the body's axes to the ones the message says its states are in.
+    fn ccsds_metadata(&self, obj: &SceneObject) -> (Metadata, Rotation3<f64>) {
+        let ref_frame = if self.sgp4_trajectory(obj).is_some() {
+            "TEME"
+        } else if self.body == bodies::EARTH {
+            "EME2000"
+        } else {
+            "ICRF"
+        };
+        let meta = Metadata {
+            object_name: obj.name.clone(),
+            object_id: obj.tle
+                .as_ref()
+                .and_then(Tle::object_id)
+                .unwrap_or_else(|| "UNKNOWN".to_owned()),
+            center_name: self.body().name.to_uppercase(),
+            ref_frame: ref_frame.to_owned(),
+            time_system: "UTC".to_owned(),
+        };
+        let axes = self.ccsds_axes(self.body, ref_frame).unwrap();
+        (meta, axes)
+    }
+
+    /// Finds the rotation from the `body`th body's axes to the ones CCSDS calls `ref_frame`,
+    /// if it's one that can be used around that body. The bodies' equators are oriented relative
+    /// to the ICRF, which EME2000 and GCRF are close enough to. SGP4's TEME is taken to be
+    /// Earth's own axes, like it is for satellites from TLEs.
+    fn ccsds_axes(&self, body: usize, ref_frame: &str) -> Option<Rotation3<f64>> {
+        match &*ref_frame.to_uppercase() {
+            "ICRF" | "EME2000" | "GCRF" => Some(self.bodies[body].equator()),
+            "TEME" if body == bodies::EARTH => Some(Rotation3::identity()),
+            _ => None,
+        }
+    }
+}
+
+/// Turns the position and velocity in `state` by `rotation`.
+fn rotate(rotation: &Rotation3<f64>, state: StateVector) -> StateVector {
+    StateVector {
+        position: rotation * state.position,
+        velocity: rotation * state.velocity,
+        ..state
+    }
+}
+
+/// Returns the current date (s since J2000).
+fn now() -> f64 {
+    let since_unix = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_unix.as_secs_f64() - UNIX_TO_J2000
+}
+
+/// Reads an OPM or OEM from a file.
+pub fn load_ccsds(path: &Path) -> Result<Message, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Message::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Writes an OPM or OEM (in KVN format) to a file.
+pub fn save_ccsds(path: &Path, message: &Message) -> Result<(), String> {
+    let text = match *message {
+        Message::Opm(ref opm) => opm.to_kvn(),
+        Message::Oem(ref oem) => oem.to_kvn(),
+    };
+    fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Reads the TLEs in a file, which has to have at least one.
+pub fn load_tles(path: &Path) -> Result<Vec<Tle>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match tle::parse_all(&text) {
+        Ok(ref tles) if tles.is_empty() => {
+            Err(format!("{}: there aren't any element sets", path.display()))
+        }
+        Ok(tles) => Ok(tles),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+pub const OBJECT_COLORS: [(f64, f64, f64); 6] = [(1.0, 0.0, 0.0),
+                                                 (1.0, 0.0, 1.0),
+                                                 (1.0, 1.0, 1.0),
+                                                 (0.5, 0.5, 1.0),
+                                                 (1.0, 0.5, 0.5),
+                                                 (0.6, 1.0, 0.2)];
+
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct SceneObject {
+    pub name: String,
+    pub color: (f64, f64, f64), // rgb
+    pub visible: bool,
+    pub trajectory: Trajectory,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maneuver: Option<Maneuver>,
+    /// The elements this came from, for SGP4 (if it's a satellite from a TLE).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tle: Option<Tle>,
+    /// The legs `State::legs` last worked out for this, and what from.
+    #[serde(skip)]
+    legs_cache: RefCell<Option<(LegsKey, Vec<Leg>)>>,
+    /// SGP4 set up for `tle`, and which TLE it was set up for.
+    #[serde(skip)]
+    sgp4_cache: RefCell<Option<(Tle, Result<Sgp4, Sgp4Error>)>>,
+}
+
+/// Everything the legs an object flies depend on (besides the bodies, which don't change).
+#[derive(Clone,Debug,PartialEq)]
+struct LegsKey {
+    body: usize,
+    trajectory: Trajectory,
+    maneuver: Option<Maneuver>,
+    end_time: f64, // s
+}
+
+impl SceneObject {
+    pub fn new(name: String, color: (f64, f64, f64)) -> SceneObject {
+        SceneObject {
+            name: name,
+            color: color,
+            visible: true,
             trajectory: Trajectory::default(),
+            maneuver: None,
+            tle: None,
+            legs_cache: RefCell::new(None),
+            sgp4_cache: RefCell::new(None),
+        }
+    }
+
+    /// Calls `f` with SGP4 set up for `tle`, which is kept with this object so it only has to be
+    /// set up again when the TLE changes.
+    fn sgp4<T, F>(&self, tle: &Tle, f: F) -> Result<T, Sgp4Error>
+        where F: FnOnce(&Sgp4) -> Result<T, Sgp4Error>
+    {
+        let mut cache = self.sgp4_cache.borrow_mut();
+        if cache.as_ref().is_some_and(|(cached_tle, _)| cached_tle != tle) {
+            *cache = None;
         }
+        match cache.get_or_insert_with(|| (tle.clone(), Sgp4::new(tle))).1 {
+            Ok(ref sat) => f(sat),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Computes when this object gets to its maneuver (if it has one and ever gets there),
+    /// given a standard gravitational parameter `mu`.
+    pub fn burn_time(&self, mu: f64) -> Option<f64> {
+        let t = &self.trajectory.t;
+        self.maneuver
+            .map(|man| {
+                let burn_time = (man.mean_anom - t.mean_anom0) / t.mean_motion(mu);
+                t.period(mu).map_or(burn_time, |period| burn_time.rem_euclid(period))
+            })
+            .filter(|&burn_time| burn_time >= 0.0)
+    }
+
+    /// Computes the legs this object flies around the `body`th of `bodies` (and whatever
+    /// it goes to after) until `end_time`, switching to the planned trajectory at the maneuver.
+    /// This object is at its `mean_anom0` at time zero.
+    pub fn legs(&self, bodies: &[Body], body: usize, end_time: f64) -> Vec<Leg> {
+        let mu = bodies[body].mu;
+        if let (Some(man), Some(burn_time)) = (self.maneuver, self.burn_time(mu)) {
+            if burn_time < end_time {
+                let mut legs = bodies::propagate_patched(bodies, body, &self.trajectory, 0.0,
+                                                         burn_time);
+                // the maneuver only happens if we're still around `body` by then
+                if legs.len() == 1 {
+                    let planned = man.apply(mu, &self.trajectory);
+                    legs.extend(bodies::propagate_patched(bodies, body, &planned, burn_time,
+                                                          end_time));
+                    return legs;
+                }
+            }
+        }
+        bodies::propagate_patched(bodies, body, &self.trajectory, 0.0, end_time)
+    }
+}
+
+/// Saves which of `bodies::PRESETS` is at the center by its name.
+mod preset_name {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    use orbit_sim::bodies::{self, PRESETS};
+
+    pub fn serialize<S: Serializer>(body: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&PRESETS[*body].name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        bodies::find(&PRESETS, &name)
+            .ok_or_else(|| D::Error::custom(format!("there's no body called {}", name)))
     }
 }
 
-impl Default for PlanarTrajectory {
-    fn default() -> Self {
-        PlanarTrajectory {
-            periapsis: PLANET_RADIUS + 200.0,
-            eccentr: 0.0,
-            mean_anom0: 0.0,
-            mean_anom1: TAU,
+/// An equirectangular map of a body's surface.
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<(u8, u8, u8)>, // rgb, row by row from the north pole
+}
+
+impl Texture {
+    /// Looks up the color (rgb) at the given latitude and longitude (rad).
+    pub fn sample(&self, lat: f64, lon: f64) -> (f64, f64, f64) {
+        let x = ((lon / TAU + 0.5).rem_euclid(1.0) * self.width as f64) as usize;
+        let y = ((0.5 - lat / (TAU / 2.0)) * self.height as f64) as usize;
+        let (r, g, b) = self.pixels[y.min(self.height - 1) * self.width + x.min(self.width - 1)];
+        (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0)
+    }
+}
+
+impl fmt::Debug for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Texture({}x{})", self.width, self.height)
+    }
+}
+
+/// A part of a maneuver node that can be dragged around.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ManeuverHandle {
+    /// Moves the node along the trajectory.
+    Node,
+    Prograde,
+    Retrograde,
+    Normal,
+    AntiNormal,
+    RadialOut,
+    RadialIn,
+}
+
+impl ManeuverHandle {
+    pub const DIRECTIONS: [ManeuverHandle; 6] = [ManeuverHandle::Prograde,
+                                                 ManeuverHandle::Retrograde,
+                                                 ManeuverHandle::Normal,
+                                                 ManeuverHandle::AntiNormal,
+                                                 ManeuverHandle::RadialOut,
+                                                 ManeuverHandle::RadialIn];
+
+    /// Returns which of the directions from `Maneuver::frame` this handle pulls along
+    /// and which way, or `None` for `Node`.
+    pub fn axis(self) -> Option<(usize, f64)> {
+        match self {
+            ManeuverHandle::Node => None,
+            ManeuverHandle::Prograde => Some((0, 1.0)),
+            ManeuverHandle::Retrograde => Some((0, -1.0)),
+            ManeuverHandle::Normal => Some((1, 1.0)),
+            ManeuverHandle::AntiNormal => Some((1, -1.0)),
+            ManeuverHandle::RadialOut => Some((2, 1.0)),
+            ManeuverHandle::RadialIn => Some((2, -1.0)),
+        }
+    }
+
+    /// Returns the delta-v component of `man` that this handle changes.
+    pub fn component_mut(self, man: &mut Maneuver) -> Option<&mut f64> {
+        match self.axis() {
+            Some((0, _)) => Some(&mut man.prograde),
+            Some((1, _)) => Some(&mut man.normal),
+            Some((2, _)) => Some(&mut man.radial),
+            _ => None,
         }
     }
 }
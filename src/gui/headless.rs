@@ -0,0 +1,234 @@
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::fs::File;
+use std::os::raw::{c_char, c_double};
+use std::path::Path;
+use std::rc::Rc;
+
+use cairo::{Context, Format, ImageSurface, PDFSurface, Status, Surface};
+use cairo::prelude::*;
+use cairo_sys;
+
+use orbit_sim::bodies;
+use gui;
+use gui::common::*;
+use gui::render;
+
+const DEFAULT_WIDTH: f64 = 800.0;
+const DEFAULT_HEIGHT: f64 = 600.0;
+
+pub const USAGE: &str = "\
+Usage: orbit-sim [--scenario FILE] [--output FILE] [OPTIONS]
+
+This opens the viewer, or with --output, draws a picture without opening any
+windows instead.
+
+Options:
+    --scenario FILE   a scenario saved from the viewer to start with
+    --tle FILE        add the satellites in a file of two-line element sets
+    --ccsds FILE      add the objects in a CCSDS OPM or OEM
+    --output FILE     where to write the picture (.png, .svg or .pdf)
+    --size WxH        how big the picture is, in px (or pt) [default: 800x600]
+    --eye LAT,LON     where the camera is looking from, in degrees
+    --zoom PX_PER_KM  how big things are
+    --body NAME       which body is in the middle [default: Earth]
+    --time SECONDS    how far into the simulation to draw
+    --map             draw the ground track map instead of the 3D view
+
+Run `orbit-sim help` for the commands that do calculations instead.";
+
+/// Which of the viewer's drawings to make.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum View {
+    Orbits,
+    Map,
+}
+
+/// The kinds of file we can draw into.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+    Pdf,
+}
+
+impl ImageFormat {
+    /// Guesses the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let ext = path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase);
+        match ext.as_deref() {
+            Some("png") => Some(ImageFormat::Png),
+            Some("svg") => Some(ImageFormat::Svg),
+            Some("pdf") => Some(ImageFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+/// Draws `view` of `st` into a `width` by `height` (px, or pt for vector formats) file at `path`.
+pub fn render_to_file(st: &State,
+                      view: View,
+                      path: &Path,
+                      format: ImageFormat,
+                      width: f64,
+                      height: f64)
+                      -> Result<(), String> {
+    let draw = |ctx: &Context| match view {
+        View::Orbits => render::draw(ctx, st),
+        View::Map => render::draw_map(ctx, st),
+    };
+    match format {
+        ImageFormat::Png => {
+            let surface = ImageSurface::create(Format::ARgb32, width as i32, height as i32)
+                .map_err(|e| format!("couldn't make a {}x{} image: {:?}", width, height, e))?;
+            draw(&Context::new(&surface));
+            let mut file = File::create(path).map_err(|e| e.to_string())?;
+            surface.write_to_png(&mut file).map_err(|e| format!("{:?}", e))
+        }
+        ImageFormat::Svg => {
+            let surface = svg_surface_create(path, width, height)?;
+            draw(&Context::new(&surface));
+            finish(&surface)
+        }
+        ImageFormat::Pdf => {
+            let surface = PDFSurface::create(path, width, height);
+            draw(&Context::new(&surface));
+            finish(&surface)
+        }
+    }
+}
+
+/// Something on the command line that changes the scenario, once it's loaded.
+enum Change<'a> {
+    Tle(&'a str),
+    Ccsds(&'a str),
+    Eye(f64, f64),
+    Body(&'a str),
+    Time(f64),
+}
+
+/// Parses the command line arguments (not including the program name)
+/// and either draws a picture like they say or opens the viewer.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut scenario = None;
+    let mut changes = Vec::new();
+    let mut view = View::Orbits;
+    let mut output = None;
+    let (mut width, mut height) = (DEFAULT_WIDTH, DEFAULT_HEIGHT);
+    let mut zoom = None;
+
+    // everything gets parsed before any files are read, so `--help` always works
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--scenario" => scenario = Some(value()?),
+            "--tle" => changes.push(Change::Tle(value()?)),
+            "--ccsds" => changes.push(Change::Ccsds(value()?)),
+            "--output" => output = Some(value()?.clone()),
+            "--size" => {
+                let (w, h) = parse_pair(value()?, 'x')?;
+                width = w;
+                height = h;
+            }
+            "--eye" => {
+                let (lat, lon) = parse_pair(value()?, ',')?;
+                changes.push(Change::Eye(lat, lon));
+            }
+            "--zoom" => zoom = Some(parse_number(value()?)?),
+            "--body" => changes.push(Change::Body(value()?)),
+            "--time" => changes.push(Change::Time(parse_number(value()?)?)),
+            "--map" => view = View::Map,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(format!("unknown argument: {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    // the scenario goes first so everything else can change it
+    let mut st = match scenario {
+        Some(path) => State::load(Path::new(path))?,
+        None => State::default(),
+    };
+    for change in changes {
+        match change {
+            Change::Tle(path) => {
+                let tles = load_tles(Path::new(path))?;
+                st.add_tles(&tles);
+            }
+            Change::Ccsds(path) => {
+                let message = load_ccsds(Path::new(path))?;
+                st.add_ccsds(&message)?;
+            }
+            Change::Eye(lat, lon) => {
+                st.eye_lat = lat.to_radians();
+                st.eye_lon = lon.to_radians();
+            }
+            Change::Body(name) => {
+                let i = bodies::find(&st.bodies, name)
+                    .ok_or_else(|| format!("there's no body called {}", name))?;
+                st.set_body(i);
+            }
+            Change::Time(time) => st.time = time,
+        }
+    }
+    // after `--body`, which resets the zoom
+    if let Some(zoom) = zoom {
+        st.scale = zoom;
+    }
+
+    let output = match output {
+        Some(output) => output,
+        None => {
+            gui::main(Rc::new(RefCell::new(st)));
+            return Ok(());
+        }
+    };
+    let path = Path::new(&output);
+    let format = ImageFormat::from_path(path)
+        .ok_or_else(|| format!("don't know how to write {} (try .png, .svg or .pdf)", output))?;
+    render_to_file(&st, view, path, format, width, height)
+}
+
+fn parse_number(s: &str) -> Result<f64, String> {
+    s.parse().map_err(|_| format!("not a number: {}", s))
+}
+
+fn parse_pair(s: &str, sep: char) -> Result<(f64, f64), String> {
+    let mut parts = s.splitn(2, sep);
+    match (parts.next(), parts.next()) {
+        (Some(a), Some(b)) => Ok((parse_number(a.trim())?, parse_number(b.trim())?)),
+        _ => Err(format!("expected two numbers separated by '{}': {}", sep, s)),
+    }
+}
+
+/// Writes out everything drawn on a vector surface and checks that it worked.
+fn finish<T: AsRef<Surface>>(surface: &T) -> Result<(), String> {
+    surface.finish();
+    match surface.as_ref().status() {
+        Status::Success => Ok(()),
+        status => Err(format!("couldn't write the file: {:?}", status)),
+    }
+}
+
+// the version of cairo-rs we use doesn't have SVG surfaces yet
+extern "C" {
+    fn cairo_svg_surface_create(filename: *const c_char,
+                                width_in_points: c_double,
+                                height_in_points: c_double)
+                                -> *mut cairo_sys::cairo_surface_t;
+}
+
+fn svg_surface_create(path: &Path, width: f64, height: f64) -> Result<Surface, String> {
+    let filename = CString::new(path.to_string_lossy().into_owned())
+        .map_err(|_| format!("bad file name: {}", path.display()))?;
+    let surface = unsafe {
+        Surface::from_raw_full(cairo_svg_surface_create(filename.as_ptr(), width, height))
+    };
+    match surface.status() {
+        Status::Success => Ok(surface),
+        status => Err(format!("couldn't make an SVG: {:?}", status)),
+    }
+}
@@ -1,10 +1,15 @@
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::f64::NAN;
+use std::path::PathBuf;
+use std::time::Instant;
 use tau::TAU;
 use gtk;
+use gdk;
 use gtk::prelude::*;
+use na::{Point2, Vector2};
 
+use orbit_sim::orbits;
+use orbit_sim::ccsds::Message;
 use gui::common::*;
 use gui::render;
 
@@ -49,7 +54,44 @@ macro_rules! setup_spinbutton {
     }
 }
 
+/// Like `setup_spinbutton!`, but for a field of whichever object is selected.
+/// This also adds a closure to `$refreshers` that updates the spin button
+/// for when the selection changes.
+macro_rules! setup_object_spinbutton {
+    ($drawing:ident, $refreshers:ident,
+     $min:tt to $max:tt by $incr:tt;
+     $spin_btn:ident -> $state:ident.selected $(.$field:ident)*
+    ) => {
+        $spin_btn.set_range($min, $max);
+        $spin_btn.set_increments($incr, 0.0);
+        setup_object_spinbutton!($drawing, $refreshers; $spin_btn -> $state.selected$(.$field)*);
+    };
+    ($drawing:ident, $refreshers:ident;
+     $spin_btn:ident -> $state:ident.selected $(.$field:ident)*
+    ) => {
+        $refreshers.push(Box::new(cloning!($state, $spin_btn => move || {
+            let value = $state.borrow().selected()$(.$field)*;
+            $spin_btn.set_value(value);
+        })));
+        $spin_btn.connect_value_changed(cloning!($state, $drawing, $spin_btn => move |_| {
+            let mut state = $state.borrow_mut();
+            let obj = state.selected_mut();
+            let value = $spin_btn.get_value();
+            // once it's been edited, it's not the satellite from the TLE anymore
+            // (but the refreshers write back the value it already had)
+            if obj$(.$field)* != value {
+                obj$(.$field)* = value;
+                obj.tle = None;
+            }
+            drop(state);
+            $drawing.queue_draw();
+        }));
+    }
+}
+
 const DRAG_TURN_RATE: f64 = 0.01; // rad/px
+const FRAME_INTERVAL: u32 = 16; // ms
+const DRAG_DELTA_V_RATE: f64 = 0.005; // km/s/px
 
 pub fn main(state: Rc<RefCell<State>>) {
     gtk::init().expect("Failed to initialize GTK.");
@@ -57,14 +99,47 @@ pub fn main(state: Rc<RefCell<State>>) {
     let builder = gtk::Builder::new_from_string(include_str!("layout.glade"));
     get_objects_from_builder!(builder,
                               window: gtk::Window,
+                              open_item: gtk::MenuItem,
+                              save_item: gtk::MenuItem,
+                              import_tle_item: gtk::MenuItem,
+                              import_ccsds_item: gtk::MenuItem,
+                              export_opm_item: gtk::MenuItem,
+                              export_oem_item: gtk::MenuItem,
+                              scenario_filter: gtk::FileFilter,
+                              tle_filter: gtk::FileFilter,
+                              ccsds_filter: gtk::FileFilter,
                               drawing: gtk::DrawingArea,
+                              map_drawing: gtk::DrawingArea,
                               pe_entry: gtk::SpinButton,
                               ec_entry: gtk::SpinButton,
                               ar_entry: gtk::SpinButton,
                               in_entry: gtk::SpinButton,
-                              an_entry: gtk::SpinButton);
-                              // ma0_entry: gtk::SpinButton,
-                              // ma1_entry: gtk::SpinButton);
+                              an_entry: gtk::SpinButton,
+                              ma0_entry: gtk::SpinButton,
+                              ma1_entry: gtk::SpinButton,
+                              time_scale: gtk::Scale,
+                              warp_entry: gtk::SpinButton,
+                              play_btn: gtk::ToggleButton,
+                              drift_btn: gtk::CheckButton,
+                              sgp4_btn: gtk::CheckButton,
+                              gt_entry: gtk::SpinButton,
+                              grat_entry: gtk::SpinButton,
+                              sun_lon_entry: gtk::SpinButton,
+                              sun_lat_entry: gtk::SpinButton,
+                              texture_btn: gtk::FileChooserButton,
+                              texture_clear_btn: gtk::Button,
+                              texture_info: gtk::Label,
+                              objects_list: gtk::ListBox,
+                              add_btn: gtk::Button,
+                              remove_btn: gtk::Button,
+                              target_combo: gtk::ComboBoxText,
+                              bi_apo_entry: gtk::SpinButton,
+                              hohmann_btn: gtk::Button,
+                              bi_elliptic_btn: gtk::Button,
+                              transfer_info: gtk::Label,
+                              maneuver_btn: gtk::CheckButton,
+                              maneuver_info: gtk::Label,
+                              body_combo: gtk::ComboBoxText);
 
     window.connect_delete_event(|_, _| {
         gtk::main_quit();
@@ -78,39 +153,411 @@ pub fn main(state: Rc<RefCell<State>>) {
         Inhibit(false)
     }));
 
+    map_drawing.connect_draw(cloning!(state => move |_, ctx| {
+        let state = state.borrow();
+        render::draw_map(ctx, &state);
+        drop(state);
+        Inhibit(false)
+    }));
+
+    let mut refreshers: Vec<Box<dyn Fn()>> = Vec::new();
+    setup_object_spinbutton!(drawing, refreshers;
+                             pe_entry -> state.selected.trajectory.t.periapsis);
+    setup_object_spinbutton!(drawing, refreshers;
+                             ec_entry -> state.selected.trajectory.t.eccentr);
+    setup_object_spinbutton!(drawing, refreshers, (-TAU) to (TAU) by (TAU/60.0);
+                             ar_entry -> state.selected.trajectory.p.arg_peri);
+    setup_object_spinbutton!(drawing, refreshers, (-TAU) to (TAU) by (TAU/60.0);
+                             in_entry -> state.selected.trajectory.p.inclination);
+    setup_object_spinbutton!(drawing, refreshers, (-TAU) to (TAU) by (TAU/60.0);
+                             an_entry -> state.selected.trajectory.p.lon_asc_node);
+    setup_object_spinbutton!(drawing, refreshers, (-2.0*TAU) to (2.0*TAU) by (TAU/60.0);
+                             ma0_entry -> state.selected.trajectory.t.mean_anom0);
+    setup_object_spinbutton!(drawing, refreshers, (-2.0*TAU) to (2.0*TAU) by (TAU/60.0);
+                             ma1_entry -> state.selected.trajectory.t.mean_anom1);
+    refreshers.push(Box::new(cloning!(state, maneuver_btn, maneuver_info => move || {
+        let state = state.borrow();
+        let has_maneuver = state.selected().maneuver.is_some();
+        maneuver_info.set_text(&maneuver_text(state.selected()));
+        drop(state);
+        maneuver_btn.set_active(has_maneuver);
+    })));
+    let refresh_entries = Rc::new(move || for refresh in &refreshers {
+        refresh();
+    });
+    refresh_entries();
+
+    // side panel
+    let st = state.borrow();
+    for body in &st.bodies {
+        body_combo.append_text(&body.name);
+    }
+    body_combo.set_active(st.body as i32);
+    drop(st);
+    body_combo.connect_changed(cloning!(state, drawing => move |body_combo| {
+        let i = body_combo.get_active();
+        // (this also gets called when a scenario is opened, which sets the zoom itself)
+        if i >= 0 && i as usize != state.borrow().body {
+            state.borrow_mut().set_body(i as usize);
+            drawing.queue_draw();
+        }
+    }));
+    let num_objects = state.borrow().objects.len();
+    for i in 0..num_objects {
+        objects_list.insert(&object_row(&state, &drawing, i), -1);
+    }
+    objects_list.select_row(objects_list.get_row_at_index(0).as_ref());
+    refresh_targets(&state, &target_combo);
+    objects_list.connect_row_selected(cloning!(state, refresh_entries => move |_, row| {
+        // this gets called with `None` while a row is being removed
+        if let Some(ref row) = *row {
+            state.borrow_mut().selected = row.get_index() as usize;
+            refresh_entries();
+        }
+    }));
+    // adds rows for the objects from index `first` on and selects the first of them
+    let add_rows = Rc::new(cloning!(state, drawing, objects_list, target_combo =>
+                                    move |first: usize| {
+        let num_objects = state.borrow().objects.len();
+        for i in first..num_objects {
+            let row = object_row(&state, &drawing, i);
+            objects_list.insert(&row, -1);
+            row.show_all();
+        }
+        objects_list.select_row(objects_list.get_row_at_index(first as i32).as_ref());
+        refresh_targets(&state, &target_combo);
+        drawing.queue_draw();
+    }));
+    add_btn.connect_clicked(cloning!(state, add_rows => move |_| {
+        let i = state.borrow_mut().add_object();
+        add_rows(i);
+    }));
+    remove_btn.connect_clicked(cloning!(state, drawing, objects_list, target_combo => move |_| {
+        let mut st = state.borrow_mut();
+        if st.objects.len() <= 1 {
+            return;
+        }
+        let i = st.selected;
+        st.objects.remove(i);
+        st.selected = i.min(st.objects.len() - 1);
+        let selected = st.selected;
+        drop(st);
+        if let Some(row) = objects_list.get_row_at_index(i as i32) {
+            row.destroy();
+        }
+        objects_list.select_row(objects_list.get_row_at_index(selected as i32).as_ref());
+        refresh_targets(&state, &target_combo);
+        drawing.queue_draw();
+    }));
+
+    // transfers
+    hohmann_btn.connect_clicked(cloning!(state, add_rows, target_combo, transfer_info => move |_| {
+        let st = state.borrow();
+        let target = target_combo.get_active();
+        if target < 0 {
+            return;
+        }
+        let transfer = orbits::hohmann(st.body().mu,
+                                       &st.selected().trajectory,
+                                       &st.objects[target as usize].trajectory);
+        drop(st);
+        add_transfer(&state, &*add_rows, &transfer_info, transfer);
+    }));
+    bi_elliptic_btn.connect_clicked(cloning!(state, add_rows, target_combo, transfer_info,
+                                             bi_apo_entry => move |_| {
+        let st = state.borrow();
+        let target = target_combo.get_active();
+        if target < 0 {
+            return;
+        }
+        let transfer = orbits::bi_elliptic(st.body().mu,
+                                           &st.selected().trajectory,
+                                           &st.objects[target as usize].trajectory,
+                                           bi_apo_entry.get_value());
+        drop(st);
+        add_transfer(&state, &*add_rows, &transfer_info, transfer);
+    }));
+
+    maneuver_btn.connect_toggled(cloning!(state, drawing, maneuver_info => move |maneuver_btn| {
+        let mut state = state.borrow_mut();
+        let obj = state.selected_mut();
+        if !maneuver_btn.get_active() {
+            obj.maneuver = None;
+        } else if obj.maneuver.is_none() {
+            obj.maneuver = Some(orbits::Maneuver {
+                mean_anom: obj.trajectory.t.mean_anom0,
+                ..Default::default()
+            });
+        }
+        maneuver_info.set_text(&maneuver_text(obj));
+        drop(state);
+        drawing.queue_draw();
+    }));
+
+    setup_spinbutton!(drawing;
+                      time_scale -> state.time);
     setup_spinbutton!(drawing;
-                      pe_entry -> state.trajectory.t.periapsis);
+                      warp_entry -> state.time_warp);
+    setup_spinbutton!(drawing;
+                      gt_entry -> state.ground_track_revs);
+
     setup_spinbutton!(drawing;
-                      ec_entry -> state.trajectory.t.eccentr);
-    setup_spinbutton!(drawing, (-TAU) to (TAU) by (TAU/60.0);
-                      ar_entry -> state.trajectory.p.arg_peri);
-    setup_spinbutton!(drawing, (-TAU) to (TAU) by (TAU/60.0);
-                      in_entry -> state.trajectory.p.inclination);
-    setup_spinbutton!(drawing, (-TAU) to (TAU) by (TAU/60.0);
-                      an_entry -> state.trajectory.p.lon_asc_node);
-    // setup_spinbutton!(drawing, (-TAU) to (TAU) by (TAU/60.0);
-    //                   ma0_entry -> state.trajectory.p.inclination);
-    // setup_spinbutton!(drawing, (-TAU) to (TAU) by (TAU/60.0);
-    //                   ma1_entry -> state.trajectory.p.lon_asc_node);
+                      grat_entry -> state.graticule_spacing);
+    setup_spinbutton!(drawing;
+                      sun_lon_entry -> state.sun_lon);
+    setup_spinbutton!(drawing;
+                      sun_lat_entry -> state.sun_lat);
+
+    texture_btn.connect_file_set(cloning!(state, drawing, texture_info => move |texture_btn| {
+        let path = match texture_btn.get_filename() {
+            Some(path) => path,
+            None => return,
+        };
+        match render::load_texture(&path) {
+            Ok(texture) => {
+                texture_info.set_text(&format!("{} × {}", texture.width, texture.height));
+                state.borrow_mut().texture = Some(texture);
+            }
+            Err(e) => texture_info.set_text(&format!("Couldn't load texture: {}", e)),
+        }
+        drawing.queue_draw();
+    }));
+
+    texture_clear_btn.connect_clicked(cloning!(state, drawing, texture_btn,
+                                                texture_info => move |_| {
+        state.borrow_mut().texture = None;
+        texture_btn.unselect_all();
+        texture_info.set_text("");
+        drawing.queue_draw();
+    }));
+
+    play_btn.connect_toggled(cloning!(state => move |play_btn| {
+        let mut state = state.borrow_mut();
+        state.playing = play_btn.get_active();
+        play_btn.set_label(if state.playing { "Pause" } else { "Play" });
+        drop(state);
+    }));
+
+    drift_btn.set_active(state.borrow().j2_drift);
+    drift_btn.connect_toggled(cloning!(state, drawing => move |drift_btn| {
+        state.borrow_mut().j2_drift = drift_btn.get_active();
+        drawing.queue_draw();
+    }));
+    sgp4_btn.set_active(state.borrow().sgp4);
+    sgp4_btn.connect_toggled(cloning!(state, drawing => move |sgp4_btn| {
+        state.borrow_mut().sgp4 = sgp4_btn.get_active();
+        drawing.queue_draw();
+    }));
+
+    // scenarios
+    save_item.connect_activate(cloning!(state, window, scenario_filter => move |_| {
+        let path = match choose_file(&window,
+                                     "Save Scenario",
+                                     &scenario_filter,
+                                     gtk::FileChooserAction::Save) {
+            Some(path) => path,
+            None => return,
+        };
+        let saved = state.borrow().save(&path);
+        if let Err(e) = saved {
+            show_error(&window, &format!("Couldn't save the scenario: {}", e));
+        }
+    }));
+    open_item.connect_activate(cloning!(state, window, scenario_filter, drawing, objects_list,
+                                        add_rows, body_combo, time_scale, warp_entry, play_btn,
+                                        drift_btn, sgp4_btn, gt_entry, grat_entry, sun_lon_entry,
+                                        sun_lat_entry => move |_| {
+        let path = match choose_file(&window,
+                                     "Open Scenario",
+                                     &scenario_filter,
+                                     gtk::FileChooserAction::Open) {
+            Some(path) => path,
+            None => return,
+        };
+        let mut loaded = match State::load(&path) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                show_error(&window, &format!("Couldn't open the scenario: {}", e));
+                return;
+            }
+        };
+        play_btn.set_active(false);
+        let mut st = state.borrow_mut();
+        // the texture isn't part of the scenario, so keep whatever we had
+        loaded.texture = st.texture.take();
+        *st = loaded;
+        drop(st);
+
+        // make the side panel match (the widgets' handlers just write the same values back)
+        let st = state.borrow();
+        let (body, selected, time) = (st.body, st.selected, st.time);
+        let (j2_drift, sgp4) = (st.j2_drift, st.sgp4);
+        let spin_values = [(&warp_entry, st.time_warp),
+                           (&gt_entry, st.ground_track_revs),
+                           (&grat_entry, st.graticule_spacing),
+                           (&sun_lon_entry, st.sun_lon),
+                           (&sun_lat_entry, st.sun_lat)];
+        drop(st);
+        body_combo.set_active(body as i32);
+        time_scale.set_value(time);
+        for &(spin_btn, value) in &spin_values {
+            spin_btn.set_value(value);
+        }
+        drift_btn.set_active(j2_drift);
+        sgp4_btn.set_active(sgp4);
+        for row in objects_list.get_children() {
+            row.destroy();
+        }
+        add_rows(0);
+        objects_list.select_row(objects_list.get_row_at_index(selected as i32).as_ref());
+    }));
+    import_tle_item.connect_activate(cloning!(state, window, tle_filter, add_rows,
+                                              body_combo => move |_| {
+        let path = match choose_file(&window,
+                                     "Import TLEs",
+                                     &tle_filter,
+                                     gtk::FileChooserAction::Open) {
+            Some(path) => path,
+            None => return,
+        };
+        let tles = match load_tles(&path) {
+            Ok(tles) => tles,
+            Err(e) => {
+                show_error(&window, &format!("Couldn't import the TLEs: {}", e));
+                return;
+            }
+        };
+        let first = state.borrow_mut().add_tles(&tles);
+        let body = state.borrow().body;
+        body_combo.set_active(body as i32);
+        add_rows(first);
+    }));
+
+    // CCSDS messages
+    import_ccsds_item.connect_activate(cloning!(state, window, ccsds_filter, add_rows,
+                                                body_combo => move |_| {
+        let path = match choose_file(&window,
+                                     "Import CCSDS Message",
+                                     &ccsds_filter,
+                                     gtk::FileChooserAction::Open) {
+            Some(path) => path,
+            None => return,
+        };
+        let added = load_ccsds(&path).and_then(|message| state.borrow_mut().add_ccsds(&message));
+        let first = match added {
+            Ok(first) => first,
+            Err(e) => {
+                show_error(&window, &format!("Couldn't import the message: {}", e));
+                return;
+            }
+        };
+        let body = state.borrow().body;
+        body_combo.set_active(body as i32);
+        add_rows(first);
+    }));
+    export_opm_item.connect_activate(cloning!(state, window, ccsds_filter => move |_| {
+        let path = match choose_file(&window,
+                                     "Export OPM",
+                                     &ccsds_filter,
+                                     gtk::FileChooserAction::Save) {
+            Some(path) => path,
+            None => return,
+        };
+        let st = state.borrow();
+        let opm = st.to_opm(st.selected());
+        drop(st);
+        if let Err(e) = save_ccsds(&path, &Message::Opm(opm)) {
+            show_error(&window, &format!("Couldn't export the OPM: {}", e));
+        }
+    }));
+    export_oem_item.connect_activate(cloning!(state, window, ccsds_filter => move |_| {
+        let path = match choose_file(&window,
+                                     "Export OEM",
+                                     &ccsds_filter,
+                                     gtk::FileChooserAction::Save) {
+            Some(path) => path,
+            None => return,
+        };
+        let st = state.borrow();
+        let oem = st.to_oem(st.selected());
+        drop(st);
+        let saved = oem.and_then(|oem| save_ccsds(&path, &Message::Oem(oem)));
+        if let Err(e) = saved {
+            show_error(&window, &format!("Couldn't export the OEM: {}", e));
+        }
+    }));
+
+    let mut last_frame = Instant::now();
+    gtk::timeout_add(FRAME_INTERVAL, cloning!(state, time_scale, play_btn, map_drawing => move || {
+        let now = Instant::now();
+        let elapsed = now.duration_since(last_frame);
+        last_frame = now;
+
+        let state = state.borrow();
+        if state.playing {
+            let time = state.time + elapsed.as_secs_f64() * state.time_warp;
+            drop(state);
+            // this goes through `time_scale`'s handler, which redraws
+            time_scale.set_value(time);
+            if time >= time_scale.get_adjustment().get_upper() {
+                play_btn.set_active(false);
+            }
+        }
+        // everything else only redraws `drawing`, so keep the map up to date here
+        if map_drawing.get_mapped() {
+            map_drawing.queue_draw();
+        }
+        gtk::Continue(true)
+    }));
 
     let gest_drag = gtk::GestureDrag::new(&drawing);
-    gest_drag.connect_drag_begin(cloning!(state => move |_, _, _| {
+    gest_drag.connect_drag_begin(cloning!(state, drawing => move |_, x, y| {
         let mut state = state.borrow_mut();
+        let p = widget_to_scene(&state, &drawing, x, y);
+        state.drag_handle = render::maneuver_handle_at(&state, p);
+        state.p_maneuver = state.selected().maneuver.unwrap_or_default();
         state.p_eye_lat = state.eye_lat;
         state.p_eye_lon = state.eye_lon;
         drop(state);
     }));
-    gest_drag.connect_drag_update(cloning!(state, drawing => move |_,dx,dy| {
+    gest_drag.connect_drag_update(cloning!(state, drawing, maneuver_info => move |gest_drag,dx,dy| {
         let mut state = state.borrow_mut();
-        state.eye_lat = clamp(state.p_eye_lat + dy * DRAG_TURN_RATE, -TAU/4.0, TAU/4.0);
-        state.eye_lon = state.p_eye_lon + dx * DRAG_TURN_RATE;
+        match state.drag_handle {
+            None => {
+                state.eye_lat = clamp(state.p_eye_lat + dy * DRAG_TURN_RATE, -TAU/4.0, TAU/4.0);
+                state.eye_lon = state.p_eye_lon + dx * DRAG_TURN_RATE;
+            }
+            Some(ManeuverHandle::Node) => {
+                let (x, y) = gest_drag.get_start_point().unwrap_or((0.0, 0.0));
+                let p = widget_to_scene(&state, &drawing, x + dx, y + dy);
+                let mean_anom = render::nearest_mean_anom(&state, p);
+                if let Some(man) = state.selected_mut().maneuver.as_mut() {
+                    man.mean_anom = mean_anom;
+                }
+            }
+            Some(handle) => {
+                // the handle's direction doesn't change while we're dragging it
+                let dir = render::maneuver_handles(&state)
+                    .into_iter()
+                    .find(|&(h, _, _)| h == handle)
+                    .map_or(Vector2::zeros(), |(_, _, dir)| dir);
+                let (_, sign) = handle.axis().unwrap();
+                let mut man = state.p_maneuver;
+                if let Some(dv) = handle.component_mut(&mut man) {
+                    *dv += sign * (dx * dir.x - dy * dir.y) * DRAG_DELTA_V_RATE;
+                }
+                state.selected_mut().maneuver = Some(man);
+            }
+        }
+        maneuver_info.set_text(&maneuver_text(state.selected()));
         drop(state);
         drawing.queue_draw();
     }));
     gest_drag.connect_drag_end(cloning!(state => move |_, _, _| {
         let mut state = state.borrow_mut();
-        state.p_eye_lat = NAN;
-        state.p_eye_lon = NAN;
+        state.drag_handle = None;
+        state.p_eye_lat = f64::NAN;
+        state.p_eye_lon = f64::NAN;
         drop(state);
     }));
 
@@ -121,3 +568,139 @@ pub fn main(state: Rc<RefCell<State>>) {
         x.min(max).max(min)
     }
 }
+
+/// Adds the legs of `transfer` to the scene and describes it in `info`.
+fn add_transfer<F>(state: &Rc<RefCell<State>>,
+                   add_rows: &F,
+                   info: &gtk::Label,
+                   transfer: Option<orbits::Transfer>)
+                   -> ()
+    where F: Fn(usize) -> ()
+{
+    match transfer {
+        Some(transfer) => {
+            let mut text = String::new();
+            for (k, dv) in transfer.burns.iter().enumerate() {
+                text.push_str(&format!("Burn {}: {:.3} km/s\n", k + 1, dv));
+            }
+            text.push_str(&format!("Total: {:.3} km/s\n", transfer.total_delta_v()));
+            text.push_str(&format!("Time: {:.0} s", transfer.time));
+            info.set_text(&text);
+            let first = state.borrow_mut().add_transfer(&transfer);
+            add_rows(first);
+        }
+        None => info.set_text("No transfer found"),
+    }
+}
+
+/// Rebuilds the list of transfer targets, keeping the current one selected if it's still there.
+fn refresh_targets(state: &Rc<RefCell<State>>, combo: &gtk::ComboBoxText) -> () {
+    let active = combo.get_active();
+    combo.remove_all();
+    let state = state.borrow();
+    for obj in &state.objects {
+        combo.append_text(&obj.name);
+    }
+    if active >= 0 && (active as usize) < state.objects.len() {
+        combo.set_active(active);
+    } else {
+        combo.set_active(0);
+    }
+}
+
+/// Asks which file to open or where to save one.
+fn choose_file(window: &gtk::Window,
+               title: &str,
+               filter: &gtk::FileFilter,
+               action: gtk::FileChooserAction)
+               -> Option<PathBuf> {
+    let button = match action {
+        gtk::FileChooserAction::Save => "_Save",
+        _ => "_Open",
+    };
+    let dialog = gtk::FileChooserDialog::new(Some(title), Some(window), action);
+    dialog.add_button("_Cancel", gtk::ResponseType::Cancel.into());
+    dialog.add_button(button, gtk::ResponseType::Accept.into());
+    dialog.add_filter(filter);
+    dialog.set_do_overwrite_confirmation(true);
+    let accept: i32 = gtk::ResponseType::Accept.into();
+    let path = if dialog.run() == accept {
+        dialog.get_filename()
+    } else {
+        None
+    };
+    dialog.destroy();
+    path
+}
+
+fn show_error(window: &gtk::Window, message: &str) -> () {
+    let dialog = gtk::MessageDialog::new(Some(window),
+                                         gtk::DIALOG_MODAL,
+                                         gtk::MessageType::Error,
+                                         gtk::ButtonsType::Close,
+                                         message);
+    dialog.run();
+    dialog.destroy();
+}
+
+/// Converts a point on `drawing` to the coordinates `render::draw` uses.
+fn widget_to_scene(state: &State, drawing: &gtk::DrawingArea, x: f64, y: f64) -> Point2<f64> {
+    let width = drawing.get_allocated_width() as f64;
+    let height = drawing.get_allocated_height() as f64;
+    render::from_widget(state, width, height, x, y)
+}
+
+/// Describes `obj`'s maneuver (if it has one).
+fn maneuver_text(obj: &SceneObject) -> String {
+    match obj.maneuver {
+        Some(man) => {
+            format!("Prograde: {:.3} km/s\nNormal: {:.3} km/s\nRadial: {:.3} km/s\n\
+                     Total: {:.3} km/s",
+                    man.prograde,
+                    man.normal,
+                    man.radial,
+                    man.delta_v())
+        }
+        None => String::new(),
+    }
+}
+
+/// Creates the side panel row for the `i`th object.
+fn object_row(state: &Rc<RefCell<State>>,
+              drawing: &gtk::DrawingArea,
+              i: usize)
+              -> gtk::ListBoxRow {
+    let st = state.borrow();
+    let obj = &st.objects[i];
+    let row = gtk::ListBoxRow::new();
+    let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+    let visible_btn = gtk::CheckButton::new_with_label(&obj.name);
+    visible_btn.set_active(obj.visible);
+    let (r, g, b) = obj.color;
+    let color_btn = gtk::ColorButton::new_with_rgba(&gdk::RGBA {
+        red: r,
+        green: g,
+        blue: b,
+        alpha: 1.0,
+    });
+    drop(st);
+
+    visible_btn.connect_toggled(cloning!(state, drawing, row => move |visible_btn| {
+        let mut state = state.borrow_mut();
+        state.objects[row.get_index() as usize].visible = visible_btn.get_active();
+        drop(state);
+        drawing.queue_draw();
+    }));
+    color_btn.connect_color_set(cloning!(state, drawing, row => move |color_btn| {
+        let c = color_btn.get_rgba();
+        let mut state = state.borrow_mut();
+        state.objects[row.get_index() as usize].color = (c.red, c.green, c.blue);
+        drop(state);
+        drawing.queue_draw();
+    }));
+
+    hbox.pack_start(&visible_btn, true, true, 0);
+    hbox.pack_start(&color_btn, false, false, 0);
+    row.add(&hbox);
+    row
+}
@@ -1,5 +1,7 @@
 pub use self::main::main;
+pub use self::headless::main as run;
 
 mod common;
+mod headless;
 mod main;
 mod render;
@@ -1,29 +1,35 @@
+use std::fs::File;
+use std::path::Path;
+
 use cairo;
 use tau::TAU;
-use na::{Affine2, Rotation2, Rotation3, Matrix3, U2, Vector2, Vector3, Transform2};
+use na::{Rotation3, U2, Point2, Vector2, Vector3};
 
 use gui::common::*;
-use orbits::*;
-use conics::*;
-
-enum Void {}
-impl FnOnce<(f64, f64)> for Void {
-    type Output = ();
-    extern "rust-call" fn call_once(self, _: (f64, f64)) -> () {
-        match self {}
-    }
-}
+use orbit_sim::orbits::*;
+use orbit_sim::conics::*;
+use orbit_sim::bodies::{self, Leg};
 
 struct RenderCommon<'a> {
     ctx: &'a cairo::Context,
     screen_extent: f64,
     proj_mat: Rotation3<f64>,
-    planet: Ellipse,
-    planet_path: cairo::Path,
-    planet_path_inv: cairo::Path,
+    planet_radius: f64, // km
 }
 
-const AXIS_LENGTH: f64 = PLANET_RADIUS + 1000.0; // km
+const AXIS_LENGTH: f64 = 1.15; // body radii
+const SPACECRAFT_RADIUS: f64 = 7.5; // px
+const APSIS_MARKER_RADIUS: f64 = 10.0; // px
+const NODE_MARKER_SIZE: f64 = 15.0; // px
+const MIN_MOON_RADIUS: f64 = 3.0; // px
+const GROUND_TRACK_ALPHA: f64 = 0.75;
+const GRATICULE_ALPHA: f64 = 0.5;
+const GRATICULE_SAMPLES: usize = 90; // per line
+/// How bright the night side is compared to where the sun is straight overhead.
+const AMBIENT_LIGHT: f64 = 0.15;
+const PLANET_COLOR: (f64, f64, f64) = (0.0, 0.0, 0.75); // rgb (when there's no texture)
+/// How far ahead we follow trajectories through maneuvers and spheres of influence.
+const PATCHED_CONIC_HORIZON: f64 = 30.0 * 86400.0; // s
 pub fn draw(ctx: &cairo::Context, st: &State) {
     ctx.set_antialias(cairo::Antialias::Best);
     ctx.set_fill_rule(cairo::FillRule::Winding);
@@ -35,22 +41,12 @@ pub fn draw(ctx: &cairo::Context, st: &State) {
 
     // precompute a bunch of stuff
     let rc = {
-        ctx.arc(0.0, 0.0, PLANET_RADIUS, 0.0, TAU);
-        let planet_path = ctx.copy_path();
-        ctx.new_path();
-        ctx.arc_negative(0.0, 0.0, PLANET_RADIUS, TAU, 0.0);
-        let planet_path_inv = ctx.copy_path();
-        ctx.new_path();
-        let proj_mat = Rotation3::from_axis_angle(&Vector3::x_axis(), st.eye_lat - TAU / 4.0) *
-                       Rotation3::from_axis_angle(&Vector3::z_axis(), st.eye_lon - TAU / 4.0);
         let (_, _, ex, ey) = ctx.clip_extents();
         RenderCommon {
             ctx: ctx,
             screen_extent: ex.hypot(ey),
-            proj_mat: proj_mat,
-            planet: Ellipse::new_circle(PLANET_RADIUS),
-            planet_path: planet_path,
-            planet_path_inv: planet_path_inv,
+            proj_mat: projection(st),
+            planet_radius: st.body().radius,
         }
     };
 
@@ -58,13 +54,12 @@ pub fn draw(ctx: &cairo::Context, st: &State) {
     ctx.set_source_rgb(0.0, 0.0, 0.0);
     ctx.paint();
 
-    // Planet
-    ctx.append_path(&rc.planet_path);
-    ctx.set_source_rgb(0.0, 0.0, 0.75);
-    ctx.fill();
+    // planet
+    draw_planet(&rc, st);
 
     // axis
-    let north_pole = (rc.proj_mat * Vector3::z()).fixed_rows::<U2>(0) * AXIS_LENGTH;
+    let north_pole = (rc.proj_mat * Vector3::z()).fixed_rows::<U2>(0) * AXIS_LENGTH *
+                     st.body().radius;
     ctx.move_to(north_pole.x, north_pole.y);
     ctx.line_to(north_pole.x, -north_pole.y);
     ctx.save();
@@ -74,73 +69,353 @@ pub fn draw(ctx: &cairo::Context, st: &State) {
     ctx.stroke();
     ctx.restore();
 
-    // hemisphere lines
-    // sorta abusing `render_trajectory`
-    let great_circle = PlanarTrajectory { periapsis: PLANET_RADIUS, ..Default::default() };
-    let hemisphere_renderer = || {
-        ctx.set_line_width(4.0);
-        ctx.set_source_rgb(0.0, 1.0, 0.0);
+    // lines of latitude and longitude, turned with the planet
+    let turn = st.rotation_angle(st.time);
+    if st.graticule_spacing > 0.0 {
+        let spacing = st.graticule_spacing;
+        let mut lon = 0.0;
+        while lon < TAU {
+            let line = (0..GRATICULE_SAMPLES + 1)
+                .map(|k| (TAU / 4.0 - TAU / 2.0 * k as f64 / GRATICULE_SAMPLES as f64, lon));
+            draw_surface_line(&rc, st.body().radius, turn, line);
+            lon += spacing;
+        }
+        let mut lat = (-TAU / 4.0 / spacing).floor() * spacing;
+        while lat < TAU / 4.0 {
+            if lat > -TAU / 4.0 {
+                let line = (0..GRATICULE_SAMPLES + 1)
+                    .map(|k| (lat, TAU * k as f64 / GRATICULE_SAMPLES as f64));
+                draw_surface_line(&rc, st.body().radius, turn, line);
+            }
+            lat += spacing;
+        }
+        ctx.save();
+        ctx.identity_matrix();
+        ctx.set_line_width(1.0);
+        ctx.set_source_rgba(0.0, 1.0, 0.0, GRATICULE_ALPHA);
         ctx.stroke();
-    };
-    println!("1");
-    render_trajctory(&rc,
-                     Default::default(),
-                     Trajectory {
-                         p: Plane {
-                             arg_peri: 0.0,
-                             lon_asc_node: 0.0,
-                             inclination: 0.0,
-                         },
-                         t: great_circle,
-                     },
-                     &hemisphere_renderer);
-    println!("2");
-    render_trajctory(&rc,
-                     Default::default(),
-                     Trajectory {
-                         p: Plane {
-                             arg_peri: 0.0,
-                             lon_asc_node: 0.0,
-                             inclination: TAU / 4.0,
-                         },
-                         t: great_circle,
-                     },
-                     &hemisphere_renderer);
-    println!("3");
-    render_trajctory(&rc,
-                     Default::default(),
-                     Trajectory {
-                         p: Plane {
-                             arg_peri: 0.0,
-                             lon_asc_node: TAU / 4.0,
-                             inclination: TAU / 4.0,
+        ctx.restore();
+    }
+
+    // moons and their spheres of influence
+    for (i, moon) in st.bodies.iter().enumerate() {
+        if moon.orbit.is_none_or(|orbit| orbit.parent != st.body) {
+            continue;
+        }
+        let pos = rc.proj_mat * bodies::relative_position(&st.bodies, st.body, i, st.time);
+        let alpha = if pos.z < 0.0 && pos.x.hypot(pos.y) < st.body().radius + moon.radius {
+            OCCLUDED_ALPHA
+        } else {
+            1.0
+        };
+        ctx.set_source_rgba(0.6, 0.6, 0.6, alpha);
+        ctx.arc(pos.x, pos.y, moon.radius.max(MIN_MOON_RADIUS / st.scale), 0.0, TAU);
+        ctx.fill();
+        if let Some(soi) = moon.soi_radius(&st.bodies) {
+            ctx.arc(pos.x, pos.y, soi, 0.0, TAU);
+            ctx.save();
+            ctx.identity_matrix();
+            ctx.set_line_width(1.0);
+            ctx.set_source_rgba(0.6, 0.6, 0.6, alpha / 2.0);
+            ctx.stroke();
+            ctx.restore();
+        }
+    }
+
+    // ground tracks, on the near side of the planet as it's turned by now
+    let objects: Vec<SceneObject> = st.objects.iter().map(|obj| st.drifted(obj)).collect();
+    for obj in objects.iter().filter(|obj| obj.visible) {
+        let track = st.ground_track(obj).into_iter().map(|(_, lat, lon)| (lat, lon));
+        draw_surface_line(&rc, st.body().radius, turn, track);
+        let (r, g, b) = obj.color;
+        ctx.save();
+        ctx.identity_matrix();
+        ctx.set_line_width(2.0);
+        ctx.set_source_rgba(r, g, b, GROUND_TRACK_ALPHA);
+        ctx.stroke();
+        ctx.restore();
+    }
+
+    // actual trajectories
+    for obj in objects.iter().filter(|obj| obj.visible) {
+        let (r, g, b) = obj.color;
+        render_trajctory(&rc,
+                         InterestingPoints {
+                             apoapsis: Some(|x, y| {
+                                 ctx.set_source_rgb(r, g, b);
+                                 ctx.arc(x, y, APSIS_MARKER_RADIUS / st.scale, 0.0, TAU);
+                                 ctx.fill();
+                             }),
+                             periapsis: Some(|x, y| {
+                                 ctx.set_source_rgb(r, g, b);
+                                 ctx.arc(x, y, APSIS_MARKER_RADIUS / st.scale, 0.0, TAU);
+                                 ctx.fill();
+                             }),
+                             ascending_node: Some(|x, y| {
+                                 ctx.set_source_rgb(1.0, 0.5, 0.0);
+                                 draw_triangle(ctx, x, y, NODE_MARKER_SIZE / st.scale);
+                                 ctx.fill();
+                             }),
+                             descending_node: Some(|x, y| {
+                                 ctx.set_source_rgb(1.0, 0.5, 0.0);
+                                 draw_triangle(ctx, x, y, -NODE_MARKER_SIZE / st.scale);
+                                 ctx.fill();
+                             }),
                          },
-                         t: great_circle,
-                     },
-                     &hemisphere_renderer);
-
-    // actual trajectory
-    render_trajctory::<_, _, Void, Void, _>(&rc,
-                                               InterestingPoints {
-                                                   apoapsis: Some(|x, y| {
-                                                       ctx.set_source_rgb(1.0, 0.0, 0.0);
-                                                       ctx.arc(x, y, 400.0, 0.0, TAU);
-                                                       ctx.fill();
-                                                   }),
-                                                   periapsis: Some(|x, y| {
-                                                       ctx.set_source_rgb(1.0, 0.0, 0.0);
-                                                       ctx.arc(x, y, 400.0, 0.0, TAU);
-                                                       ctx.fill();
-                                                   }),
-                                                   ascending_node: None,
-                                                   descending_node: None,
-                                               },
-                                               st.trajectory,
-                                               || {
-                                                   ctx.set_source_rgb(1.0, 0.0, 0.0);
-                                                   ctx.set_line_width(5.0);
-                                                   ctx.stroke();
-                                               });
+                         obj.trajectory,
+                         || {
+                             ctx.set_source_rgb(r, g, b);
+                             ctx.set_line_width(5.0);
+                             ctx.stroke();
+                         });
+    }
+
+    // the rest of each trajectory (after maneuvers and sphere of influence changes)
+    let legs: Vec<Vec<Leg>> = st.objects
+        .iter()
+        .map(|obj| if obj.visible {
+            st.legs(obj, PATCHED_CONIC_HORIZON)
+        } else {
+            Vec::new()
+        })
+        .collect();
+    for (obj, legs) in objects.iter().zip(&legs) {
+        let (r, g, b) = obj.color;
+        let leg_renderer = || {
+            ctx.set_source_rgb(r, g, b);
+            ctx.set_line_width(3.0);
+            ctx.set_dash(&[10.0, 6.0], 0.0);
+            ctx.stroke();
+            ctx.set_dash(&[], 0.0);
+        };
+        // the first leg is just (part of) the object's trajectory
+        for leg in legs.iter().skip(1) {
+            if leg.body == st.body {
+                render_trajctory(&rc, Default::default(), leg.trajectory, leg_renderer);
+            } else {
+                // this is relative to some other body, so draw it wherever that body is now
+                let offset = bodies::relative_position(&st.bodies, st.body, leg.body, st.time);
+                let rot = bodies::change_of_axes(&st.bodies, leg.body, st.body);
+                let mu = st.bodies[leg.body].mu;
+                let (m0, mut m1) = (leg.trajectory.t.mean_anom0, leg.trajectory.t.mean_anom1);
+                if leg.trajectory.t.eccentr < 1.0 {
+                    m1 = m1.min(m0 + TAU);
+                }
+                for i in 0..(SAMPLED_ARC_SEGMENTS + 1) {
+                    let m = m0 + (m1 - m0) * i as f64 / SAMPLED_ARC_SEGMENTS as f64;
+                    let p = rc.proj_mat * (offset + rot * leg.trajectory.to_state(mu, m).0);
+                    if i == 0 {
+                        ctx.move_to(p.x, p.y);
+                    } else {
+                        ctx.line_to(p.x, p.y);
+                    }
+                }
+                ctx.save();
+                ctx.identity_matrix();
+                leg_renderer();
+                ctx.restore();
+            }
+        }
+    }
+
+    // maneuver node of the selected object
+    for (handle, p, _) in maneuver_handles(st) {
+        let (r, g, b) = match handle.axis() {
+            None => (0.3, 0.6, 1.0),
+            Some((0, _)) => (0.8, 1.0, 0.0),
+            Some((1, _)) => (0.8, 0.0, 1.0),
+            Some(_) => (0.0, 0.8, 1.0),
+        };
+        ctx.set_source_rgb(r, g, b);
+        ctx.arc(p.x, p.y, HANDLE_RADIUS / st.scale, 0.0, TAU);
+        if handle == ManeuverHandle::Node {
+            ctx.save();
+            ctx.identity_matrix();
+            ctx.set_line_width(3.0);
+            ctx.stroke();
+            ctx.restore();
+        } else {
+            ctx.fill();
+        }
+    }
+
+    // spacecraft
+    for legs in &legs {
+        let leg = match legs.iter().rev().find(|leg| leg.start_time <= st.time).or(legs.first()) {
+            Some(leg) => leg,
+            None => continue,
+        };
+        let offset = bodies::relative_position(&st.bodies, st.body, leg.body, st.time);
+        let rot = bodies::change_of_axes(&st.bodies, leg.body, st.body);
+        let sc = rc.proj_mat * (offset + rot * leg.state(&st.bodies, st.time).0);
+        let sc_alpha = Occlusion::of(sc, st.body().radius).alpha();
+        ctx.set_source_rgba(1.0, 1.0, 0.0, sc_alpha);
+        ctx.arc(sc.x, sc.y, SPACECRAFT_RADIUS / st.scale, 0.0, TAU);
+        ctx.fill();
+    }
+}
+
+/// Draws the ground tracks of every visible object on an equirectangular map.
+pub fn draw_map(ctx: &cairo::Context, st: &State) {
+    ctx.set_antialias(cairo::Antialias::Best);
+    let (ox, oy, ex, ey) = ctx.clip_extents();
+
+    // fit the map in the middle, with longitude going right and latitude going up
+    let scale = ((ex - ox) / TAU).min((ey - oy) / (TAU / 2.0));
+    ctx.translate((ox + ex) / 2.0, (oy + ey) / 2.0);
+    ctx.scale(scale, -scale);
+
+    // clear
+    ctx.set_source_rgb(0.0, 0.0, 0.0);
+    ctx.paint();
+
+    // planet
+    ctx.rectangle(-TAU / 2.0, -TAU / 4.0, TAU, TAU / 2.0);
+    ctx.set_source_rgb(0.0, 0.0, 0.75);
+    ctx.fill();
+
+    // graticule
+    if st.graticule_spacing > 0.0 {
+        let mut lon = -TAU / 2.0;
+        while lon <= TAU / 2.0 {
+            ctx.move_to(lon, -TAU / 4.0);
+            ctx.line_to(lon, TAU / 4.0);
+            lon += st.graticule_spacing;
+        }
+        let mut lat = -TAU / 4.0;
+        while lat <= TAU / 4.0 {
+            ctx.move_to(-TAU / 2.0, lat);
+            ctx.line_to(TAU / 2.0, lat);
+            lat += st.graticule_spacing;
+        }
+    }
+    ctx.save();
+    ctx.identity_matrix();
+    ctx.set_line_width(1.0);
+    ctx.set_source_rgba(1.0, 1.0, 1.0, 0.25);
+    ctx.stroke();
+    ctx.restore();
+
+    for obj in st.objects.iter().filter(|obj| obj.visible) {
+        let obj = st.drifted(obj);
+        let track = st.ground_track(&obj);
+        let mut prev_lon: Option<f64> = None;
+        for &(_, lat, lon) in &track {
+            match prev_lon {
+                // don't draw a line all the way across when we wrap around
+                Some(prev) if (lon - prev).abs() < TAU / 2.0 => ctx.line_to(lon, lat),
+                _ => ctx.move_to(lon, lat),
+            }
+            prev_lon = Some(lon);
+        }
+        let (r, g, b) = obj.color;
+        ctx.save();
+        ctx.identity_matrix();
+        ctx.set_line_width(2.0);
+        ctx.set_source_rgb(r, g, b);
+        ctx.stroke();
+        ctx.restore();
+
+        // where it is now
+        if let Some(&(_, lat, lon)) = track.first() {
+            ctx.set_source_rgb(1.0, 1.0, 0.0);
+            ctx.arc(lon, lat, SPACECRAFT_RADIUS / scale, 0.0, TAU);
+            ctx.fill();
+        }
+    }
+}
+
+/// Reads an equirectangular map of the planet from a PNG file.
+pub fn load_texture(path: &Path) -> Result<Texture, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut image = cairo::ImageSurface::create_from_png(&mut file)
+        .map_err(|e| format!("{:?}", e))?;
+    let (width, height) = (image.get_width() as usize, image.get_height() as usize);
+    let stride = image.get_stride() as usize;
+    if width == 0 || height == 0 {
+        return Err("the image is empty".to_owned());
+    }
+    let data = image.get_data().map_err(|e| format!("{:?}", e))?;
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * stride + x * 4;
+            let argb = u32::from_ne_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+            pixels.push(((argb >> 16) as u8, (argb >> 8) as u8, argb as u8));
+        }
+    }
+    Ok(Texture {
+        width: width,
+        height: height,
+        pixels: pixels,
+    })
+}
+
+/// Computes the rotation from world coordinates to view coordinates
+/// (x to the right, y up, and z toward the viewer).
+pub fn projection(st: &State) -> Rotation3<f64> {
+    Rotation3::from_axis_angle(&Vector3::x_axis(), st.eye_lat - TAU / 4.0) *
+    Rotation3::from_axis_angle(&Vector3::z_axis(), st.eye_lon - TAU / 4.0)
+}
+
+/// Converts a point on a widget of the given size (px) to the coordinates `draw` uses.
+pub fn from_widget(st: &State, width: f64, height: f64, x: f64, y: f64) -> Point2<f64> {
+    Point2::new((x - width / 2.0) / st.scale, (height / 2.0 - y) / st.scale)
+}
+
+const HANDLE_DISTANCE: f64 = 60.0; // px
+const HANDLE_RADIUS: f64 = 8.0; // px
+/// Handles whose direction is foreshortened to less than this fraction are hidden,
+/// since they'd be right on top of the node.
+const MIN_HANDLE_FORESHORTENING: f64 = 0.2;
+
+/// Computes where the selected object's maneuver node and handles are drawn
+/// and the direction on screen (as a unit vector) that each handle pulls in.
+pub fn maneuver_handles(st: &State) -> Vec<(ManeuverHandle, Point2<f64>, Vector2<f64>)> {
+    let obj = st.drifted(st.selected());
+    let man = match obj.maneuver {
+        Some(man) if obj.visible => man,
+        _ => return Vec::new(),
+    };
+    let proj_mat = projection(st);
+    let (pos, dirs) = man.frame(st.body().mu, &obj.trajectory);
+    let node = Point2::from((proj_mat * pos).xy());
+    let mut handles = vec![(ManeuverHandle::Node, node, Vector2::zeros())];
+    for &handle in &ManeuverHandle::DIRECTIONS {
+        let (axis, sign) = handle.axis().unwrap();
+        let dir = sign * (proj_mat * dirs[axis]).xy();
+        if dir.norm() >= MIN_HANDLE_FORESHORTENING {
+            handles.push((handle, node + dir * HANDLE_DISTANCE / st.scale, dir.normalize()));
+        }
+    }
+    handles
+}
+
+/// Finds the maneuver handle under `p` (in the coordinates `draw` uses), if there is one.
+pub fn maneuver_handle_at(st: &State, p: Point2<f64>) -> Option<ManeuverHandle> {
+    maneuver_handles(st)
+        .into_iter()
+        .filter(|&(_, q, _)| (p - q).norm() <= HANDLE_RADIUS / st.scale)
+        .map(|(handle, _, _)| handle)
+        .next_back()
+}
+
+const NEAREST_SAMPLES: u32 = 720;
+/// Finds the mean anomaly where the selected object's trajectory is drawn closest to `p`
+/// (in the coordinates `draw` uses).
+pub fn nearest_mean_anom(st: &State, p: Point2<f64>) -> f64 {
+    let traj = st.drifted(st.selected()).trajectory;
+    let proj_mat = projection(st);
+    let (m0, m1) = (traj.t.mean_anom0, traj.t.mean_anom1);
+    (0..(NEAREST_SAMPLES + 1))
+        .map(|i| m0 + (m1 - m0) * i as f64 / NEAREST_SAMPLES as f64)
+        .map(|m| {
+            let q = (proj_mat * traj.to_state(st.body().mu, m).0).xy();
+            (m, (p.coords - q).norm())
+        })
+        .fold((m0, f64::INFINITY),
+              |best, x| if x.1 < best.1 { x } else { best })
+        .0
 }
 
 struct InterestingPoints<F1, F2, F3, F4>
@@ -155,7 +430,9 @@ struct InterestingPoints<F1, F2, F3, F4>
     descending_node: Option<F4>,
 }
 
-impl Default for InterestingPoints<Void, Void, Void, Void> {
+/// The type of a marker that never gets drawn.
+type NoMarker = fn(f64, f64) -> ();
+impl Default for InterestingPoints<NoMarker, NoMarker, NoMarker, NoMarker> {
     fn default() -> Self {
         InterestingPoints {
             apoapsis: None,
@@ -169,6 +446,39 @@ impl Default for InterestingPoints<Void, Void, Void, Void> {
 const INTERNAL_ALPHA: f64 = 1.0 / 3.0;
 const OCCLUDED_ALPHA: f64 = 1.0 / 5.0;
 
+/// Where something is compared to the planet, as the camera sees it.
+#[derive(Clone,Copy,Debug,PartialEq)]
+enum Occlusion {
+    /// In front of the planet or off to the side of it.
+    Visible,
+    /// Under the surface.
+    Inside,
+    /// Hidden behind the planet.
+    Behind,
+}
+
+impl Occlusion {
+    /// Classifies `p` (in view coordinates) against a planet of the given radius.
+    fn of(p: Vector3<f64>, radius: f64) -> Occlusion {
+        if p.norm() < radius {
+            Occlusion::Inside
+        } else if p.z < 0.0 && p.x.hypot(p.y) < radius {
+            Occlusion::Behind
+        } else {
+            Occlusion::Visible
+        }
+    }
+
+    /// How opaque things that are here get drawn.
+    fn alpha(self) -> f64 {
+        match self {
+            Occlusion::Visible => 1.0,
+            Occlusion::Inside => INTERNAL_ALPHA,
+            Occlusion::Behind => OCCLUDED_ALPHA,
+        }
+    }
+}
+
 // TODO: dynamic dispatch might be better here
 fn render_trajctory<F1, F2, F3, F4, Fr>(rc: &RenderCommon,
                                         pts: InterestingPoints<F1, F2, F3, F4>,
@@ -179,122 +489,249 @@ fn render_trajctory<F1, F2, F3, F4, Fr>(rc: &RenderCommon,
           F2: FnOnce(f64, f64) -> (),
           F3: FnOnce(f64, f64) -> (),
           F4: FnOnce(f64, f64) -> (),
-          Fr: FnOnce() -> ()
+          Fr: Fn() -> ()
 {
-    let ctx = rc.ctx;
-
     // compute some stuff using `traj`
     let mat3 = rc.proj_mat * traj.p.to_matrix();
-    let mat2 = mat3.matrix().fixed_slice::<U2, U2>(0, 0);
-    let mut aff: Transform2<f64> = Transform2::identity();
-    {
-        let mut aff_set = aff.matrix_mut_unchecked().fixed_slice_mut::<U2, U2>(0, 0);
-        for (a, m) in aff_set.iter_mut().zip(mat2.iter()) {
-            *a = *m;
+    let to_view = |p: Point2<f64>| mat3 * Vector3::new(p.x, p.y, 0.0);
+
+    // interesting points
+    if let Some(f) = pts.apoapsis {
+        if let Some(ap) = traj.t.apoapsis() {
+            draw_marker(rc, to_view(Point2::from(ap)), f);
         }
     }
-    let aff = aff;
+    if let Some(f) = pts.periapsis {
+        draw_marker(rc, to_view(Point2::from(traj.t.periapsis())), f);
+    }
+    if let Some(f) = pts.ascending_node {
+        if let Some(an) = traj.ascending_node() {
+            draw_marker(rc, to_view(Point2::from(an)), f);
+        }
+    }
+    if let Some(f) = pts.descending_node {
+        if let Some(dn) = traj.descending_node() {
+            draw_marker(rc, to_view(Point2::from(dn)), f);
+        }
+    }
+
+    // the trajectory itself, which we follow in 3D so that we know which parts the planet hides
+    // (the projection alone can't tell, e.g. when we're looking at the orbit edge-on)
+    let ecc_anom0 = traj.t.ecc_anom(traj.t.mean_anom0);
+    let ecc_anom1 = traj.t.ecc_anom(traj.t.mean_anom1);
+    match traj.t.to_conic() {
+        Conic::Ellipse(ell) => {
+            let ell = ell.to_canonical();
+            let (t0, t1) = if (ecc_anom1 - ecc_anom0).abs() >= TAU {
+                (0.0, TAU)
+            } else {
+                (ecc_anom0, ecc_anom1)
+            };
+            draw_occluded_arc(rc, t0, t1, |t| to_view(ell.point(t)), tr_renderer);
+        }
+        Conic::Parabola(par) => {
+            // only draw the part that's on screen
+            let t_max = (rc.screen_extent / par.focal_length - 1.0).max(0.0).sqrt();
+            let t0 = clamp(-ecc_anom0, -t_max, t_max);
+            let t1 = clamp(-ecc_anom1, -t_max, t_max);
+            draw_occluded_arc(rc, t0, t1, |t| to_view(par.point(t)), tr_renderer);
+        }
+        Conic::Hyperbola(hyp) => {
+            let cosh_max = (rc.screen_extent / hyp.semi_axes.x + 1.0) / traj.t.eccentr;
+            let t_max = cosh_max.max(1.0).acosh();
+            let t0 = clamp(-ecc_anom0, -t_max, t_max);
+            let t1 = clamp(-ecc_anom1, -t_max, t_max);
+            draw_occluded_arc(rc, t0, t1, |t| to_view(hyp.point(t)), tr_renderer);
+        }
+    }
+}
+
+const SAMPLED_ARC_SEGMENTS: u32 = 256;
+/// How many times we halve a segment to find where it goes behind or into the planet.
+const OCCLUSION_BISECTIONS: u32 = 20;
 
-    rc.planet.transform(&aff).map(|e| draw_ellipse_arc(ctx, e, 0.0, TAU));
-    let inside_planet_path = ctx.copy_path();
-    ctx.new_path();
+/// Approximates the curve traced by `point` (in view coordinates) between `t1` and `t2` with
+/// line segments and draws it with `renderer`: opaque where it's visible, and faded where it's
+/// inside or behind the planet.
+fn draw_occluded_arc<F, Fr>(rc: &RenderCommon, t1: f64, t2: f64, point: F, renderer: Fr) -> ()
+    where F: Fn(f64) -> Vector3<f64>,
+          Fr: Fn() -> ()
+{
+    let ctx = rc.ctx;
+    let occlusion = |t| Occlusion::of(point(t), rc.planet_radius);
 
-    let orbit_norm = mat3 * Vector3::z();
-    let ell = match traj.t.to_ellipse().transform(&aff) {
-        None => {
-            println!("{:?}", traj);
-            return;
+    // split the curve into runs that are all visible, inside, or behind
+    let mut runs = vec![(occlusion(t1), vec![point(t1)])];
+    let mut prev_t = t1;
+    for i in 1..(SAMPLED_ARC_SEGMENTS + 1) {
+        let t = t1 + (t2 - t1) * i as f64 / SAMPLED_ARC_SEGMENTS as f64;
+        let occ = occlusion(t);
+        // this segment might cross more than one boundary (e.g. straight through the limb)
+        let mut start = prev_t;
+        while runs[runs.len() - 1].0 != occ {
+            let current = runs[runs.len() - 1].0;
+            let (mut a, mut b) = (start, t);
+            for _ in 0..OCCLUSION_BISECTIONS {
+                let mid = (a + b) / 2.0;
+                if occlusion(mid) == current {
+                    a = mid;
+                } else {
+                    b = mid;
+                }
+            }
+            let edge = point(b);
+            let last = runs.len() - 1;
+            runs[last].1.push(edge);
+            runs.push((occlusion(b), vec![edge]));
+            start = b;
         }
-        Some(x) => x,
-    };
+        let last = runs.len() - 1;
+        runs[last].1.push(point(t));
+        prev_t = t;
+    }
 
-    // interesting points
-    pts.apoapsis.map(|f| {
-        traj.t.apoapsis().map(|ap| {
-            let ap = mat2 * ap;
-            f(ap.x, ap.y);
-        })
-    });
-    pts.periapsis.map(|f| {
-        let pe = mat2 * traj.t.periapsis();
-        f(pe.x, pe.y);
-    });
+    // draw the hidden parts first so the visible parts go over them
+    for &occ in &[Occlusion::Behind, Occlusion::Inside, Occlusion::Visible] {
+        if !runs.iter().any(|run| run.0 == occ) {
+            continue;
+        }
+        ctx.push_group();
+        for (_, points) in runs.iter().filter(|run| run.0 == occ) {
+            ctx.move_to(points[0].x, points[0].y);
+            for p in &points[1..] {
+                ctx.line_to(p.x, p.y);
+            }
+        }
+        ctx.save();
+        ctx.identity_matrix();
+        renderer();
+        ctx.restore();
+        ctx.pop_group_to_source();
+        ctx.paint_with_alpha(occ.alpha());
+    }
+}
 
-    // setup source
+/// Draws a marker with `f` at `p` (in view coordinates), faded like the trajectory it's on
+/// if it's inside or behind the planet.
+fn draw_marker<F>(rc: &RenderCommon, p: Vector3<f64>, f: F) -> ()
+    where F: FnOnce(f64, f64) -> ()
+{
+    let ctx = rc.ctx;
     ctx.push_group();
-    draw_ellipse_arc(ctx, ell, 0.0, TAU);
+    f(p.x, p.y);
+    ctx.pop_group_to_source();
+    ctx.paint_with_alpha(Occlusion::of(p, rc.planet_radius).alpha());
+}
+
+/// Draws the planet, lit from the sun's direction and textured if there's a texture.
+fn draw_planet(rc: &RenderCommon, st: &State) -> () {
+    let ctx = rc.ctx;
     ctx.save();
+    let center = ctx.user_to_device(0.0, 0.0);
+    let radius = ctx.user_to_device_distance(st.body().radius, 0.0).0.abs(); // px
+
+    // we only need to shade the pixels that are actually on the screen
     ctx.identity_matrix();
-    tr_renderer();
+    let (ox, oy, ex, ey) = ctx.clip_extents();
+    let (x0, y0) = ((center.0 - radius).max(ox).floor(), (center.1 - radius).max(oy).floor());
+    let (x1, y1) = ((center.0 + radius).min(ex).ceil(), (center.1 + radius).min(ey).ceil());
+    if x1 > x0 && y1 > y0 {
+        let image = cairo::ImageSurface::create(cairo::Format::ARgb32,
+                                                (x1 - x0) as i32,
+                                                (y1 - y0) as i32);
+        if let Ok(mut image) = image {
+            shade_planet(&mut image,
+                         st,
+                         rc.proj_mat,
+                         (center.0 - x0, center.1 - y0),
+                         radius);
+            ctx.set_source_surface(&image, x0, y0);
+            ctx.paint();
+        }
+    }
     ctx.restore();
-    ctx.pop_group_to_source();
+}
+
+/// Fills `image` with the planet centered at `center` (px) and `radius` px across.
+fn shade_planet(image: &mut cairo::ImageSurface,
+                st: &State,
+                proj_mat: Rotation3<f64>,
+                center: (f64, f64),
+                radius: f64)
+                -> () {
+    let unproject = proj_mat.inverse();
+    let turn = st.rotation_angle(st.time);
+    let sun = Vector3::new(st.sun_lat.cos() * st.sun_lon.cos(),
+                           st.sun_lat.cos() * st.sun_lon.sin(),
+                           st.sun_lat.sin());
+    let (width, height) = (image.get_width() as usize, image.get_height() as usize);
+    let stride = image.get_stride() as usize;
+    let mut data = match image.get_data() {
+        Ok(data) => data,
+        Err(_) => return,
+    };
+    for y in 0..height {
+        for x in 0..width {
+            // where this pixel is in view coordinates (in body radii)
+            let u = (x as f64 + 0.5 - center.0) / radius;
+            let v = (center.1 - y as f64 - 0.5) / radius;
+            let rr = u * u + v * v;
+            // fade out over the last pixel so the edge is antialiased
+            let alpha = clamp((1.0 - rr.sqrt()) * radius + 0.5, 0.0, 1.0);
+            let pixel = if alpha > 0.0 {
+                let normal = unproject * Vector3::new(u, v, (1.0 - rr).max(0.0).sqrt());
+                let (r, g, b) = match st.texture {
+                    Some(ref texture) => {
+                        texture.sample(clamp(normal.z, -1.0, 1.0).asin(),
+                                       normal.y.atan2(normal.x) - turn)
+                    }
+                    None => PLANET_COLOR,
+                };
+                let light = AMBIENT_LIGHT + (1.0 - AMBIENT_LIGHT) * normal.dot(&sun).max(0.0);
+                // cairo wants premultiplied alpha
+                let channel = |c: f64| (c * light * alpha * 255.0).round() as u32;
+                ((alpha * 255.0).round() as u32) << 24 | channel(r) << 16 | channel(g) << 8 |
+                channel(b)
+            } else {
+                0
+            };
+            let i = y * stride + x * 4;
+            data[i..i + 4].copy_from_slice(&pixel.to_ne_bytes());
+        }
+    }
+}
 
-    // // bits inside the planet
-    // ctx.save();
-    // ctx.append_path(&inside_planet_path);
-    // ctx.clip();
-    // ctx.paint_with_alpha(INTERNAL_ALPHA);
-    // ctx.restore();
-
-    // figure out which parts are in front of the planet and behind the planet
-    let (back_path_inv, front_path_inv) = {
-        let cut_dir = orbit_norm.cross(&Vector3::z_axis());
-        let cut_angle = cut_dir.y.atan2(cut_dir.x);
-
-        ctx.arc_negative(0.0, 0.0, rc.screen_extent, cut_angle + TAU / 2.0, cut_angle);
-        let left_path_inv = ctx.copy_path();
-        ctx.new_path();
-        ctx.arc_negative(0.0, 0.0, rc.screen_extent, cut_angle, cut_angle - TAU / 2.0);
-        let right_path_inv = ctx.copy_path();
-        ctx.new_path();
-
-        if orbit_norm.z > 0.0 {
-            (left_path_inv, right_path_inv)
+/// Adds a line along the surface of the planet (given by latitudes and longitudes in rad)
+/// to the current path, leaving out the parts on the far side.
+/// `turn` is how far the planet has rotated.
+fn draw_surface_line<I>(rc: &RenderCommon, radius: f64, turn: f64, points: I) -> ()
+    where I: IntoIterator<Item = (f64, f64)>
+{
+    let mut pen_down = false;
+    for (lat, lon) in points {
+        let p = rc.proj_mat *
+                Vector3::new(lat.cos() * (lon + turn).cos(),
+                             lat.cos() * (lon + turn).sin(),
+                             lat.sin()) * radius;
+        if p.z < 0.0 {
+            pen_down = false;
+        } else if pen_down {
+            rc.ctx.line_to(p.x, p.y);
         } else {
-            (right_path_inv, left_path_inv)
+            rc.ctx.move_to(p.x, p.y);
+            pen_down = true;
         }
-    };
+    }
+}
 
-    // bits outside the planet
-    // front
-    ctx.save();
-    ctx.append_path(&inside_planet_path);
-    ctx.append_path(&front_path_inv);
-    ctx.clip();
-    ctx.append_path(&front_path_inv);
-    ctx.clip();
-    ctx.paint();
-    ctx.restore();
-    // back occluded
-    ctx.save();
-    ctx.append_path(&inside_planet_path);
-    ctx.append_path(&rc.planet_path_inv);
-    ctx.clip();
-    ctx.append_path(&back_path_inv);
-    ctx.clip();
-    ctx.paint_with_alpha(OCCLUDED_ALPHA);
-    ctx.restore();
-    // back outside
-    ctx.save();
-    ctx.append_path(&rc.planet_path);
-    ctx.append_path(&back_path_inv);
-    ctx.clip();
-    ctx.append_path(&back_path_inv);
-    ctx.clip();
-    ctx.paint();
-    ctx.restore();
+/// Draws an isosceles triangle centered on `(x, y)` that points up (or down if `size` is negative).
+fn draw_triangle(ctx: &cairo::Context, x: f64, y: f64, size: f64) -> () {
+    ctx.move_to(x, y + size / 2.0);
+    ctx.line_to(x + size / 2.0, y - size / 2.0);
+    ctx.line_to(x - size / 2.0, y - size / 2.0);
+    ctx.close_path();
 }
 
-fn draw_ellipse_arc(ctx: &cairo::Context, ellipse: Ellipse, eta1: f64, eta2: f64) -> () {
-    let el = ellipse.to_canonical();
-    ctx.save();
-    ctx.translate(el.center.x, el.center.y);
-    ctx.rotate(el.rotation);
-    if el.semi_axes.x != 0.0 && el.semi_axes.y != 0.0 {
-        ctx.scale(el.semi_axes.x, el.semi_axes.y);
-        ctx.arc(0.0, 0.0, 1.0, eta1, eta2);
-    } else {
-        // println!("line ellipse: {:?}", el);
-    }
-    ctx.restore();
+fn clamp(x: f64, min: f64, max: f64) -> f64 {
+    x.min(max).max(min)
 }
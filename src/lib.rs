@@ -0,0 +1,32 @@
+//! Keplerian orbits and the math around them.
+//!
+//! - [`conics`](conics/index.html) has the conic sections trajectories trace out.
+//! - [`orbits`](orbits/index.html) has orbital elements, Kepler's equation, transfers,
+//!   Lambert's problem, and maneuvers.
+//! - [`bodies`](bodies/index.html) has the planets and moons, J2 drift, ground tracks,
+//!   and patched conics.
+//! - [`numerical`](numerical/index.html) integrates trajectories under arbitrary forces.
+//! - [`tle`](tle/index.html) reads the two-line element sets satellites are published as,
+//!   and [`sgp4`](sgp4/index.html) propagates them the way they're meant to be.
+//! - [`ccsds`](ccsds/index.html) reads and writes CCSDS orbit parameter and ephemeris messages.
+//!
+//! Distances are in km, times in s, and angles in rad unless it says otherwise.
+
+#![allow(clippy::redundant_field_names, clippy::unused_unit)]
+#![allow(clippy::too_many_arguments, clippy::wrong_self_convention)]
+
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+extern crate tau;
+extern crate nalgebra as na;
+
+pub mod conics;
+pub mod orbits;
+pub mod bodies;
+pub mod numerical;
+pub mod tle;
+pub mod sgp4;
+pub mod ccsds;
@@ -1,35 +1,58 @@
-#![feature(unboxed_closures,fn_traits)]
+#![allow(clippy::redundant_field_names, clippy::unused_unit)]
 
+extern crate orbit_sim;
+#[cfg(feature = "gui")]
 #[macro_use]
-extern crate lazy_static;
+extern crate serde_derive;
+#[cfg(feature = "gui")]
+extern crate serde;
+extern crate serde_json;
 extern crate rand;
 extern crate typenum;
 extern crate generic_array;
 extern crate num_traits;
+#[cfg(feature = "gui")]
 extern crate gtk;
+#[cfg(feature = "gui")]
+extern crate gdk;
+#[cfg(feature = "gui")]
 extern crate cairo;
+#[cfg(feature = "gui")]
+extern crate cairo_sys;
 extern crate tau;
 extern crate nalgebra as na;
 
-use std::rc::Rc;
+use std::env;
+use std::process;
 
-mod orbits;
-mod conics;
+mod cli;
+#[cfg(feature = "gui")]
 mod gui;
 
 fn main() -> () {
-    // use rand::distributions::{IndependentSample, Range};
-    // use tau::TAU;
-    // let range = Range::new(-TAU, TAU);
-    // let mut rng = rand::thread_rng();
-    // for e in 0..100 {
-    //     for _ in 0..2000 {
-    //         let m = range.ind_sample(&mut rng);
-    //         let ea = orbits::approx_inv_kepler(e as f64 / 100.0, m);
-    //         // println!("m: {:?} ({:?}), ea: :{:?}", m, orbits::kepler(e, ea), ea);
-    //     }
-    // }
-    gui::main(Rc::default());
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    match args.first() {
+        Some(command) if cli::COMMANDS.contains(&command.as_str()) => cli::main(args),
+        _ => view(args),
+    }
+}
+
+#[cfg(feature = "gui")]
+fn view(args: &[String]) -> Result<(), String> {
+    gui::run(args)
+}
+
+#[cfg(not(feature = "gui"))]
+fn view(_: &[String]) -> Result<(), String> {
+    Err(format!("orbit-sim was built without the viewer (the \"gui\" feature)\n\n{}",
+                cli::USAGE))
 }
 
 // let e1 = Ellipse::Canonical(CanonicalEllipseRepr {
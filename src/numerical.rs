@@ -0,0 +1,359 @@
+//! Integrating trajectories numerically under arbitrary forces.
+
+use std::error::Error;
+use std::fmt;
+use na::{U3, Vector3, Vector6};
+
+use orbits::Trajectory;
+use bodies::{self, Body};
+
+/// Where something is and how fast it's going at some point in time.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct StateVector {
+    pub time: f64, // s
+    pub position: Vector3<f64>, // km
+    pub velocity: Vector3<f64>, // km/s
+}
+
+impl StateVector {
+    /// Computes the osculating trajectory (and the mean anomaly on it)
+    /// given a standard gravitational parameter `mu`.
+    pub fn to_trajectory(&self, mu: f64) -> (Trajectory, f64) {
+        Trajectory::from_state(mu, self.position, self.velocity)
+    }
+
+    fn to_vector(&self) -> Vector6<f64> {
+        let (p, v) = (self.position, self.velocity);
+        Vector6::new(p.x, p.y, p.z, v.x, v.y, v.z)
+    }
+
+    fn from_vector(time: f64, y: &Vector6<f64>) -> StateVector {
+        StateVector {
+            time: time,
+            position: y.fixed_rows::<U3>(0).into_owned(),
+            velocity: y.fixed_rows::<U3>(3).into_owned(),
+        }
+    }
+}
+
+/// Something that accelerates a spacecraft.
+pub trait ForceModel {
+    /// Computes the acceleration (km/s²) on something at `pos` (km) moving at `vel` (km/s)
+    /// at `time` (s).
+    fn acceleration(&self, time: f64, pos: &Vector3<f64>, vel: &Vector3<f64>) -> Vector3<f64>;
+}
+
+/// Newtonian gravity from the central body.
+#[derive(Clone,Copy,Debug)]
+pub struct PointMass {
+    pub mu: f64, // km³/s²
+}
+
+impl ForceModel for PointMass {
+    fn acceleration(&self, _: f64, pos: &Vector3<f64>, _: &Vector3<f64>) -> Vector3<f64> {
+        -self.mu / pos.norm().powi(3) * pos
+    }
+}
+
+/// The extra gravity from the central body's oblateness.
+/// This assumes positions are relative to the body's equator.
+#[derive(Clone,Copy,Debug)]
+pub struct J2 {
+    pub mu: f64, // km³/s²
+    pub j2: f64,
+    pub radius: f64, // km (equatorial)
+}
+
+impl J2 {
+    /// Takes the constants from `body`.
+    pub fn from_body(body: &Body) -> J2 {
+        J2 {
+            mu: body.mu,
+            j2: body.j2,
+            radius: body.radius,
+        }
+    }
+}
+
+impl ForceModel for J2 {
+    fn acceleration(&self, _: f64, pos: &Vector3<f64>, _: &Vector3<f64>) -> Vector3<f64> {
+        let r2 = pos.norm_squared();
+        let k = -1.5 * self.j2 * self.mu * self.radius * self.radius / r2.powi(2) / r2.sqrt();
+        let zz = 5.0 * pos.z * pos.z / r2;
+        k * Vector3::new(pos.x * (1.0 - zz), pos.y * (1.0 - zz), pos.z * (3.0 - zz))
+    }
+}
+
+/// Gravity from another body (relative to the central body, which it also pulls on).
+pub struct ThirdBody {
+    pub mu: f64, // km³/s²
+    /// Where the other body is relative to the central body (and its equator) at a given time.
+    pub position: Box<dyn Fn(f64) -> Vector3<f64>>,
+}
+
+impl ThirdBody {
+    /// Gravity from the `other`th of `bodies` on something around the `central`th.
+    pub fn from_bodies(bodies: &[Body], central: usize, other: usize) -> ThirdBody {
+        let bodies = bodies.to_vec();
+        ThirdBody {
+            mu: bodies[other].mu,
+            position: Box::new(move |time| {
+                bodies::relative_position(&bodies, central, other, time)
+            }),
+        }
+    }
+}
+
+impl ForceModel for ThirdBody {
+    fn acceleration(&self, time: f64, pos: &Vector3<f64>, _: &Vector3<f64>) -> Vector3<f64> {
+        let other = (self.position)(time);
+        let rel = other - pos;
+        self.mu * (rel / rel.norm().powi(3) - other / other.norm().powi(3))
+    }
+}
+
+/// A constant acceleration from an engine, in the same directions a `Maneuver` uses.
+#[derive(Clone,Copy,Debug)]
+pub struct ConstantThrust {
+    pub prograde: f64, // km/s²
+    pub normal: f64, // km/s²
+    pub radial: f64, // km/s² (outward)
+}
+
+impl ForceModel for ConstantThrust {
+    fn acceleration(&self, _: f64, pos: &Vector3<f64>, vel: &Vector3<f64>) -> Vector3<f64> {
+        let prograde = vel.normalize();
+        let normal = pos.cross(vel).normalize();
+        let radial = prograde.cross(&normal);
+        self.prograde * prograde + self.normal * normal + self.radial * radial
+    }
+}
+
+impl<'a> ForceModel for [Box<dyn ForceModel + 'a>] {
+    fn acceleration(&self, time: f64, pos: &Vector3<f64>, vel: &Vector3<f64>) -> Vector3<f64> {
+        self.iter().fold(Vector3::zeros(), |acc, f| acc + f.acceleration(time, pos, vel))
+    }
+}
+
+/// How to step a state forward in time.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Integrator {
+    /// The classic fourth-order Runge-Kutta method with a fixed step (s).
+    Rk4 { step: f64 },
+    /// The Dormand-Prince 5(4) method, adjusting the step to keep the local error
+    /// (relative to each component's size, or 1 if that's smaller) within `tolerance`.
+    DormandPrince { tolerance: f64 },
+    /// The (symplectic) velocity Verlet method with a fixed step (s).
+    /// This is only symplectic for forces that don't depend on velocity.
+    Leapfrog { step: f64 },
+}
+
+/// Why `propagate` stopped before the end time.
+/// Each one has the states up to where it stopped, the last of which says when that was.
+#[derive(Clone,Debug,PartialEq)]
+pub enum PropagationError {
+    /// Dormand-Prince took `DP_MAX_STEPS` steps without getting there.
+    TooManySteps(Vec<StateVector>),
+    /// Dormand-Prince's step got too small to move the time along,
+    /// because the tolerance is too tight or the state stopped being finite.
+    StepTooSmall(Vec<StateVector>),
+    /// The step (or Dormand-Prince's tolerance) wasn't positive and finite,
+    /// so propagation didn't start.
+    InvalidStep(Vec<StateVector>),
+}
+
+impl PropagationError {
+    /// Returns the states up to where propagation stopped.
+    pub fn history(&self) -> &[StateVector] {
+        match *self {
+            PropagationError::TooManySteps(ref history) |
+            PropagationError::StepTooSmall(ref history) |
+            PropagationError::InvalidStep(ref history) => history,
+        }
+    }
+}
+
+impl fmt::Display for PropagationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            PropagationError::TooManySteps(_) => "took too many steps",
+            PropagationError::StepTooSmall(_) => "step got too small",
+            PropagationError::InvalidStep(_) => "step has to be positive",
+        };
+        let last = self.history().last().map_or(f64::NAN, |s| s.time);
+        write!(f, "{} (stopped at {} s)", reason, last)
+    }
+}
+
+impl Error for PropagationError {}
+
+const DP_MAX_STEPS: usize = 1000000;
+const DP_SAFETY: f64 = 0.9;
+const DP_MIN_SCALE: f64 = 0.2;
+const DP_MAX_SCALE: f64 = 5.0;
+
+/// Numerically integrates `initial` under `forces` until `end_time` (which may be earlier),
+/// returning the state after every step (starting with `initial` and ending at `end_time`).
+/// Only `DormandPrince` can fail to get there, but any of them fails to start with a step
+/// or tolerance that isn't positive and finite.
+pub fn propagate<F>(forces: &F,
+                    integrator: Integrator,
+                    initial: StateVector,
+                    end_time: f64)
+                    -> Result<Vec<StateVector>, PropagationError>
+    where F: ForceModel + ?Sized
+{
+    let derivative = |t: f64, y: &Vector6<f64>| {
+        let s = StateVector::from_vector(t, y);
+        let a = forces.acceleration(t, &s.position, &s.velocity);
+        Vector6::new(s.velocity.x, s.velocity.y, s.velocity.z, a.x, a.y, a.z)
+    };
+    let span = end_time - initial.time;
+    let mut history = vec![initial];
+    let size = match integrator {
+        Integrator::Rk4 { step } |
+        Integrator::Leapfrog { step } => step,
+        Integrator::DormandPrince { tolerance } => tolerance,
+    };
+    if !(size > 0.0 && size.is_finite()) {
+        return Err(PropagationError::InvalidStep(history));
+    }
+    if span == 0.0 {
+        return Ok(history);
+    }
+    let mut t = initial.time;
+    let mut y = initial.to_vector();
+    match integrator {
+        Integrator::Rk4 { step } |
+        Integrator::Leapfrog { step } => {
+            let steps = (span.abs() / step).ceil().max(1.0) as usize;
+            let h = span / steps as f64;
+            for i in 0..steps {
+                y = if let Integrator::Rk4 { .. } = integrator {
+                    rk4_step(&derivative, t, &y, h)
+                } else {
+                    leapfrog_step(&derivative, t, &y, h)
+                };
+                t = initial.time + h * (i + 1) as f64;
+                history.push(StateVector::from_vector(t, &y));
+            }
+        }
+        Integrator::DormandPrince { tolerance } => {
+            let mut h = span / 100.0;
+            for _ in 0..DP_MAX_STEPS {
+                if (end_time - t) * span.signum() <= 0.0 {
+                    return Ok(history);
+                }
+                if (t + h - end_time) * span.signum() > 0.0 {
+                    h = end_time - t;
+                }
+                if t + h == t || !h.is_finite() {
+                    return Err(PropagationError::StepTooSmall(history));
+                }
+                let (y_new, err) = dormand_prince_step(&derivative, t, &y, h);
+                // (`f64::max` ignores NaNs, so a step that blows up has to be rejected explicitly)
+                let err_norm = if y_new.iter().all(|x| x.is_finite()) {
+                    err.iter()
+                        .zip(y.iter().zip(y_new.iter()))
+                        .map(|(e, (a, b))| e.abs() / (tolerance * a.abs().max(b.abs()).max(1.0)))
+                        .fold(0.0, f64::max)
+                } else {
+                    f64::INFINITY
+                };
+                if err_norm <= 1.0 {
+                    t += h;
+                    y = y_new;
+                    history.push(StateVector::from_vector(t, &y));
+                }
+                let scale = DP_SAFETY * err_norm.powf(-1.0 / 5.0);
+                h *= scale.clamp(DP_MIN_SCALE, DP_MAX_SCALE);
+            }
+            if (end_time - t) * span.signum() > 0.0 {
+                return Err(PropagationError::TooManySteps(history));
+            }
+        }
+    }
+    Ok(history)
+}
+
+fn rk4_step<D>(f: &D, t: f64, y: &Vector6<f64>, h: f64) -> Vector6<f64>
+    where D: Fn(f64, &Vector6<f64>) -> Vector6<f64>
+{
+    let k1 = f(t, y);
+    let k2 = f(t + h / 2.0, &(y + k1 * (h / 2.0)));
+    let k3 = f(t + h / 2.0, &(y + k2 * (h / 2.0)));
+    let k4 = f(t + h, &(y + k3 * h));
+    y + (k1 + k2 * 2.0 + k3 * 2.0 + k4) * (h / 6.0)
+}
+
+fn leapfrog_step<D>(f: &D, t: f64, y: &Vector6<f64>, h: f64) -> Vector6<f64>
+    where D: Fn(f64, &Vector6<f64>) -> Vector6<f64>
+{
+    // the velocity half of `f` is just the velocity, so we only need the acceleration half
+    let a0 = f(t, y).fixed_rows::<U3>(3).into_owned();
+    let v_half = y.fixed_rows::<U3>(3) + a0 * (h / 2.0);
+    let pos = y.fixed_rows::<U3>(0) + v_half * h;
+    let mid = Vector6::new(pos.x, pos.y, pos.z, v_half.x, v_half.y, v_half.z);
+    let a1 = f(t + h, &mid).fixed_rows::<U3>(3).into_owned();
+    let vel = v_half + a1 * (h / 2.0);
+    Vector6::new(pos.x, pos.y, pos.z, vel.x, vel.y, vel.z)
+}
+
+// the Dormand-Prince tableau
+const DP_C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const DP_A: [[f64; 6]; 7] = [[0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                             [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                             [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+                             [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+                             [19372.0 / 6561.0,
+                              -25360.0 / 2187.0,
+                              64448.0 / 6561.0,
+                              -212.0 / 729.0,
+                              0.0,
+                              0.0],
+                             [9017.0 / 3168.0,
+                              -355.0 / 33.0,
+                              46732.0 / 5247.0,
+                              49.0 / 176.0,
+                              -5103.0 / 18656.0,
+                              0.0],
+                             [35.0 / 384.0,
+                              0.0,
+                              500.0 / 1113.0,
+                              125.0 / 192.0,
+                              -2187.0 / 6784.0,
+                              11.0 / 84.0]];
+/// The fifth-order weights (which are the same as the last row of `DP_A`).
+const DP_B: [f64; 7] = [35.0 / 384.0,
+                        0.0,
+                        500.0 / 1113.0,
+                        125.0 / 192.0,
+                        -2187.0 / 6784.0,
+                        11.0 / 84.0,
+                        0.0];
+/// The fourth-order weights.
+const DP_B_STAR: [f64; 7] = [5179.0 / 57600.0,
+                             0.0,
+                             7571.0 / 16695.0,
+                             393.0 / 640.0,
+                             -92097.0 / 339200.0,
+                             187.0 / 2100.0,
+                             1.0 / 40.0];
+
+/// Returns the new state and an estimate of the error in it.
+fn dormand_prince_step<D>(f: &D,
+                          t: f64,
+                          y: &Vector6<f64>,
+                          h: f64)
+                          -> (Vector6<f64>, Vector6<f64>)
+    where D: Fn(f64, &Vector6<f64>) -> Vector6<f64>
+{
+    let mut k = [Vector6::zeros(); 7];
+    for i in 0..7 {
+        let y_i = (0..i).fold(*y, |acc, j| acc + k[j] * (h * DP_A[i][j]));
+        k[i] = f(t + DP_C[i] * h, &y_i);
+    }
+    let y_new = (0..7).fold(*y, |acc, i| acc + k[i] * (h * DP_B[i]));
+    let err = (0..7).fold(Vector6::zeros(), |acc, i| acc + k[i] * (h * (DP_B[i] - DP_B_STAR[i])));
+    (y_new, err)
+}
@@ -1,18 +1,165 @@
+//! Orbital elements, Kepler's equation, transfers, Lambert's problem, and maneuvers.
+
+use std::error::Error;
+use std::fmt;
 use tau::TAU;
-use na::{Matrix3, Point2, Vector2, Vector3, Rotation3, Unit};
+use na::{Point2, Vector2, Vector3, Rotation3};
 
 use conics::*;
 
-#[derive(Clone,Copy,Debug,Default)]
+/// A Keplerian trajectory around some central body: which plane it's in and its shape.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Serialize,Deserialize)]
 pub struct Trajectory {
+    #[serde(rename = "plane")]
     pub p: Plane,
+    #[serde(rename = "shape")]
     pub t: PlanarTrajectory,
 }
 
-#[derive(Clone,Copy,Debug,Default)]
+/// Where a body is along its trajectory at some point in time.
+#[derive(Clone,Copy,Debug)]
+pub struct TrajectoryState {
+    pub mean_anom: f64,
+    /// See `PlanarTrajectory::ecc_anom` for what this is for open trajectories.
+    pub ecc_anom: f64,
+    pub true_anom: f64,
+    pub position: Vector3<f64>, // km
+}
+
+/// When converting from a state vector, we treat an orbit as circular, parabolic, or equatorial
+/// if its eccentricity, its eccentricity minus one, or its relative node vector length
+/// (respectively) is smaller than this.
+const DEGENERATE_TOLERANCE: f64 = 1e-11;
+
+impl Trajectory {
+    /// Computes the position (km) and velocity (km/s) at the given mean anomaly
+    /// given a standard gravitational parameter `mu`.
+    pub fn to_state(&self, mu: f64, mean_anom: f64) -> (Vector3<f64>, Vector3<f64>) {
+        self.state_at_true_anom(mu, self.t.true_anom(mean_anom))
+    }
+
+    /// Computes the position (km) and velocity (km/s) at the given true anomaly
+    /// given a standard gravitational parameter `mu`.
+    pub fn state_at_true_anom(&self, mu: f64, true_anom: f64) -> (Vector3<f64>, Vector3<f64>) {
+        let pos = self.t.position(true_anom);
+        let vel = self.t.velocity(mu, true_anom);
+        let mat = self.p.to_matrix();
+        (mat * Vector3::new(pos.x, pos.y, 0.0), mat * Vector3::new(vel.x, vel.y, 0.0))
+    }
+
+    /// Computes where a body on this trajectory is at time `time` (s)
+    /// given a standard gravitational parameter `mu`
+    /// and that the body is at `mean_anom0` at time `epoch` (s).
+    pub fn propagate(&self, mu: f64, epoch: f64, time: f64) -> TrajectoryState {
+        let mean_anom = self.t.mean_anom0 + self.t.mean_motion(mu) * (time - epoch);
+        let ecc_anom = self.t.ecc_anom(mean_anom);
+        let true_anom = self.t.true_anom_from_ecc(ecc_anom);
+        let pos = self.t.position(true_anom);
+        TrajectoryState {
+            mean_anom: mean_anom,
+            ecc_anom: ecc_anom,
+            true_anom: true_anom,
+            position: self.p.to_matrix() * Vector3::new(pos.x, pos.y, 0.0),
+        }
+    }
+
+    /// Computes the true anomaly where this trajectory points in the direction of `dir`
+    /// (or its projection onto this trajectory's plane).
+    pub fn true_anom_towards(&self, dir: &Vector3<f64>) -> f64 {
+        let d = self.p.to_matrix().inverse() * dir;
+        d.y.atan2(d.x)
+    }
+
+    /// Computes the normal vector of this trajectory's plane.
+    pub fn normal(&self) -> Vector3<f64> {
+        self.p.to_matrix() * Vector3::z()
+    }
+
+    /// Computes the ascending node location (if it exists) in the coordinate system of `t`.
+    pub fn ascending_node(&self) -> Option<Vector2<f64>> {
+        self.node(-self.p.arg_peri)
+    }
+
+    /// Computes the descending node location (if it exists) in the coordinate system of `t`.
+    pub fn descending_node(&self) -> Option<Vector2<f64>> {
+        self.node(TAU / 2.0 - self.p.arg_peri)
+    }
+
+    fn node(&self, true_anom: f64) -> Option<Vector2<f64>> {
+        // equatorial orbits don't have well-defined nodes
+        // and open trajectories don't necessarily reach them
+        if self.p.inclination.sin().abs() > DEGENERATE_TOLERANCE && self.t.reaches(true_anom) {
+            Some(self.t.position(true_anom))
+        } else {
+            None
+        }
+    }
+
+    /// Computes the trajectory through the given position (km) and velocity (km/s)
+    /// given a standard gravitational parameter `mu`.
+    /// Also returns the mean anomaly of the given state.
+    ///
+    /// For equatorial orbits, the longitude of the ascending node is zero.
+    /// For circular orbits, the argument of the periapsis is zero
+    /// (i.e. the periapsis is at the ascending node).
+    pub fn from_state(mu: f64, pos: Vector3<f64>, vel: Vector3<f64>) -> (Trajectory, f64) {
+        let h = pos.cross(&vel);
+        let h_norm = h.norm();
+        let inclination = (h.z / h_norm).clamp(-1.0, 1.0).acos();
+
+        let node = Vector3::new(-h.y, h.x, 0.0);
+        let lon_asc_node = if node.norm() > DEGENERATE_TOLERANCE * h_norm {
+            node.y.atan2(node.x)
+        } else {
+            0.0
+        };
+
+        let r = pos.norm();
+        let ecc_vec = ((vel.norm_squared() - mu / r) * pos - pos.dot(&vel) * vel) / mu;
+        let eccentr = match ecc_vec.norm() {
+            // the elliptic and hyperbolic anomalies are badly conditioned around here
+            e if (e - 1.0).abs() < DEGENERATE_TOLERANCE => 1.0,
+            e => e,
+        };
+        let arg_peri = if eccentr > DEGENERATE_TOLERANCE {
+            let node_mat = Plane {
+                    lon_asc_node: lon_asc_node,
+                    inclination: inclination,
+                    arg_peri: 0.0,
+                }
+                .to_matrix();
+            let e = node_mat.inverse() * ecc_vec;
+            e.y.atan2(e.x)
+        } else {
+            0.0
+        };
+
+        let p = Plane {
+            lon_asc_node: lon_asc_node,
+            inclination: inclination,
+            arg_peri: arg_peri,
+        };
+        let t = PlanarTrajectory {
+            periapsis: h_norm * h_norm / mu / (1.0 + eccentr),
+            eccentr: eccentr,
+            mean_anom0: 0.0,
+            mean_anom1: TAU,
+        };
+        let planar_pos = p.to_matrix().inverse() * pos;
+        let true_anom = planar_pos.y.atan2(planar_pos.x);
+        (Trajectory { p: p, t: t }, t.mean_anom(true_anom))
+    }
+}
+
+/// The orientation of an orbital plane (and of the periapsis within it)
+/// relative to the central body's equator.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Serialize,Deserialize)]
 pub struct Plane {
+    #[serde(rename = "lon_asc_node_rad")]
     pub lon_asc_node: f64,
+    #[serde(rename = "inclination_rad")]
     pub inclination: f64,
+    #[serde(rename = "arg_peri_rad")]
     pub arg_peri: f64,
 }
 
@@ -27,25 +174,44 @@ impl Plane {
     }
 }
 
-#[derive(Clone,Copy,Debug)]
+/// The shape of a trajectory within its plane, with the periapsis along the x-axis.
+///
+/// `mean_anom0` and `mean_anom1` are where the part that gets drawn starts and ends;
+/// a full orbit spans `TAU`.
+#[derive(Clone,Copy,Debug,PartialEq,Serialize,Deserialize)]
 pub struct PlanarTrajectory {
+    #[serde(rename = "periapsis_km")]
     pub periapsis: f64, // km
+    #[serde(rename = "eccentricity")]
     pub eccentr: f64,
+    #[serde(rename = "mean_anom0_rad")]
     pub mean_anom0: f64,
+    #[serde(rename = "mean_anom1_rad")]
     pub mean_anom1: f64,
 }
 
 impl PlanarTrajectory {
-    /// Computes the period of this trajectory given a standard gravitational parameter `mu`.
-    pub fn period(&self, mu: f64) -> f64 {
-        let r = self.periapsis / (1.0 - self.eccentr);
-        TAU * (r.powi(3) / mu).sqrt()
+    /// Computes the semi-major axis of this trajectory.
+    /// It is negative for hyperbolic trajectories and infinite for parabolic ones.
+    pub fn semi_major_axis(&self) -> f64 {
+        self.periapsis / (1.0 - self.eccentr)
+    }
+
+    /// Computes the period (if it exists) of this trajectory
+    /// given a standard gravitational parameter `mu`.
+    pub fn period(&self, mu: f64) -> Option<f64> {
+        if self.eccentr < 1.0 {
+            let a = self.semi_major_axis();
+            Some(TAU * (a.powi(3) / mu).sqrt())
+        } else {
+            None
+        }
     }
 
     /// Computes the apoapsis location (if it exists) in this coordinate system.
     pub fn apoapsis(&self) -> Option<Vector2<f64>> {
         if self.eccentr < 1.0 {
-            let a = self.periapsis / (1.0 - self.eccentr);
+            let a = self.semi_major_axis();
             Some(Vector2::new(self.periapsis - 2.0 * a, 0.0))
         } else {
             None
@@ -57,29 +223,151 @@ impl PlanarTrajectory {
         Vector2::new(self.periapsis, 0.0)
     }
 
-    pub fn to_ellipse(&self) -> Ellipse {
-        assert!(self.eccentr < 1.0);
-        let a = self.periapsis / (1.0 - self.eccentr);
-        let b = a * (1.0 - self.eccentr.powi(2)).sqrt();
-        Ellipse::Canonical(CanonicalEllipseRepr {
-            semi_axes: Vector2::new(a, b),
-            center: Point2::new(self.periapsis - a, 0.0),
-            rotation: 0.0,
-        })
+    /// Computes the semi-latus rectum of this trajectory.
+    pub fn semi_latus_rectum(&self) -> f64 {
+        self.periapsis * (1.0 + self.eccentr)
+    }
+
+    /// Checks whether this trajectory ever gets to the given true anomaly.
+    pub fn reaches(&self, true_anom: f64) -> bool {
+        1.0 + self.eccentr * true_anom.cos() > 0.0
+    }
+
+    /// Computes the location at the given true anomaly in this coordinate system.
+    pub fn position(&self, true_anom: f64) -> Vector2<f64> {
+        let r = self.semi_latus_rectum() / (1.0 + self.eccentr * true_anom.cos());
+        let (s, c) = true_anom.sin_cos();
+        Vector2::new(r * c, r * s)
+    }
+
+    /// Computes the velocity at the given true anomaly in this coordinate system
+    /// given a standard gravitational parameter `mu`.
+    pub fn velocity(&self, mu: f64, true_anom: f64) -> Vector2<f64> {
+        let k = (mu / self.semi_latus_rectum()).sqrt();
+        let (s, c) = true_anom.sin_cos();
+        Vector2::new(-k * s, k * (self.eccentr + c))
+    }
+
+    /// Computes the mean motion (rad/s) of this trajectory
+    /// given a standard gravitational parameter `mu`.
+    /// For parabolic trajectories, this is the rate of change of Barker's mean anomaly.
+    pub fn mean_motion(&self, mu: f64) -> f64 {
+        if self.eccentr == 1.0 {
+            (mu / (2.0 * self.periapsis.powi(3))).sqrt()
+        } else {
+            (mu / self.semi_major_axis().abs().powi(3)).sqrt()
+        }
+    }
+
+    /// Computes the eccentric anomaly at the given mean anomaly.
+    /// For hyperbolic trajectories, this is the hyperbolic anomaly instead
+    /// and for parabolic trajectories, this is `tan(ν/2)` instead.
+    /// For closed orbits, whole revolutions of mean anomaly are carried over to the result.
+    pub fn ecc_anom(&self, mean_anom: f64) -> f64 {
+        let e = self.eccentr;
+        if e < 1.0 {
+            let revs = (mean_anom / TAU).round();
+            approx_inv_kepler(e, mean_anom - revs * TAU) + revs * TAU
+        } else if e == 1.0 {
+            inv_barker(mean_anom)
+        } else {
+            approx_inv_kepler_hyp(e, mean_anom)
+        }
+    }
+
+    /// Computes the true anomaly at the given eccentric anomaly (see `ecc_anom`).
+    /// For closed orbits, whole revolutions of eccentric anomaly are carried over to the result.
+    pub fn true_anom_from_ecc(&self, ecc_anom: f64) -> f64 {
+        let e = self.eccentr;
+        if e < 1.0 {
+            let revs = (ecc_anom / TAU).round();
+            let (s, c) = ((ecc_anom - revs * TAU) / 2.0).sin_cos();
+            2.0 * ((1.0 + e).sqrt() * s).atan2((1.0 - e).sqrt() * c) + revs * TAU
+        } else if e == 1.0 {
+            2.0 * ecc_anom.atan()
+        } else {
+            2.0 * (((e + 1.0) / (e - 1.0)).sqrt() * (ecc_anom / 2.0).tanh()).atan()
+        }
+    }
+
+    /// Computes the true anomaly at the given mean anomaly.
+    /// For closed orbits, whole revolutions of mean anomaly are carried over to the result.
+    pub fn true_anom(&self, mean_anom: f64) -> f64 {
+        self.true_anom_from_ecc(self.ecc_anom(mean_anom))
+    }
+
+    /// Computes the mean anomaly at the given true anomaly.
+    /// This is the inverse of `true_anom`.
+    pub fn mean_anom(&self, true_anom: f64) -> f64 {
+        let e = self.eccentr;
+        if e < 1.0 {
+            let revs = (true_anom / TAU).round();
+            let (s, c) = ((true_anom - revs * TAU) / 2.0).sin_cos();
+            let ec_an = 2.0 * ((1.0 - e).sqrt() * s).atan2((1.0 + e).sqrt() * c);
+            kepler(e, ec_an) + revs * TAU
+        } else if e == 1.0 {
+            barker((true_anom / 2.0).tan())
+        } else {
+            let hyp_an = 2.0 * (((e - 1.0) / (e + 1.0)).sqrt() * (true_anom / 2.0).tan()).atanh();
+            kepler_hyp(e, hyp_an)
+        }
+    }
+
+    /// Computes the ellipse this trajectory lies on, if it's closed.
+    pub fn to_ellipse(&self) -> Option<Ellipse> {
+        match self.to_conic() {
+            Conic::Ellipse(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Computes the conic section this trajectory lies on.
+    /// For open trajectories, only the branch around the central body is represented.
+    /// The point at hyperbolic anomaly `H` (or at `D = tan(ν/2)` for parabolic trajectories)
+    /// is at parameter `-H` (or `-D`) of the conic.
+    pub fn to_conic(&self) -> Conic {
+        let q = self.periapsis;
+        let e = self.eccentr;
+        if e < 1.0 {
+            let a = self.semi_major_axis();
+            let b = a * (1.0 - e.powi(2)).sqrt();
+            Conic::Ellipse(Ellipse::Canonical(CanonicalEllipseRepr {
+                semi_axes: Vector2::new(a, b),
+                center: Point2::new(q - a, 0.0),
+                rotation: 0.0,
+            }))
+        } else if e == 1.0 {
+            Conic::Parabola(CanonicalParabolaRepr {
+                focal_length: q,
+                vertex: Point2::new(q, 0.0),
+                rotation: TAU / 2.0,
+            })
+        } else {
+            let a = -self.semi_major_axis();
+            let b = a * (e.powi(2) - 1.0).sqrt();
+            Conic::Hyperbola(CanonicalHyperbolaRepr {
+                semi_axes: Vector2::new(a, b),
+                center: Point2::new(q + a, 0.0),
+                rotation: TAU / 2.0,
+            })
+        }
     }
 }
 
 const MAX_ITERATIONS: u8 = 20;
 const ACCURACY: f64 = 1e-15;
+/// Solves Kepler's equation `M = E - e sin E` for the eccentric anomaly `E`.
+/// The mean anomaly must be in `[-TAU, TAU]`.
 pub fn approx_inv_kepler(ecc: f64, mean_anom: f64) -> f64 {
-    assert!(-TAU <= mean_anom && mean_anom <= TAU,
+    assert!((-TAU..=TAU).contains(&mean_anom),
             "{:?} must be in the range [-TAU,TAU]",
             mean_anom);
 
     let mut ec_an = if ecc < 0.8 {
         mean_anom
     } else {
-        mean_anom.signum() * TAU / 2.0
+        // the cube root is for near-parabolic orbits, where `E - sin E` dominates
+        mean_anom.signum() * (TAU / 2.0).min((6.0 * mean_anom.abs() / ecc).cbrt())
     };
     for _ in 0..MAX_ITERATIONS {
         let f = ec_an - ecc * ec_an.sin() - mean_anom;
@@ -99,6 +387,502 @@ pub fn approx_inv_kepler(ecc: f64, mean_anom: f64) -> f64 {
            mean_anom);
 }
 
+/// Computes the mean anomaly from the eccentric anomaly using Kepler's equation.
 pub fn kepler(ecc: f64, ecc_anom: f64) -> f64 {
     ecc_anom - ecc * ecc_anom.sin()
 }
+
+/// Solves the hyperbolic Kepler's equation `M = e sinh H - H` for the hyperbolic anomaly `H`.
+pub fn approx_inv_kepler_hyp(ecc: f64, mean_anom: f64) -> f64 {
+    assert!(ecc > 1.0, "{:?} must be greater than 1", ecc);
+
+    // the cube root is for near-parabolic trajectories, where `sinh H - H` dominates
+    let m = mean_anom.abs();
+    let mut hyp_an = mean_anom.signum() * (2.0 * m / ecc + 1.8).ln().min((6.0 * m / ecc).cbrt());
+    for _ in 0..MAX_ITERATIONS {
+        let f = ecc * hyp_an.sinh() - hyp_an - mean_anom;
+        if f.abs() < ACCURACY {
+            return hyp_an;
+        }
+        let prev_hyp_an = hyp_an;
+        hyp_an -= f / (ecc * hyp_an.cosh() - 1.0);
+        if (prev_hyp_an - hyp_an).abs() <= ACCURACY * hyp_an.abs() {
+            return hyp_an;
+        }
+    }
+    panic!("newton's failed to converge after {:?} iterations: \
+            approx_inv_kepler_hyp({:?}, {:?})",
+           MAX_ITERATIONS,
+           ecc,
+           mean_anom);
+}
+
+/// Computes the mean anomaly from the hyperbolic anomaly.
+pub fn kepler_hyp(ecc: f64, hyp_anom: f64) -> f64 {
+    ecc * hyp_anom.sinh() - hyp_anom
+}
+
+/// Solves Barker's equation `M = D + D³/3` for `D = tan(ν/2)`.
+/// For a parabolic trajectory, `M = sqrt(mu / (2 q³)) t` where `q` is the periapsis distance.
+pub fn inv_barker(mean_anom: f64) -> f64 {
+    // Cardano's formula, arranged to avoid cancellation
+    let y = 1.5 * mean_anom.abs();
+    let s = (y + (y * y + 1.0).sqrt()).cbrt();
+    mean_anom.signum() * (s - 1.0 / s)
+}
+
+/// Computes the mean anomaly of a parabolic trajectory from `D = tan(ν/2)`.
+pub fn barker(tan_half_true_anom: f64) -> f64 {
+    tan_half_true_anom + tan_half_true_anom.powi(3) / 3.0
+}
+
+/// Why `universal_kepler` couldn't find a solution.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum KeplerError {
+    /// One of the arguments was NaN or infinite.
+    InvalidArgument,
+    /// The solver failed to converge after the given number of iterations.
+    NoConvergence(u8),
+}
+
+impl fmt::Display for KeplerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeplerError::InvalidArgument => write!(f, "argument was not finite"),
+            KeplerError::NoConvergence(n) => {
+                write!(f, "failed to converge after {} iterations", n)
+            }
+        }
+    }
+}
+
+impl Error for KeplerError {}
+
+const UNIVERSAL_MAX_ITERATIONS: u8 = 50;
+const LAGUERRE_N: f64 = 5.0;
+/// How close to 0 `alpha r0` has to be for `universal_kepler` to start from Barker's equation.
+const PARABOLIC_GUESS_LIMIT: f64 = 1e-2;
+/// Solves the universal Kepler's equation for the universal anomaly `χ` (in km^½),
+/// `dt` seconds after passing a point at distance `r0` with radial velocity `vr0`.
+/// `alpha` is the reciprocal of the semi-major axis, so this works for any eccentricity.
+///
+/// This uses the Laguerre-Conway method, which converges from pretty much any initial guess.
+pub fn universal_kepler(mu: f64,
+                        r0: f64,
+                        vr0: f64,
+                        alpha: f64,
+                        dt: f64)
+                        -> Result<f64, KeplerError> {
+    if ![mu, r0, vr0, alpha, dt].iter().all(|x| x.is_finite()) {
+        return Err(KeplerError::InvalidArgument);
+    }
+
+    let sqrt_mu = mu.sqrt();
+    let k = r0 * vr0 / sqrt_mu;
+    let l = 1.0 - alpha * r0;
+    let mut chi = if (alpha * r0).abs() < PARABOLIC_GUESS_LIMIT {
+        // the other guesses degenerate near a parabola, where the equation is close to
+        // Barker's `χ³ + 3kχ² + 6r0χ = 6√μ dt`; that's a monotonic cubic, so Cardano's
+        // formula (with `χ = y - k`) gives its one real root
+        let p = 6.0 * r0 - 3.0 * k * k;
+        let q = 2.0 * k.powi(3) - 6.0 * r0 * k - 6.0 * sqrt_mu * dt;
+        let d = (q * q / 4.0 + p.powi(3) / 27.0).max(0.0).sqrt();
+        let u = (-q / 2.0 - q.signum() * d).cbrt();
+        let y = if u == 0.0 { 0.0 } else { u - p / (3.0 * u) };
+        y - k
+    } else if alpha > 0.0 {
+        sqrt_mu * alpha * dt
+    } else {
+        // the elliptic guess overflows `cosh` when we're far out on a hyperbola
+        let a = 1.0 / alpha;
+        let sign = dt.signum();
+        let ratio = -2.0 * mu * alpha * dt /
+                    (r0 * vr0 + sign * (-mu * a).sqrt() * (1.0 - r0 * alpha));
+        let guess = sign * (-a).sqrt() * ratio.ln();
+        if guess.is_finite() { guess } else { 0.0 }
+    };
+    for _ in 0..UNIVERSAL_MAX_ITERATIONS {
+        let chi2 = chi * chi;
+        let z = alpha * chi2;
+        let (c, s) = (stumpff_c(z), stumpff_s(z));
+        let f = k * chi2 * c + l * chi2 * chi * s + r0 * chi - sqrt_mu * dt;
+        let df = k * chi * (1.0 - z * s) + l * chi2 * c + r0;
+        let ddf = k * (1.0 - z * c) + l * chi * (1.0 - z * s);
+        let disc = ((LAGUERRE_N - 1.0).powi(2) * df * df -
+                    LAGUERRE_N * (LAGUERRE_N - 1.0) * f * ddf)
+            .abs()
+            .sqrt();
+        let delta = LAGUERRE_N * f / (df + df.signum() * disc);
+        chi -= delta;
+        if !chi.is_finite() {
+            return Err(KeplerError::NoConvergence(UNIVERSAL_MAX_ITERATIONS));
+        }
+        if delta.abs() <= 1e-12 * chi.abs().max(1.0) {
+            return Ok(chi);
+        }
+    }
+    Err(KeplerError::NoConvergence(UNIVERSAL_MAX_ITERATIONS))
+}
+
+/// Below this, the closed forms of the Stumpff functions lose too many digits to cancellation
+/// for `universal_kepler` to converge, so they're summed as series instead.
+const STUMPFF_SERIES_LIMIT: f64 = 1e-2;
+/// Computes the Stumpff function `C(z)`.
+pub fn stumpff_c(z: f64) -> f64 {
+    if z > STUMPFF_SERIES_LIMIT {
+        (1.0 - z.sqrt().cos()) / z
+    } else if z < -STUMPFF_SERIES_LIMIT {
+        ((-z).sqrt().cosh() - 1.0) / -z
+    } else {
+        1.0 / 2.0 - z * (1.0 / 24.0 - z * (1.0 / 720.0 - z * (1.0 / 40320.0 - z / 3628800.0)))
+    }
+}
+
+/// Computes the Stumpff function `S(z)`.
+pub fn stumpff_s(z: f64) -> f64 {
+    if z > STUMPFF_SERIES_LIMIT {
+        let sz = z.sqrt();
+        (sz - sz.sin()) / sz.powi(3)
+    } else if z < -STUMPFF_SERIES_LIMIT {
+        let sz = (-z).sqrt();
+        (sz.sinh() - sz) / sz.powi(3)
+    } else {
+        1.0 / 6.0 - z * (1.0 / 120.0 - z * (1.0 / 5040.0 - z * (1.0 / 362880.0 - z / 39916800.0)))
+    }
+}
+
+/// A sequence of impulsive burns that takes a spacecraft from one trajectory to another.
+#[derive(Clone,Debug)]
+pub struct Transfer {
+    /// The trajectories flown between consecutive burns.
+    /// Each one's mean anomalies cover just the part that gets flown.
+    pub legs: Vec<Trajectory>,
+    /// The delta-v of each burn (km/s), in order.
+    pub burns: Vec<f64>,
+    pub time: f64, // s
+}
+
+impl Transfer {
+    /// Sums the delta-v of every burn (km/s).
+    pub fn total_delta_v(&self) -> f64 {
+        self.burns.iter().sum()
+    }
+}
+
+/// Computes the Hohmann transfer from `from` to `to`
+/// given a standard gravitational parameter `mu`.
+///
+/// The transfer starts on `from` and ends on `to` on the opposite side of the central body.
+/// If the trajectories aren't coplanar, this happens along the line of nodes
+/// and the plane change is done with the second burn.
+/// Otherwise, this happens along `from`'s line of apsides.
+/// Returns `None` if neither trajectory can get to the places needed.
+pub fn hohmann(mu: f64, from: &Trajectory, to: &Trajectory) -> Option<Transfer> {
+    transfer_directions(from, to)
+        .into_iter()
+        .filter_map(|dir| {
+            let (pos1, vel1) = state_towards(mu, from, &dir)?;
+            let (pos2, vel2) = state_towards(mu, to, &-dir)?;
+            let leg = half_ellipse(from, &dir, pos1.norm(), pos2.norm());
+            let (_, leg_vel1) = state_towards(mu, &leg, &dir)?;
+            let (_, leg_vel2) = state_towards(mu, &leg, &-dir)?;
+            Some(Transfer {
+                legs: vec![leg],
+                burns: vec![(leg_vel1 - vel1).norm(), (vel2 - leg_vel2).norm()],
+                time: leg.t.period(mu)? / 2.0,
+            })
+        })
+        .min_by(|a, b| a.total_delta_v().partial_cmp(&b.total_delta_v()).unwrap())
+}
+
+/// Computes the bi-elliptic transfer from `from` to `to` via the intermediate apoapsis
+/// `apoapsis` (km), given a standard gravitational parameter `mu`.
+///
+/// The transfer starts and ends on the same side of the central body
+/// and any plane change is done with the second burn, at the intermediate apoapsis.
+/// See `hohmann` for where this happens.
+pub fn bi_elliptic(mu: f64,
+                   from: &Trajectory,
+                   to: &Trajectory,
+                   apoapsis: f64)
+                   -> Option<Transfer> {
+    transfer_directions(from, to)
+        .into_iter()
+        .filter_map(|dir| {
+            let (pos1, vel1) = state_towards(mu, from, &dir)?;
+            let (pos2, vel2) = state_towards(mu, to, &dir)?;
+            let leg1 = half_ellipse(from, &dir, pos1.norm(), apoapsis);
+            let leg2 = half_ellipse(to, &-dir, apoapsis, pos2.norm());
+            let (_, leg1_vel1) = state_towards(mu, &leg1, &dir)?;
+            let (_, leg1_vel2) = state_towards(mu, &leg1, &-dir)?;
+            let (_, leg2_vel1) = state_towards(mu, &leg2, &-dir)?;
+            let (_, leg2_vel2) = state_towards(mu, &leg2, &dir)?;
+            Some(Transfer {
+                legs: vec![leg1, leg2],
+                burns: vec![(leg1_vel1 - vel1).norm(),
+                            (leg2_vel1 - leg1_vel2).norm(),
+                            (vel2 - leg2_vel2).norm()],
+                time: (leg1.t.period(mu)? + leg2.t.period(mu)?) / 2.0,
+            })
+        })
+        .min_by(|a, b| a.total_delta_v().partial_cmp(&b.total_delta_v()).unwrap())
+}
+
+/// Computes the candidate directions for the first burn of a transfer.
+fn transfer_directions(from: &Trajectory, to: &Trajectory) -> Vec<Vector3<f64>> {
+    let node_line = from.normal().cross(&to.normal());
+    let dir = if node_line.norm() > DEGENERATE_TOLERANCE {
+        node_line.normalize()
+    } else {
+        from.p.to_matrix() * Vector3::x()
+    };
+    vec![dir, -dir]
+}
+
+/// Computes the state of `traj` in the direction of `dir` (if it gets there).
+fn state_towards(mu: f64,
+                 traj: &Trajectory,
+                 dir: &Vector3<f64>)
+                 -> Option<(Vector3<f64>, Vector3<f64>)> {
+    let true_anom = traj.true_anom_towards(dir);
+    if traj.t.reaches(true_anom) {
+        Some(traj.state_at_true_anom(mu, true_anom))
+    } else {
+        None
+    }
+}
+
+/// Computes the half of an ellipse in the plane of `plane_of` that goes from distance `r_dep`
+/// (km) in the direction of `dir` to distance `r_arr` (km) in the opposite direction.
+fn half_ellipse(plane_of: &Trajectory, dir: &Vector3<f64>, r_dep: f64, r_arr: f64) -> Trajectory {
+    let arg_lat = plane_of.p.arg_peri + plane_of.true_anom_towards(dir);
+    let (arg_peri, mean_anom0) = if r_dep <= r_arr {
+        (arg_lat, 0.0)
+    } else {
+        (arg_lat + TAU / 2.0, TAU / 2.0)
+    };
+    Trajectory {
+        p: Plane { arg_peri: arg_peri, ..plane_of.p },
+        t: PlanarTrajectory {
+            periapsis: r_dep.min(r_arr),
+            eccentr: (r_arr - r_dep).abs() / (r_arr + r_dep),
+            mean_anom0: mean_anom0,
+            mean_anom1: mean_anom0 + TAU / 2.0,
+        },
+    }
+}
+
+/// A solution to Lambert's problem.
+#[derive(Clone,Copy,Debug)]
+pub struct LambertSolution {
+    pub vel1: Vector3<f64>, // km/s (at departure)
+    pub vel2: Vector3<f64>, // km/s (at arrival)
+    /// The trajectory flown, with mean anomalies covering just the transfer.
+    pub trajectory: Trajectory,
+}
+
+/// Why `lambert` couldn't find a solution.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum LambertError {
+    /// One of the arguments was NaN or infinite, the time of flight wasn't positive,
+    /// or the positions are collinear with the central body (which leaves the plane undefined).
+    InvalidArgument,
+    /// There's no transfer with that many revolutions that takes that long.
+    NoSolution,
+}
+
+impl fmt::Display for LambertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LambertError::InvalidArgument => write!(f, "invalid argument"),
+            LambertError::NoSolution => write!(f, "no solution"),
+        }
+    }
+}
+
+impl Error for LambertError {}
+
+const LAMBERT_MAX_ITERATIONS: u32 = 200;
+/// Solves Lambert's problem: finds the trajectories that go from `pos1` to `pos2` (km)
+/// in `tof` seconds given a standard gravitational parameter `mu`.
+///
+/// `prograde` picks which way around the transfer goes (counterclockwise when seen from +z),
+/// and `revs` is the number of complete revolutions made on the way.
+/// There's only ever one solution with no revolutions,
+/// but there are two (or none) for each higher number of revolutions.
+///
+/// This uses the universal variable formulation (Bate, Mueller & White),
+/// bisecting on `z = χ²/a` within the range where it takes `revs` revolutions.
+pub fn lambert(mu: f64,
+               pos1: Vector3<f64>,
+               pos2: Vector3<f64>,
+               tof: f64,
+               prograde: bool,
+               revs: u32)
+               -> Result<Vec<LambertSolution>, LambertError> {
+    let args_finite = [mu, tof].iter().chain(pos1.iter()).chain(pos2.iter()).all(|x| x.is_finite());
+    if !args_finite || tof <= 0.0 {
+        return Err(LambertError::InvalidArgument);
+    }
+
+    let r1 = pos1.norm();
+    let r2 = pos2.norm();
+    let cross = pos1.cross(&pos2);
+    if cross.norm() <= DEGENERATE_TOLERANCE * r1 * r2 {
+        return Err(LambertError::InvalidArgument);
+    }
+    let cos_dnu = (pos1.dot(&pos2) / (r1 * r2)).clamp(-1.0, 1.0);
+    let dnu = if (cross.z >= 0.0) == prograde {
+        cos_dnu.acos()
+    } else {
+        TAU - cos_dnu.acos()
+    };
+    let a = dnu.sin() * (r1 * r2 / (1.0 - cos_dnu)).sqrt();
+
+    let y = |z: f64| r1 + r2 + a * (z * stumpff_s(z) - 1.0) / stumpff_c(z).sqrt();
+    // the time of flight, or `None` where there's no trajectory for this `z`
+    let time = |z: f64| {
+        let y = y(z);
+        if y < 0.0 {
+            None
+        } else {
+            let chi = (y / stumpff_c(z)).sqrt();
+            Some((chi.powi(3) * stumpff_s(z) + a * y.sqrt()) / mu.sqrt())
+        }
+    };
+    let too_short = |z: f64| time(z).is_none_or(|t| t < tof);
+
+    let z_min = (TAU * revs as f64).powi(2);
+    let z_max = (TAU * (revs + 1) as f64).powi(2);
+    let zs = if revs == 0 {
+        // the time of flight increases with `z`, and goes to zero as `z` goes to -∞
+        let mut lo = -z_max;
+        while !too_short(lo) {
+            lo *= 2.0;
+            if !lo.is_finite() || !time(lo).is_none_or(|t| t.is_finite()) {
+                return Err(LambertError::NoSolution);
+            }
+        }
+        vec![bisect(lo, z_max, &too_short)]
+    } else {
+        // the time of flight goes to infinity at both ends, so find the minimum in between
+        let time = |z: f64| time(z).unwrap_or(f64::INFINITY);
+        let z_best = golden_section_min(z_min, z_max, &time);
+        if time(z_best) > tof {
+            return Err(LambertError::NoSolution);
+        }
+        vec![bisect(z_min, z_best, &|z| time(z) > tof), bisect(z_best, z_max, &|z| time(z) < tof)]
+    };
+
+    Ok(zs.into_iter()
+        .map(|z| {
+            let y = y(z);
+            let f = 1.0 - y / r1;
+            let g = a * (y / mu).sqrt();
+            let g_dot = 1.0 - y / r2;
+            let vel1 = (pos2 - f * pos1) / g;
+            let vel2 = (g_dot * pos2 - pos1) / g;
+            let (mut trajectory, mean_anom) = Trajectory::from_state(mu, pos1, vel1);
+            trajectory.t.mean_anom0 = mean_anom;
+            trajectory.t.mean_anom1 = mean_anom + trajectory.t.mean_motion(mu) * tof;
+            LambertSolution {
+                vel1: vel1,
+                vel2: vel2,
+                trajectory: trajectory,
+            }
+        })
+        .collect())
+}
+
+/// Finds where `pred` switches from true to false between `lo` and `hi`.
+fn bisect<F>(mut lo: f64, mut hi: f64, pred: &F) -> f64
+    where F: Fn(f64) -> bool
+{
+    for _ in 0..LAMBERT_MAX_ITERATIONS {
+        let mid = (lo + hi) / 2.0;
+        if mid <= lo || mid >= hi {
+            break;
+        }
+        if pred(mid) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.0
+}
+
+/// Finds the minimum of a unimodal function `f` between `lo` and `hi`.
+fn golden_section_min<F>(mut lo: f64, mut hi: f64, f: &F) -> f64
+    where F: Fn(f64) -> f64
+{
+    let inv_phi = (5.0f64.sqrt() - 1.0) / 2.0;
+    let mut x1 = hi - inv_phi * (hi - lo);
+    let mut x2 = lo + inv_phi * (hi - lo);
+    let mut f1 = f(x1);
+    let mut f2 = f(x2);
+    for _ in 0..LAMBERT_MAX_ITERATIONS {
+        if x2 - x1 <= 1e-12 * hi {
+            break;
+        }
+        if f1 < f2 {
+            hi = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = hi - inv_phi * (hi - lo);
+            f1 = f(x1);
+        } else {
+            lo = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = lo + inv_phi * (hi - lo);
+            f2 = f(x2);
+        }
+    }
+    (x1 + x2) / 2.0
+}
+
+/// An impulsive burn at some point along a trajectory.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Serialize,Deserialize)]
+pub struct Maneuver {
+    /// Where along the trajectory the burn happens.
+    #[serde(rename = "mean_anom_rad")]
+    pub mean_anom: f64,
+    /// The components of the burn's delta-v (km/s).
+    #[serde(rename = "prograde_km_s")]
+    pub prograde: f64,
+    #[serde(rename = "normal_km_s")]
+    pub normal: f64,
+    #[serde(rename = "radial_km_s")]
+    pub radial: f64, // outward
+}
+
+impl Maneuver {
+    /// Computes the magnitude of the burn (km/s).
+    pub fn delta_v(&self) -> f64 {
+        Vector3::new(self.prograde, self.normal, self.radial).norm()
+    }
+
+    /// Computes the position of this maneuver on `traj` and the directions
+    /// of prograde, normal, and radial there, given a standard gravitational parameter `mu`.
+    pub fn frame(&self, mu: f64, traj: &Trajectory) -> (Vector3<f64>, [Vector3<f64>; 3]) {
+        let (pos, vel) = traj.to_state(mu, self.mean_anom);
+        let prograde = vel.normalize();
+        let normal = pos.cross(&vel).normalize();
+        let radial = prograde.cross(&normal);
+        (pos, [prograde, normal, radial])
+    }
+
+    /// Computes the trajectory after doing this maneuver on `traj`,
+    /// given a standard gravitational parameter `mu`.
+    /// Its mean anomalies start at the maneuver and cover one revolution.
+    pub fn apply(&self, mu: f64, traj: &Trajectory) -> Trajectory {
+        let (pos, [prograde, normal, radial]) = self.frame(mu, traj);
+        let (_, vel) = traj.to_state(mu, self.mean_anom);
+        let dv = self.prograde * prograde + self.normal * normal + self.radial * radial;
+        let (mut after, mean_anom) = Trajectory::from_state(mu, pos, vel + dv);
+        after.t.mean_anom0 = mean_anom;
+        after.t.mean_anom1 = mean_anom + TAU;
+        after
+    }
+}
@@ -0,0 +1,1039 @@
+//! SGP4 and SDP4, the propagators two-line element sets are made for.
+//!
+//! This follows Vallado, Crawford, Hujsak & Kelso, "Revisiting Spacetrack Report #3" (2006),
+//! with the WGS-72 constants TLEs are fitted with.
+//! Positions and velocities are in the TEME frame, which is the frame the TLE's elements
+//! (and so `Tle::trajectory`) are relative to.
+
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
+use na::Vector3;
+use tau::TAU;
+
+use tle::Tle;
+
+// WGS-72
+const MU: f64 = 398600.8; // km³/s²
+const RADIUS: f64 = 6378.135; // km
+const J2: f64 = 0.001082616;
+const J3: f64 = -0.00000253881;
+const J4: f64 = -0.00000165597;
+const J3OJ2: f64 = J3 / J2;
+const X2O3: f64 = 2.0 / 3.0;
+
+/// The mean motion (rad/min) of an orbit one Earth radius from the center.
+fn xke() -> f64 {
+    60.0 / (RADIUS.powi(3) / MU).sqrt()
+}
+
+/// Why SGP4 couldn't propagate a satellite.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Sgp4Error {
+    /// The mean eccentricity left `[0, 1)`.
+    Eccentricity,
+    /// The mean motion stopped being positive.
+    MeanMotion,
+    /// The eccentricity with lunar and solar perturbations left `[0, 1]`.
+    PerturbedEccentricity,
+    /// The semi-latus rectum went negative.
+    SemiLatusRectum,
+    /// The satellite is below the surface.
+    Decayed,
+}
+
+impl fmt::Display for Sgp4Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Sgp4Error::Eccentricity => write!(f, "mean eccentricity is out of range"),
+            Sgp4Error::MeanMotion => write!(f, "mean motion is not positive"),
+            Sgp4Error::PerturbedEccentricity => write!(f, "perturbed eccentricity is out of range"),
+            Sgp4Error::SemiLatusRectum => write!(f, "semi-latus rectum is negative"),
+            Sgp4Error::Decayed => write!(f, "satellite has decayed"),
+        }
+    }
+}
+
+impl Error for Sgp4Error {}
+
+/// A satellite set up for propagating with SGP4 (or SDP4 for periods of 225 minutes or more).
+///
+/// Internally, distances are in Earth radii and times in minutes.
+#[derive(Clone,Debug)]
+pub struct Sgp4 {
+    // the elements at epoch
+    ecco: f64,
+    inclo: f64,
+    nodeo: f64,
+    argpo: f64,
+    mo: f64,
+    no: f64, // rad/min (un-Kozai'd)
+    bstar: f64,
+    // near Earth
+    isimp: bool,
+    aycof: f64,
+    con41: f64,
+    cc1: f64,
+    cc4: f64,
+    cc5: f64,
+    d2: f64,
+    d3: f64,
+    d4: f64,
+    delmo: f64,
+    eta: f64,
+    argpdot: f64,
+    omgcof: f64,
+    sinmao: f64,
+    t2cof: f64,
+    t3cof: f64,
+    t4cof: f64,
+    t5cof: f64,
+    x1mth2: f64,
+    x7thm1: f64,
+    mdot: f64,
+    nodedot: f64,
+    xlcof: f64,
+    xmcof: f64,
+    nodecf: f64,
+    deep_space: Option<DeepSpace>,
+}
+
+/// The lunar and solar terms for SDP4.
+#[derive(Clone,Debug,Default)]
+struct DeepSpace {
+    gsto: f64, // rad (Greenwich sidereal time at epoch)
+    // periodics
+    e3: f64,
+    ee2: f64,
+    se2: f64,
+    se3: f64,
+    sgh2: f64,
+    sgh3: f64,
+    sgh4: f64,
+    sh2: f64,
+    sh3: f64,
+    si2: f64,
+    si3: f64,
+    sl2: f64,
+    sl3: f64,
+    sl4: f64,
+    xgh2: f64,
+    xgh3: f64,
+    xgh4: f64,
+    xh2: f64,
+    xh3: f64,
+    xi2: f64,
+    xi3: f64,
+    xl2: f64,
+    xl3: f64,
+    xl4: f64,
+    zmol: f64,
+    zmos: f64,
+    // secular rates
+    dedt: f64,
+    didt: f64,
+    dmdt: f64,
+    dnodt: f64,
+    domdt: f64,
+    // resonance
+    irez: u8, // 0 for none, 1 for one day, 2 for half a day
+    d2201: f64,
+    d2211: f64,
+    d3210: f64,
+    d3222: f64,
+    d4410: f64,
+    d4422: f64,
+    d5220: f64,
+    d5232: f64,
+    d5421: f64,
+    d5433: f64,
+    del1: f64,
+    del2: f64,
+    del3: f64,
+    xfact: f64,
+    xlamo: f64,
+}
+
+/// The lunar and solar quantities `dscom` computes that `dsinit` needs but SDP4 doesn't keep.
+#[derive(Clone,Copy,Debug,Default)]
+struct DsCom {
+    sinim: f64,
+    cosim: f64,
+    emsq: f64,
+    s1: f64,
+    s2: f64,
+    s3: f64,
+    s4: f64,
+    s5: f64,
+    ss1: f64,
+    ss2: f64,
+    ss3: f64,
+    ss4: f64,
+    ss5: f64,
+    sz1: f64,
+    sz3: f64,
+    sz11: f64,
+    sz13: f64,
+    sz21: f64,
+    sz23: f64,
+    sz31: f64,
+    sz33: f64,
+    z1: f64,
+    z3: f64,
+    z11: f64,
+    z13: f64,
+    z21: f64,
+    z23: f64,
+    z31: f64,
+    z33: f64,
+}
+
+impl Sgp4 {
+    /// Sets up SGP4 for the satellite in `tle`.
+    pub fn new(tle: &Tle) -> Result<Sgp4, Sgp4Error> {
+        let xke = xke();
+        let ecco = tle.eccentr;
+        let inclo = tle.inclination;
+        let argpo = tle.arg_peri;
+        let mo = tle.mean_anom;
+        let bstar = tle.bstar;
+        // days since 1950 January 0.0
+        let epoch = 18263.5 + tle.epoch / 86400.0;
+
+        // initl
+        let eccsq = ecco * ecco;
+        let omeosq = 1.0 - eccsq;
+        let rteosq = omeosq.sqrt();
+        let cosio = inclo.cos();
+        let cosio2 = cosio * cosio;
+
+        // un-Kozai the mean motion
+        let no_kozai = tle.mean_motion * 60.0;
+        let ak = (xke / no_kozai).powf(X2O3);
+        let d1 = 0.75 * J2 * (3.0 * cosio2 - 1.0) / (rteosq * omeosq);
+        let del = d1 / (ak * ak);
+        let adel = ak * (1.0 - del * del - del * (1.0 / 3.0 + 134.0 * del * del / 81.0));
+        let del = d1 / (adel * adel);
+        let no = no_kozai / (1.0 + del);
+
+        let ao = (xke / no).powf(X2O3);
+        let sinio = inclo.sin();
+        let po = ao * omeosq;
+        let con42 = 1.0 - 5.0 * cosio2;
+        let con41 = -con42 - cosio2 - cosio2;
+        let posq = po * po;
+        let rp = ao * (1.0 - ecco);
+        let gsto = gstime(epoch + 2433281.5);
+
+        let mut isimp = rp < 220.0 / RADIUS + 1.0;
+        let ss = 78.0 / RADIUS + 1.0;
+        let qzms2t = ((120.0 - 78.0) / RADIUS).powi(4);
+        let mut sfour = ss;
+        let mut qzms24 = qzms2t;
+        let perige = (rp - 1.0) * RADIUS;
+        // the atmosphere model changes for low perigees
+        if perige < 156.0 {
+            sfour = if perige < 98.0 { 20.0 } else { perige - 78.0 };
+            qzms24 = ((120.0 - sfour) / RADIUS).powi(4);
+            sfour = sfour / RADIUS + 1.0;
+        }
+        let pinvsq = 1.0 / posq;
+        let tsi = 1.0 / (ao - sfour);
+        let eta = ao * ecco * tsi;
+        let etasq = eta * eta;
+        let eeta = ecco * eta;
+        let psisq = (1.0 - etasq).abs();
+        let coef = qzms24 * tsi.powi(4);
+        let coef1 = coef / psisq.powf(3.5);
+        let cc2 = coef1 * no *
+                  (ao * (1.0 + 1.5 * etasq + eeta * (4.0 + etasq)) +
+                   0.375 * J2 * tsi / psisq * con41 * (8.0 + 3.0 * etasq * (8.0 + etasq)));
+        let cc1 = bstar * cc2;
+        let cc3 = if ecco > 1.0e-4 {
+            -2.0 * coef * tsi * J3OJ2 * no * sinio / ecco
+        } else {
+            0.0
+        };
+        let x1mth2 = 1.0 - cosio2;
+        let cc4 = 2.0 * no * coef1 * ao * omeosq *
+                  (eta * (2.0 + 0.5 * etasq) + ecco * (0.5 + 2.0 * etasq) -
+                   J2 * tsi / (ao * psisq) *
+                   (-3.0 * con41 * (1.0 - 2.0 * eeta + etasq * (1.5 - 0.5 * eeta)) +
+                    0.75 * x1mth2 * (2.0 * etasq - eeta * (1.0 + etasq)) * (2.0 * argpo).cos()));
+        let cc5 = 2.0 * coef1 * ao * omeosq * (1.0 + 2.75 * (etasq + eeta) + eeta * etasq);
+        let cosio4 = cosio2 * cosio2;
+        let temp1 = 1.5 * J2 * pinvsq * no;
+        let temp2 = 0.5 * temp1 * J2 * pinvsq;
+        let temp3 = -0.46875 * J4 * pinvsq * pinvsq * no;
+        let mdot = no + 0.5 * temp1 * rteosq * con41 +
+                   0.0625 * temp2 * rteosq * (13.0 - 78.0 * cosio2 + 137.0 * cosio4);
+        let argpdot = -0.5 * temp1 * con42 +
+                      0.0625 * temp2 * (7.0 - 114.0 * cosio2 + 395.0 * cosio4) +
+                      temp3 * (3.0 - 36.0 * cosio2 + 49.0 * cosio4);
+        let xhdot1 = -temp1 * cosio;
+        let nodedot = xhdot1 +
+                      (0.5 * temp2 * (4.0 - 19.0 * cosio2) + 2.0 * temp3 * (3.0 - 7.0 * cosio2)) *
+                      cosio;
+        let xpidot = argpdot + nodedot;
+        let xmcof = if ecco > 1.0e-4 {
+            -X2O3 * coef * bstar / eeta
+        } else {
+            0.0
+        };
+
+        let mut sat = Sgp4 {
+            ecco: ecco,
+            inclo: inclo,
+            nodeo: tle.lon_asc_node,
+            argpo: argpo,
+            mo: mo,
+            no: no,
+            bstar: bstar,
+            isimp: isimp,
+            aycof: -0.5 * J3OJ2 * sinio,
+            con41: con41,
+            cc1: cc1,
+            cc4: cc4,
+            cc5: cc5,
+            d2: 0.0,
+            d3: 0.0,
+            d4: 0.0,
+            delmo: (1.0 + eta * mo.cos()).powi(3),
+            eta: eta,
+            argpdot: argpdot,
+            omgcof: bstar * cc3 * argpo.cos(),
+            sinmao: mo.sin(),
+            t2cof: 1.5 * cc1,
+            t3cof: 0.0,
+            t4cof: 0.0,
+            t5cof: 0.0,
+            x1mth2: x1mth2,
+            x7thm1: 7.0 * cosio2 - 1.0,
+            mdot: mdot,
+            nodedot: nodedot,
+            xlcof: xlcof(sinio, cosio),
+            xmcof: xmcof,
+            nodecf: 3.5 * omeosq * xhdot1 * cc1,
+            deep_space: None,
+        };
+
+        if TAU / no >= 225.0 {
+            isimp = true;
+            let (mut ds, com) = dscom(epoch, ecco, argpo, inclo, sat.nodeo, no);
+            ds.gsto = gsto;
+            dsinit(&mut ds, &com, &sat, eccsq, xpidot);
+            sat.deep_space = Some(ds);
+        }
+        sat.isimp = isimp;
+
+        if !isimp {
+            let cc1sq = cc1 * cc1;
+            let d2 = 4.0 * ao * tsi * cc1sq;
+            let temp = d2 * tsi * cc1 / 3.0;
+            let d3 = (17.0 * ao + sfour) * temp;
+            let d4 = 0.5 * temp * ao * tsi * (221.0 * ao + 31.0 * sfour) * cc1;
+            sat.d2 = d2;
+            sat.d3 = d3;
+            sat.d4 = d4;
+            sat.t3cof = d2 + 2.0 * cc1sq;
+            sat.t4cof = 0.25 * (3.0 * d3 + cc1 * (12.0 * d2 + 10.0 * cc1sq));
+            sat.t5cof = 0.2 *
+                        (3.0 * d4 + 12.0 * cc1 * d3 + 6.0 * d2 * d2 +
+                         15.0 * cc1sq * (2.0 * d2 + cc1sq));
+        }
+
+        sat.propagate(0.0)?;
+        Ok(sat)
+    }
+
+    /// Whether this uses SDP4 (for periods of 225 minutes or more) instead of SGP4.
+    pub fn is_deep_space(&self) -> bool {
+        self.deep_space.is_some()
+    }
+
+    /// Computes the position (km) and velocity (km/s) `time` seconds after the TLE's epoch.
+    pub fn propagate(&self, time: f64) -> Result<(Vector3<f64>, Vector3<f64>), Sgp4Error> {
+        let xke = xke();
+        let t = time / 60.0;
+
+        // secular gravity and atmospheric drag
+        let xmdf = self.mo + self.mdot * t;
+        let argpdf = self.argpo + self.argpdot * t;
+        let nodedf = self.nodeo + self.nodedot * t;
+        let mut argpm = argpdf;
+        let mut mm = xmdf;
+        let t2 = t * t;
+        let mut nodem = nodedf + self.nodecf * t2;
+        let mut tempa = 1.0 - self.cc1 * t;
+        let mut tempe = self.bstar * self.cc4 * t;
+        let mut templ = self.t2cof * t2;
+
+        if !self.isimp {
+            let delomg = self.omgcof * t;
+            let delm = self.xmcof * ((1.0 + self.eta * xmdf.cos()).powi(3) - self.delmo);
+            let temp = delomg + delm;
+            mm = xmdf + temp;
+            argpm = argpdf - temp;
+            let t3 = t2 * t;
+            let t4 = t3 * t;
+            tempa = tempa - self.d2 * t2 - self.d3 * t3 - self.d4 * t4;
+            tempe += self.bstar * self.cc5 * (mm.sin() - self.sinmao);
+            templ += self.t3cof * t3 + t4 * (self.t4cof + t * self.t5cof);
+        }
+
+        let mut nm = self.no;
+        let mut em = self.ecco;
+        let mut inclm = self.inclo;
+        if let Some(ref ds) = self.deep_space {
+            let secular = dspace(ds, self, t, em, argpm, inclm, mm, nodem);
+            em = secular.0;
+            argpm = secular.1;
+            inclm = secular.2;
+            mm = secular.3;
+            nodem = secular.4;
+            nm = secular.5;
+        }
+
+        if nm <= 0.0 {
+            return Err(Sgp4Error::MeanMotion);
+        }
+        let am = (xke / nm).powf(X2O3) * tempa * tempa;
+        nm = xke / am.powf(1.5);
+        em -= tempe;
+        if !(-0.001..1.0).contains(&em) {
+            return Err(Sgp4Error::Eccentricity);
+        }
+        // avoid dividing by zero
+        if em < 1.0e-6 {
+            em = 1.0e-6;
+        }
+        mm += self.no * templ;
+        let xlm = mm + argpm + nodem;
+
+        nodem %= TAU;
+        argpm %= TAU;
+        let xlm = xlm % TAU;
+        mm = (xlm - argpm - nodem) % TAU;
+
+        // lunar and solar periodics
+        let mut ep = em;
+        let mut xincp = inclm;
+        let mut argpp = argpm;
+        let mut nodep = nodem;
+        let mut mp = mm;
+        let mut aycof = self.aycof;
+        let mut xlcof = self.xlcof;
+        if let Some(ref ds) = self.deep_space {
+            dpper(ds, t, &mut ep, &mut xincp, &mut nodep, &mut argpp, &mut mp);
+            if xincp < 0.0 {
+                xincp = -xincp;
+                nodep += PI;
+                argpp -= PI;
+            }
+            if !(0.0..=1.0).contains(&ep) {
+                return Err(Sgp4Error::PerturbedEccentricity);
+            }
+            let (sinip, cosip) = xincp.sin_cos();
+            aycof = -0.5 * J3OJ2 * sinip;
+            xlcof = ::sgp4::xlcof(sinip, cosip);
+        }
+        let (sinip, cosip) = xincp.sin_cos();
+
+        // long period periodics
+        let axnl = ep * argpp.cos();
+        let temp = 1.0 / (am * (1.0 - ep * ep));
+        let aynl = ep * argpp.sin() + temp * aycof;
+        let xl = mp + argpp + nodep + temp * xlcof * axnl;
+
+        // Kepler's equation
+        let u = (xl - nodep) % TAU;
+        let mut eo1 = u;
+        let mut tem5: f64 = 9999.9;
+        let mut ktr = 1;
+        let (mut sineo1, mut coseo1) = (0.0, 0.0);
+        while tem5.abs() >= 1.0e-12 && ktr <= 10 {
+            sineo1 = eo1.sin();
+            coseo1 = eo1.cos();
+            tem5 = 1.0 - coseo1 * axnl - sineo1 * aynl;
+            tem5 = (u - aynl * coseo1 + axnl * sineo1 - eo1) / tem5;
+            if tem5.abs() >= 0.95 {
+                tem5 = 0.95f64.copysign(tem5);
+            }
+            eo1 += tem5;
+            ktr += 1;
+        }
+
+        // short period periodics
+        let ecose = axnl * coseo1 + aynl * sineo1;
+        let esine = axnl * sineo1 - aynl * coseo1;
+        let el2 = axnl * axnl + aynl * aynl;
+        let pl = am * (1.0 - el2);
+        if pl < 0.0 {
+            return Err(Sgp4Error::SemiLatusRectum);
+        }
+        let rl = am * (1.0 - ecose);
+        let rdotl = am.sqrt() * esine / rl;
+        let rvdotl = pl.sqrt() / rl;
+        let betal = (1.0 - el2).sqrt();
+        let temp = esine / (1.0 + betal);
+        let sinu = am / rl * (sineo1 - aynl - axnl * temp);
+        let cosu = am / rl * (coseo1 - axnl + aynl * temp);
+        let mut su = sinu.atan2(cosu);
+        let sin2u = (cosu + cosu) * sinu;
+        let cos2u = 1.0 - 2.0 * sinu * sinu;
+        let temp = 1.0 / pl;
+        let temp1 = 0.5 * J2 * temp;
+        let temp2 = temp1 * temp;
+
+        let (con41, x1mth2, x7thm1) = if self.deep_space.is_some() {
+            let cosisq = cosip * cosip;
+            (3.0 * cosisq - 1.0, 1.0 - cosisq, 7.0 * cosisq - 1.0)
+        } else {
+            (self.con41, self.x1mth2, self.x7thm1)
+        };
+        let mrt = rl * (1.0 - 1.5 * temp2 * betal * con41) + 0.5 * temp1 * x1mth2 * cos2u;
+        su -= 0.25 * temp2 * x7thm1 * sin2u;
+        let xnode = nodep + 1.5 * temp2 * cosip * sin2u;
+        let xinc = xincp + 1.5 * temp2 * cosip * sinip * cos2u;
+        let mvt = rdotl - nm * temp1 * x1mth2 * sin2u / xke;
+        let rvdot = rvdotl + nm * temp1 * (x1mth2 * cos2u + 1.5 * con41) / xke;
+
+        // orientation vectors
+        let (sinsu, cossu) = su.sin_cos();
+        let (snod, cnod) = xnode.sin_cos();
+        let (sini, cosi) = xinc.sin_cos();
+        let xmx = -snod * cosi;
+        let xmy = cnod * cosi;
+        let u = Vector3::new(xmx * sinsu + cnod * cossu, xmy * sinsu + snod * cossu, sini * sinsu);
+        let v = Vector3::new(xmx * cossu - cnod * sinsu, xmy * cossu - snod * sinsu, sini * cossu);
+
+        if mrt < 1.0 {
+            return Err(Sgp4Error::Decayed);
+        }
+        let vkmpersec = RADIUS * xke / 60.0;
+        Ok((mrt * u * RADIUS, (mvt * u + rvdot * v) * vkmpersec))
+    }
+}
+
+fn xlcof(sinio: f64, cosio: f64) -> f64 {
+    // avoid dividing by zero at 180° inclination
+    let denom = if (cosio + 1.0).abs() > 1.5e-12 {
+        1.0 + cosio
+    } else {
+        1.5e-12
+    };
+    -0.25 * J3OJ2 * sinio * (3.0 + 5.0 * cosio) / denom
+}
+
+/// Computes Greenwich mean sidereal time (rad), how far Earth has turned from the vernal equinox,
+/// at `epoch` (s since J2000, UTC).
+pub fn gmst(epoch: f64) -> f64 {
+    gstime(2451545.0 + epoch / 86400.0)
+}
+
+/// Computes Greenwich mean sidereal time (rad) at the Julian date `jdut1`.
+fn gstime(jdut1: f64) -> f64 {
+    let tut1 = (jdut1 - 2451545.0) / 36525.0;
+    let temp = -6.2e-6 * tut1.powi(3) + 0.093104 * tut1 * tut1 +
+               (876600.0 * 3600.0 + 8640184.812866) * tut1 + 67310.54841; // s
+    (temp.to_radians() / 240.0).rem_euclid(TAU)
+}
+
+// lunar and solar constants
+const ZES: f64 = 0.01675;
+const ZEL: f64 = 0.05490;
+const ZNS: f64 = 1.19459e-5;
+const ZNL: f64 = 1.5835218e-4;
+const RPTIM: f64 = 0.0043752690880113; // rad/min (Earth's rotation rate)
+
+/// Computes the lunar and solar terms at epoch.
+fn dscom(epoch: f64,
+         ep: f64,
+         argpp: f64,
+         inclp: f64,
+         nodep: f64,
+         np: f64)
+         -> (DeepSpace, DsCom) {
+    const C1SS: f64 = 2.9864797e-6;
+    const C1L: f64 = 4.7968065e-7;
+    const ZSINIS: f64 = 0.39785416;
+    const ZCOSIS: f64 = 0.91744867;
+    const ZCOSGS: f64 = 0.1945905;
+    const ZSINGS: f64 = -0.98088458;
+
+    let mut ds = DeepSpace::default();
+    let mut c = DsCom::default();
+
+    let nm = np;
+    let em = ep;
+    let (snodm, cnodm) = nodep.sin_cos();
+    let (sinomm, cosomm) = argpp.sin_cos();
+    let (sinim, cosim) = inclp.sin_cos();
+    let emsq = em * em;
+    let betasq = 1.0 - emsq;
+    let rtemsq = betasq.sqrt();
+    c.sinim = sinim;
+    c.cosim = cosim;
+    c.emsq = emsq;
+
+    let day = epoch + 18261.5;
+    let xnodce = (4.5236020 - 9.2422029e-4 * day) % TAU;
+    let (stem, ctem) = xnodce.sin_cos();
+    let zcosil = 0.91375164 - 0.03568096 * ctem;
+    let zsinil = (1.0 - zcosil * zcosil).sqrt();
+    let zsinhl = 0.089683511 * stem / zsinil;
+    let zcoshl = (1.0 - zsinhl * zsinhl).sqrt();
+    let gam = 5.8351514 + 0.0019443680 * day;
+    let zx = 0.39785416 * stem / zsinil;
+    let zy = zcoshl * ctem + 0.91744867 * zsinhl * stem;
+    let zx = gam + zx.atan2(zy) - xnodce;
+    let (zsingl, zcosgl) = zx.sin_cos();
+
+    // the sun first, then the moon
+    let mut zcosg = ZCOSGS;
+    let mut zsing = ZSINGS;
+    let mut zcosi = ZCOSIS;
+    let mut zsini = ZSINIS;
+    let mut zcosh = cnodm;
+    let mut zsinh = snodm;
+    let mut cc = C1SS;
+    let xnoi = 1.0 / nm;
+    let mut s = [0.0; 8];
+    let mut z = [[0.0; 4]; 4];
+    for lsflg in 1..3 {
+        let a1 = zcosg * zcosh + zsing * zcosi * zsinh;
+        let a3 = -zsing * zcosh + zcosg * zcosi * zsinh;
+        let a7 = -zcosg * zsinh + zsing * zcosi * zcosh;
+        let a8 = zsing * zsini;
+        let a9 = zsing * zsinh + zcosg * zcosi * zcosh;
+        let a10 = zcosg * zsini;
+        let a2 = cosim * a7 + sinim * a8;
+        let a4 = cosim * a9 + sinim * a10;
+        let a5 = -sinim * a7 + cosim * a8;
+        let a6 = -sinim * a9 + cosim * a10;
+
+        let x1 = a1 * cosomm + a2 * sinomm;
+        let x2 = a3 * cosomm + a4 * sinomm;
+        let x3 = -a1 * sinomm + a2 * cosomm;
+        let x4 = -a3 * sinomm + a4 * cosomm;
+        let x5 = a5 * sinomm;
+        let x6 = a6 * sinomm;
+        let x7 = a5 * cosomm;
+        let x8 = a6 * cosomm;
+
+        // z[i][j] is `zij` in the report (and z[i][0] is `zi`)
+        z[3][1] = 12.0 * x1 * x1 - 3.0 * x3 * x3;
+        z[3][2] = 24.0 * x1 * x2 - 6.0 * x3 * x4;
+        z[3][3] = 12.0 * x2 * x2 - 3.0 * x4 * x4;
+        z[1][0] = 3.0 * (a1 * a1 + a2 * a2) + z[3][1] * emsq;
+        z[2][0] = 6.0 * (a1 * a3 + a2 * a4) + z[3][2] * emsq;
+        z[3][0] = 3.0 * (a3 * a3 + a4 * a4) + z[3][3] * emsq;
+        z[1][1] = -6.0 * a1 * a5 + emsq * (-24.0 * x1 * x7 - 6.0 * x3 * x5);
+        z[1][2] = -6.0 * (a1 * a6 + a3 * a5) +
+                  emsq * (-24.0 * (x2 * x7 + x1 * x8) - 6.0 * (x3 * x6 + x4 * x5));
+        z[1][3] = -6.0 * a3 * a6 + emsq * (-24.0 * x2 * x8 - 6.0 * x4 * x6);
+        z[2][1] = 6.0 * a2 * a5 + emsq * (24.0 * x1 * x5 - 6.0 * x3 * x7);
+        z[2][2] = 6.0 * (a4 * a5 + a2 * a6) +
+                  emsq * (24.0 * (x2 * x5 + x1 * x6) - 6.0 * (x4 * x7 + x3 * x8));
+        z[2][3] = 6.0 * a4 * a6 + emsq * (24.0 * x2 * x6 - 6.0 * x4 * x8);
+        z[1][0] = z[1][0] + z[1][0] + betasq * z[3][1];
+        z[2][0] = z[2][0] + z[2][0] + betasq * z[3][2];
+        z[3][0] = z[3][0] + z[3][0] + betasq * z[3][3];
+        s[3] = cc * xnoi;
+        s[2] = -0.5 * s[3] / rtemsq;
+        s[4] = s[3] * rtemsq;
+        s[1] = -15.0 * em * s[4];
+        s[5] = x1 * x3 + x2 * x4;
+        s[6] = x2 * x3 + x1 * x4;
+        s[7] = x2 * x4 - x1 * x3;
+
+        if lsflg == 1 {
+            // keep the solar terms
+            let (ss, sz) = (s, z);
+            ds.se2 = 2.0 * ss[1] * ss[6];
+            ds.se3 = 2.0 * ss[1] * ss[7];
+            ds.si2 = 2.0 * ss[2] * sz[1][2];
+            ds.si3 = 2.0 * ss[2] * (sz[1][3] - sz[1][1]);
+            ds.sl2 = -2.0 * ss[3] * sz[2][0];
+            ds.sl3 = -2.0 * ss[3] * (sz[3][0] - sz[1][0]);
+            ds.sl4 = -2.0 * ss[3] * (-21.0 - 9.0 * emsq) * ZES;
+            ds.sgh2 = 2.0 * ss[4] * sz[3][2];
+            ds.sgh3 = 2.0 * ss[4] * (sz[3][3] - sz[3][1]);
+            ds.sgh4 = -18.0 * ss[4] * ZES;
+            ds.sh2 = -2.0 * ss[2] * sz[2][2];
+            ds.sh3 = -2.0 * ss[2] * (sz[2][3] - sz[2][1]);
+            c.ss1 = ss[1];
+            c.ss2 = ss[2];
+            c.ss3 = ss[3];
+            c.ss4 = ss[4];
+            c.ss5 = ss[5];
+            c.sz1 = sz[1][0];
+            c.sz3 = sz[3][0];
+            c.sz11 = sz[1][1];
+            c.sz13 = sz[1][3];
+            c.sz21 = sz[2][1];
+            c.sz23 = sz[2][3];
+            c.sz31 = sz[3][1];
+            c.sz33 = sz[3][3];
+
+            zcosg = zcosgl;
+            zsing = zsingl;
+            zcosi = zcosil;
+            zsini = zsinil;
+            zcosh = zcoshl * cnodm + zsinhl * snodm;
+            zsinh = snodm * zcoshl - cnodm * zsinhl;
+            cc = C1L;
+        }
+    }
+
+    ds.zmol = (4.7199672 + 0.22997150 * day - gam) % TAU;
+    ds.zmos = (6.2565837 + 0.017201977 * day) % TAU;
+
+    ds.ee2 = 2.0 * s[1] * s[6];
+    ds.e3 = 2.0 * s[1] * s[7];
+    ds.xi2 = 2.0 * s[2] * z[1][2];
+    ds.xi3 = 2.0 * s[2] * (z[1][3] - z[1][1]);
+    ds.xl2 = -2.0 * s[3] * z[2][0];
+    ds.xl3 = -2.0 * s[3] * (z[3][0] - z[1][0]);
+    ds.xl4 = -2.0 * s[3] * (-21.0 - 9.0 * emsq) * ZEL;
+    ds.xgh2 = 2.0 * s[4] * z[3][2];
+    ds.xgh3 = 2.0 * s[4] * (z[3][3] - z[3][1]);
+    ds.xgh4 = -18.0 * s[4] * ZEL;
+    ds.xh2 = -2.0 * s[2] * z[2][2];
+    ds.xh3 = -2.0 * s[2] * (z[2][3] - z[2][1]);
+    c.s1 = s[1];
+    c.s2 = s[2];
+    c.s3 = s[3];
+    c.s4 = s[4];
+    c.s5 = s[5];
+    c.z1 = z[1][0];
+    c.z3 = z[3][0];
+    c.z11 = z[1][1];
+    c.z13 = z[1][3];
+    c.z21 = z[2][1];
+    c.z23 = z[2][3];
+    c.z31 = z[3][1];
+    c.z33 = z[3][3];
+    (ds, c)
+}
+
+/// Computes the secular rates from the sun and moon, and sets up resonances.
+fn dsinit(ds: &mut DeepSpace, c: &DsCom, sat: &Sgp4, eccsq: f64, xpidot: f64) -> () {
+    const Q22: f64 = 1.7891679e-6;
+    const Q31: f64 = 2.1460748e-6;
+    const Q33: f64 = 2.2123015e-7;
+    const ROOT22: f64 = 1.7891679e-6;
+    const ROOT44: f64 = 7.3636953e-9;
+    const ROOT54: f64 = 2.1765803e-9;
+    const ROOT32: f64 = 3.7393792e-7;
+    const ROOT52: f64 = 1.1428639e-7;
+
+    let (sinim, cosim, emsq) = (c.sinim, c.cosim, c.emsq);
+    let nm = sat.no;
+    let em = sat.ecco;
+    let inclm = sat.inclo;
+
+    ds.irez = if nm < 0.0052359877 && nm > 0.0034906585 {
+        1
+    } else if (8.26e-3..=9.24e-3).contains(&nm) && em >= 0.5 {
+        2
+    } else {
+        0
+    };
+
+    // solar terms
+    let ses = c.ss1 * ZNS * c.ss5;
+    let sis = c.ss2 * ZNS * (c.sz11 + c.sz13);
+    let sls = -ZNS * c.ss3 * (c.sz1 + c.sz3 - 14.0 - 6.0 * emsq);
+    let sghs = c.ss4 * ZNS * (c.sz31 + c.sz33 - 6.0);
+    let mut shs = -ZNS * c.ss2 * (c.sz21 + c.sz23);
+    // the node isn't defined near 0° and 180° inclination
+    let near_equatorial = !(5.2359877e-2..=PI - 5.2359877e-2).contains(&inclm);
+    if near_equatorial {
+        shs = 0.0;
+    }
+    if sinim != 0.0 {
+        shs /= sinim;
+    }
+    let sgs = sghs - cosim * shs;
+
+    // lunar terms
+    ds.dedt = ses + c.s1 * ZNL * c.s5;
+    ds.didt = sis + c.s2 * ZNL * (c.z11 + c.z13);
+    ds.dmdt = sls - ZNL * c.s3 * (c.z1 + c.z3 - 14.0 - 6.0 * emsq);
+    let sghl = c.s4 * ZNL * (c.z31 + c.z33 - 6.0);
+    let mut shll = -ZNL * c.s2 * (c.z21 + c.z23);
+    if near_equatorial {
+        shll = 0.0;
+    }
+    ds.domdt = sgs + sghl;
+    ds.dnodt = shs;
+    if sinim != 0.0 {
+        ds.domdt -= cosim / sinim * shll;
+        ds.dnodt += shll / sinim;
+    }
+
+    let theta = ds.gsto % TAU;
+    if ds.irez == 0 {
+        return;
+    }
+    let aonv = (nm / xke()).powf(X2O3);
+
+    if ds.irez == 2 {
+        // geopotential resonance for 12 hour orbits
+        let cosisq = cosim * cosim;
+        let em = sat.ecco;
+        let emsq = eccsq;
+        let eoc = em * emsq;
+        let g201 = -0.306 - (em - 0.64) * 0.440;
+
+        let (g211, g310, g322, g410, g422, g520);
+        if em <= 0.65 {
+            g211 = 3.616 - 13.2470 * em + 16.2900 * emsq;
+            g310 = -19.302 + 117.3900 * em - 228.4190 * emsq + 156.5910 * eoc;
+            g322 = -18.9068 + 109.7927 * em - 214.6334 * emsq + 146.5816 * eoc;
+            g410 = -41.122 + 242.6940 * em - 471.0940 * emsq + 313.9530 * eoc;
+            g422 = -146.407 + 841.8800 * em - 1629.014 * emsq + 1083.4350 * eoc;
+            g520 = -532.114 + 3017.977 * em - 5740.032 * emsq + 3708.2760 * eoc;
+        } else {
+            g211 = -72.099 + 331.819 * em - 508.738 * emsq + 266.724 * eoc;
+            g310 = -346.844 + 1582.851 * em - 2415.925 * emsq + 1246.113 * eoc;
+            g322 = -342.585 + 1554.908 * em - 2366.899 * emsq + 1215.972 * eoc;
+            g410 = -1052.797 + 4758.686 * em - 7193.992 * emsq + 3651.957 * eoc;
+            g422 = -3581.690 + 16178.110 * em - 24462.770 * emsq + 12422.520 * eoc;
+            g520 = if em > 0.715 {
+                -5149.66 + 29936.92 * em - 54087.36 * emsq + 31324.56 * eoc
+            } else {
+                1464.74 - 4664.75 * em + 3763.64 * emsq
+            };
+        }
+        let (g533, g521, g532);
+        if em < 0.7 {
+            g533 = -919.22770 + 4988.6100 * em - 9064.7700 * emsq + 5542.21 * eoc;
+            g521 = -822.71072 + 4568.6173 * em - 8491.4146 * emsq + 5337.524 * eoc;
+            g532 = -853.66600 + 4690.2500 * em - 8624.7700 * emsq + 5341.4 * eoc;
+        } else {
+            g533 = -37995.780 + 161616.52 * em - 229838.20 * emsq + 109377.94 * eoc;
+            g521 = -51752.104 + 218913.95 * em - 309468.16 * emsq + 146349.42 * eoc;
+            g532 = -40023.880 + 170470.89 * em - 242699.48 * emsq + 115605.82 * eoc;
+        }
+
+        let sini2 = sinim * sinim;
+        let f220 = 0.75 * (1.0 + 2.0 * cosim + cosisq);
+        let f221 = 1.5 * sini2;
+        let f321 = 1.875 * sinim * (1.0 - 2.0 * cosim - 3.0 * cosisq);
+        let f322 = -1.875 * sinim * (1.0 + 2.0 * cosim - 3.0 * cosisq);
+        let f441 = 35.0 * sini2 * f220;
+        let f442 = 39.3750 * sini2 * sini2;
+        let f522 = 9.84375 * sinim *
+                   (sini2 * (1.0 - 2.0 * cosim - 5.0 * cosisq) +
+                    0.33333333 * (-2.0 + 4.0 * cosim + 6.0 * cosisq));
+        let f523 = sinim *
+                   (4.92187512 * sini2 * (-2.0 - 4.0 * cosim + 10.0 * cosisq) +
+                    6.56250012 * (1.0 + 2.0 * cosim - 3.0 * cosisq));
+        let f542 = 29.53125 * sinim *
+                   (2.0 - 8.0 * cosim + cosisq * (-12.0 + 8.0 * cosim + 10.0 * cosisq));
+        let f543 = 29.53125 * sinim *
+                   (-2.0 - 8.0 * cosim + cosisq * (12.0 + 8.0 * cosim - 10.0 * cosisq));
+        let xno2 = nm * nm;
+        let ainv2 = aonv * aonv;
+        let mut temp1 = 3.0 * xno2 * ainv2;
+        let mut temp = temp1 * ROOT22;
+        ds.d2201 = temp * f220 * g201;
+        ds.d2211 = temp * f221 * g211;
+        temp1 *= aonv;
+        temp = temp1 * ROOT32;
+        ds.d3210 = temp * f321 * g310;
+        ds.d3222 = temp * f322 * g322;
+        temp1 *= aonv;
+        temp = 2.0 * temp1 * ROOT44;
+        ds.d4410 = temp * f441 * g410;
+        ds.d4422 = temp * f442 * g422;
+        temp1 *= aonv;
+        temp = temp1 * ROOT52;
+        ds.d5220 = temp * f522 * g520;
+        ds.d5232 = temp * f523 * g532;
+        temp = 2.0 * temp1 * ROOT54;
+        ds.d5421 = temp * f542 * g521;
+        ds.d5433 = temp * f543 * g533;
+        ds.xlamo = (sat.mo + sat.nodeo + sat.nodeo - theta - theta) % TAU;
+        ds.xfact = sat.mdot + ds.dmdt + 2.0 * (sat.nodedot + ds.dnodt - RPTIM) - sat.no;
+    } else {
+        // synchronous resonance
+        let g200 = 1.0 + emsq * (-2.5 + 0.8125 * emsq);
+        let g310 = 1.0 + 2.0 * emsq;
+        let g300 = 1.0 + emsq * (-6.0 + 6.60937 * emsq);
+        let f220 = 0.75 * (1.0 + cosim) * (1.0 + cosim);
+        let f311 = 0.9375 * sinim * sinim * (1.0 + 3.0 * cosim) - 0.75 * (1.0 + cosim);
+        let f330 = 1.875 * (1.0 + cosim).powi(3);
+        let del1 = 3.0 * nm * nm * aonv * aonv;
+        ds.del2 = 2.0 * del1 * f220 * g200 * Q22;
+        ds.del3 = 3.0 * del1 * f330 * g300 * Q33 * aonv;
+        ds.del1 = del1 * f311 * g310 * Q31 * aonv;
+        ds.xlamo = (sat.mo + sat.nodeo + sat.argpo - theta) % TAU;
+        ds.xfact = sat.mdot + xpidot - RPTIM + ds.dmdt + ds.domdt + ds.dnodt - sat.no;
+    }
+}
+
+/// Applies the secular effects of the sun and moon `t` minutes after epoch,
+/// integrating the resonance terms if there are any.
+/// Returns the eccentricity, argument of perigee, inclination, mean anomaly, node,
+/// and mean motion.
+fn dspace(ds: &DeepSpace,
+          sat: &Sgp4,
+          t: f64,
+          em: f64,
+          argpm: f64,
+          inclm: f64,
+          mm: f64,
+          nodem: f64)
+          -> (f64, f64, f64, f64, f64, f64) {
+    const FASX2: f64 = 0.13130908;
+    const FASX4: f64 = 2.8843198;
+    const FASX6: f64 = 0.37448087;
+    const G22: f64 = 5.7686396;
+    const G32: f64 = 0.95240898;
+    const G44: f64 = 1.8014998;
+    const G52: f64 = 1.0508330;
+    const G54: f64 = 4.4108898;
+    const STEPP: f64 = 720.0;
+    const STEP2: f64 = 259200.0;
+
+    let theta = (ds.gsto + t * RPTIM) % TAU;
+    let em = em + ds.dedt * t;
+    let inclm = inclm + ds.didt * t;
+    let argpm = argpm + ds.domdt * t;
+    let nodem = nodem + ds.dnodt * t;
+    let mut mm = mm + ds.dmdt * t;
+    let mut nm = sat.no;
+
+    if ds.irez != 0 {
+        // integrate from epoch in steps of half a day
+        let delt = if t > 0.0 { STEPP } else { -STEPP };
+        let mut atime = 0.0;
+        let mut xni = sat.no;
+        let mut xli = ds.xlamo;
+        let ft;
+        let (mut xndt, mut xldot, mut xnddt);
+        loop {
+            if ds.irez != 2 {
+                // near synchronous
+                xndt = ds.del1 * (xli - FASX2).sin() + ds.del2 * (2.0 * (xli - FASX4)).sin() +
+                       ds.del3 * (3.0 * (xli - FASX6)).sin();
+                xldot = xni + ds.xfact;
+                xnddt = ds.del1 * (xli - FASX2).cos() +
+                        2.0 * ds.del2 * (2.0 * (xli - FASX4)).cos() +
+                        3.0 * ds.del3 * (3.0 * (xli - FASX6)).cos();
+                xnddt *= xldot;
+            } else {
+                // near half a day
+                let xomi = sat.argpo + sat.argpdot * atime;
+                let x2omi = xomi + xomi;
+                let x2li = xli + xli;
+                xndt = ds.d2201 * (x2omi + xli - G22).sin() + ds.d2211 * (xli - G22).sin() +
+                       ds.d3210 * (xomi + xli - G32).sin() +
+                       ds.d3222 * (-xomi + xli - G32).sin() +
+                       ds.d4410 * (x2omi + x2li - G44).sin() +
+                       ds.d4422 * (x2li - G44).sin() +
+                       ds.d5220 * (xomi + xli - G52).sin() +
+                       ds.d5232 * (-xomi + xli - G52).sin() +
+                       ds.d5421 * (xomi + x2li - G54).sin() +
+                       ds.d5433 * (-xomi + x2li - G54).sin();
+                xldot = xni + ds.xfact;
+                xnddt = ds.d2201 * (x2omi + xli - G22).cos() + ds.d2211 * (xli - G22).cos() +
+                        ds.d3210 * (xomi + xli - G32).cos() +
+                        ds.d3222 * (-xomi + xli - G32).cos() +
+                        ds.d5220 * (xomi + xli - G52).cos() +
+                        ds.d5232 * (-xomi + xli - G52).cos() +
+                        2.0 *
+                        (ds.d4410 * (x2omi + x2li - G44).cos() +
+                         ds.d4422 * (x2li - G44).cos() +
+                         ds.d5421 * (xomi + x2li - G54).cos() +
+                         ds.d5433 * (-xomi + x2li - G54).cos());
+                xnddt *= xldot;
+            }
+
+            if (t - atime).abs() < STEPP {
+                ft = t - atime;
+                break;
+            }
+            xli += xldot * delt + xndt * STEP2;
+            xni += xndt * delt + xnddt * STEP2;
+            atime += delt;
+        }
+
+        nm = xni + xndt * ft + xnddt * ft * ft * 0.5;
+        let xl = xli + xldot * ft + xndt * ft * ft * 0.5;
+        mm = if ds.irez != 1 {
+            xl - 2.0 * nodem + 2.0 * theta
+        } else {
+            xl - nodem - argpm + theta
+        };
+    }
+    (em, argpm, inclm, mm, nodem, nm)
+}
+
+/// Adds the periodic effects of the sun and moon `t` minutes after epoch to the elements.
+fn dpper(ds: &DeepSpace,
+         t: f64,
+         ep: &mut f64,
+         inclp: &mut f64,
+         nodep: &mut f64,
+         argpp: &mut f64,
+         mp: &mut f64)
+         -> () {
+    let zm = ds.zmos + ZNS * t;
+    let zf = zm + 2.0 * ZES * zm.sin();
+    let sinzf = zf.sin();
+    let f2 = 0.5 * sinzf * sinzf - 0.25;
+    let f3 = -0.5 * sinzf * zf.cos();
+    let ses = ds.se2 * f2 + ds.se3 * f3;
+    let sis = ds.si2 * f2 + ds.si3 * f3;
+    let sls = ds.sl2 * f2 + ds.sl3 * f3 + ds.sl4 * sinzf;
+    let sghs = ds.sgh2 * f2 + ds.sgh3 * f3 + ds.sgh4 * sinzf;
+    let shs = ds.sh2 * f2 + ds.sh3 * f3;
+    let zm = ds.zmol + ZNL * t;
+    let zf = zm + 2.0 * ZEL * zm.sin();
+    let sinzf = zf.sin();
+    let f2 = 0.5 * sinzf * sinzf - 0.25;
+    let f3 = -0.5 * sinzf * zf.cos();
+    let sel = ds.ee2 * f2 + ds.e3 * f3;
+    let sil = ds.xi2 * f2 + ds.xi3 * f3;
+    let sll = ds.xl2 * f2 + ds.xl3 * f3 + ds.xl4 * sinzf;
+    let sghl = ds.xgh2 * f2 + ds.xgh3 * f3 + ds.xgh4 * sinzf;
+    let shll = ds.xh2 * f2 + ds.xh3 * f3;
+    let pe = ses + sel;
+    let pinc = sis + sil;
+    let pl = sls + sll;
+    let mut pgh = sghs + sghl;
+    let mut ph = shs + shll;
+
+    *inclp += pinc;
+    *ep += pe;
+    let (sinip, cosip) = inclp.sin_cos();
+    if *inclp >= 0.2 {
+        ph /= sinip;
+        pgh -= cosip * ph;
+        *argpp += pgh;
+        *nodep += ph;
+        *mp += pl;
+    } else {
+        // Lyddane's modification, for low inclinations
+        let (sinop, cosop) = nodep.sin_cos();
+        let alfdp = sinip * sinop + ph * cosop + pinc * cosip * sinop;
+        let betdp = sinip * cosop - ph * sinop + pinc * cosip * cosop;
+        *nodep %= TAU;
+        let xls = *mp + *argpp + cosip * *nodep + pl + pgh - pinc * *nodep * sinip;
+        let xnoh = *nodep;
+        *nodep = alfdp.atan2(betdp);
+        if (xnoh - *nodep).abs() > PI {
+            if *nodep < xnoh {
+                *nodep += TAU;
+            } else {
+                *nodep -= TAU;
+            }
+        }
+        *mp += pl;
+        *argpp = xls - *mp - cosip * *nodep;
+    }
+}
@@ -0,0 +1,336 @@
+//! Two-line element sets, the format satellites' orbits are published in.
+//!
+//! See <https://celestrak.org/columns/v04n03/> for the format. The elements are mean elements
+//! meant for SGP4 (see [`sgp4`](../sgp4/index.html)), so treating them as a Keplerian trajectory
+//! is only an approximation.
+
+use std::error::Error;
+use std::fmt;
+use tau::TAU;
+
+use bodies::{EARTH, PRESETS};
+use orbits::{Plane, PlanarTrajectory, Trajectory};
+
+/// How many characters there are on each line (including the checksum).
+const LINE_LENGTH: usize = 69;
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// One satellite's orbit, as read from a TLE.
+#[derive(Clone,Debug,PartialEq,Serialize,Deserialize)]
+pub struct Tle {
+    /// The name from the line before the elements (if there was one).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub catalog_number: u32,
+    pub classification: char,
+    /// The launch year, launch number, and piece (e.g. `98067A`).
+    pub intl_designator: String,
+    /// When the elements are for.
+    #[serde(rename = "epoch_s")]
+    pub epoch: f64, // s since J2000 (UTC, ignoring leap seconds)
+    #[serde(rename = "mean_motion_dot_rad_s2")]
+    pub mean_motion_dot: f64, // rad/s²
+    #[serde(rename = "mean_motion_ddot_rad_s3")]
+    pub mean_motion_ddot: f64, // rad/s³
+    /// The drag term used by SGP4.
+    #[serde(rename = "bstar_per_earth_radius")]
+    pub bstar: f64, // 1/Earth radii
+    pub element_set: u32,
+    #[serde(rename = "inclination_rad")]
+    pub inclination: f64, // rad
+    #[serde(rename = "lon_asc_node_rad")]
+    pub lon_asc_node: f64, // rad
+    #[serde(rename = "eccentricity")]
+    pub eccentr: f64,
+    #[serde(rename = "arg_peri_rad")]
+    pub arg_peri: f64, // rad
+    #[serde(rename = "mean_anom_rad")]
+    pub mean_anom: f64, // rad (at `epoch`)
+    #[serde(rename = "mean_motion_rad_s")]
+    pub mean_motion: f64, // rad/s
+    /// How many revolutions the satellite had made by `epoch`.
+    pub rev_number: u32,
+}
+
+/// What was wrong with a TLE. Lines are numbered from 1.
+#[derive(Clone,Debug,PartialEq)]
+pub enum TleError {
+    /// The line wasn't 69 characters long.
+    WrongLength { line: usize, length: usize },
+    /// The line didn't start with the line number (1 or 2) it should have.
+    WrongLineNumber { line: usize, expected: u8 },
+    /// The last digit of the line didn't match the checksum of the rest of it.
+    BadChecksum { line: usize, expected: u8, actual: u8 },
+    /// One of the fields couldn't be read.
+    BadField {
+        line: usize,
+        field: &'static str,
+        text: String,
+    },
+    /// The two lines have different catalog numbers.
+    CatalogMismatch { line: usize },
+    /// The text ended partway through an element set.
+    MissingLine { line: usize },
+}
+
+impl TleError {
+    /// Returns the number of the line the error is on.
+    pub fn line(&self) -> usize {
+        match *self {
+            TleError::WrongLength { line, .. } |
+            TleError::WrongLineNumber { line, .. } |
+            TleError::BadChecksum { line, .. } |
+            TleError::BadField { line, .. } |
+            TleError::CatalogMismatch { line } |
+            TleError::MissingLine { line } => line,
+        }
+    }
+}
+
+impl fmt::Display for TleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line())?;
+        match *self {
+            TleError::WrongLength { length, .. } => {
+                write!(f, "expected {} characters but found {}", LINE_LENGTH, length)
+            }
+            TleError::WrongLineNumber { expected, .. } => {
+                write!(f, "expected line {} of an element set", expected)
+            }
+            TleError::BadChecksum { expected, actual, .. } => {
+                write!(f, "checksum is {} but should be {}", actual, expected)
+            }
+            TleError::BadField { field, ref text, .. } => {
+                write!(f, "couldn't read the {} from {:?}", field, text)
+            }
+            TleError::CatalogMismatch { .. } => {
+                write!(f, "catalog number doesn't match the previous line")
+            }
+            TleError::MissingLine { .. } => write!(f, "the element set is missing a line"),
+        }
+    }
+}
+
+impl Error for TleError {}
+
+impl Tle {
+    /// Reads a TLE from its two lines.
+    pub fn parse(line1: &str, line2: &str) -> Result<Tle, TleError> {
+        Tle::parse_at(None, (1, line1), (2, line2))
+    }
+
+    /// Reads a TLE from its two lines, each with its line number.
+    fn parse_at(name: Option<String>,
+                (n1, line1): (usize, &str),
+                (n2, line2): (usize, &str))
+                -> Result<Tle, TleError> {
+        let l1 = Line::new(line1, n1, 1)?;
+        let l2 = Line::new(line2, n2, 2)?;
+
+        let catalog_number = l1.parse(3, 7, "catalog number")?;
+        if l2.parse::<u32>(3, 7, "catalog number")? != catalog_number {
+            return Err(TleError::CatalogMismatch { line: n2 });
+        }
+
+        let year: u32 = l1.parse(19, 20, "epoch year")?;
+        // two-digit years start at 1957, when Sputnik was launched
+        let year = if year < 57 { 2000 + year } else { 1900 + year };
+        let day: f64 = l1.parse(21, 32, "epoch day")?;
+        let epoch = (days_to_year(year) + day - 1.5) * SECONDS_PER_DAY;
+
+        let rev_per_day = TAU / SECONDS_PER_DAY;
+        let rev_per_day2 = rev_per_day / SECONDS_PER_DAY;
+        let rev_per_day3 = rev_per_day2 / SECONDS_PER_DAY;
+        let classification = l1.field(8, 8).chars().next().unwrap_or(' ');
+        Ok(Tle {
+            name: name,
+            catalog_number: catalog_number,
+            classification: classification,
+            intl_designator: l1.field(10, 17).to_owned(),
+            epoch: epoch,
+            // the TLE has half of the first derivative and a sixth of the second
+            mean_motion_dot: 2.0 * l1.parse::<f64>(34, 43, "mean motion derivative")? *
+                             rev_per_day2,
+            mean_motion_ddot: 6.0 * l1.exponent(45, 52, "mean motion second derivative")? *
+                              rev_per_day3,
+            bstar: l1.exponent(54, 61, "drag term")?,
+            element_set: l1.parse(65, 68, "element set number")?,
+            inclination: l2.parse::<f64>(9, 16, "inclination")?.to_radians(),
+            lon_asc_node: l2.parse::<f64>(18, 25, "right ascension of the ascending node")?
+                .to_radians(),
+            eccentr: l2.decimal(27, 33, "eccentricity")?,
+            arg_peri: l2.parse::<f64>(35, 42, "argument of perigee")?.to_radians(),
+            mean_anom: l2.parse::<f64>(44, 51, "mean anomaly")?.to_radians(),
+            mean_motion: l2.parse::<f64>(53, 63, "mean motion")? * rev_per_day,
+            rev_number: l2.parse(64, 68, "revolution number")?,
+        })
+    }
+
+    /// Returns the international designator in the `YYYY-NNNP` form other formats use
+    /// (e.g. `1998-067A`), if it's there.
+    pub fn object_id(&self) -> Option<String> {
+        let designator = &self.intl_designator;
+        let year: u32 = designator.get(..2)?.parse().ok()?;
+        let year = if year < 57 { 2000 + year } else { 1900 + year };
+        Some(format!("{}-{}", year, designator.get(2..)?))
+    }
+
+    /// Computes the Keplerian trajectory around Earth with these elements.
+    /// The satellite is at `mean_anom0` at `epoch`.
+    pub fn trajectory(&self) -> Trajectory {
+        let mu = PRESETS[EARTH].mu;
+        let semi_major_axis = (mu / self.mean_motion.powi(2)).cbrt();
+        Trajectory {
+            p: Plane {
+                lon_asc_node: self.lon_asc_node,
+                inclination: self.inclination,
+                arg_peri: self.arg_peri,
+            },
+            t: PlanarTrajectory {
+                periapsis: semi_major_axis * (1.0 - self.eccentr),
+                eccentr: self.eccentr,
+                mean_anom0: self.mean_anom,
+                mean_anom1: self.mean_anom + TAU,
+            },
+        }
+    }
+}
+
+/// Reads every TLE in `text`, which can have a name on the line before each one.
+/// Blank lines are skipped.
+pub fn parse_all(text: &str) -> Result<Vec<Tle>, TleError> {
+    let mut lines = text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim_end()))
+        .filter(|&(_, line)| !line.is_empty());
+    let mut tles = Vec::new();
+    while let Some((n, line)) = lines.next() {
+        let (name, n1, line1) = if line.starts_with("1 ") {
+            (None, n, line)
+        } else if line.starts_with("2 ") {
+            return Err(TleError::WrongLineNumber {
+                line: n,
+                expected: 1,
+            });
+        } else {
+            // some sources put a "0 " in front of names
+            let name = line.strip_prefix("0 ").unwrap_or(line);
+            let (n1, line1) = lines.next().ok_or(TleError::MissingLine { line: n + 1 })?;
+            (Some(name.trim().to_owned()), n1, line1)
+        };
+        let (n2, line2) = lines.next().ok_or(TleError::MissingLine { line: n1 + 1 })?;
+        tles.push(Tle::parse_at(name, (n1, line1), (n2, line2))?);
+    }
+    Ok(tles)
+}
+
+/// One line of a TLE that's the right length and has the right checksum.
+struct Line<'a> {
+    text: &'a str,
+    n: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(text: &'a str, n: usize, line_number: u8) -> Result<Line<'a>, TleError> {
+        let text = text.trim_end();
+        let length = text.chars().count();
+        if length != LINE_LENGTH {
+            return Err(TleError::WrongLength {
+                line: n,
+                length: length,
+            });
+        }
+        if !text.is_ascii() {
+            return Err(TleError::BadField {
+                line: n,
+                field: "line (it has characters that aren't ASCII)",
+                text: text.to_owned(),
+            });
+        }
+        if !text.starts_with(&format!("{} ", line_number)) {
+            return Err(TleError::WrongLineNumber {
+                line: n,
+                expected: line_number,
+            });
+        }
+        let actual = text.as_bytes()[LINE_LENGTH - 1];
+        let expected = checksum(&text[..LINE_LENGTH - 1]);
+        if actual != b'0' + expected {
+            return Err(TleError::BadChecksum {
+                line: n,
+                expected: expected,
+                actual: actual.wrapping_sub(b'0'),
+            });
+        }
+        Ok(Line { text: text, n: n })
+    }
+
+    /// Returns columns `first` to `last` (numbered from 1, inclusive) without the spaces around.
+    fn field(&self, first: usize, last: usize) -> &'a str {
+        self.text[first - 1..last].trim()
+    }
+
+    fn error(&self, first: usize, last: usize, field: &'static str) -> TleError {
+        TleError::BadField {
+            line: self.n,
+            field: field,
+            text: self.text[first - 1..last].to_owned(),
+        }
+    }
+
+    fn parse<T: ::std::str::FromStr>(&self,
+                                     first: usize,
+                                     last: usize,
+                                     field: &'static str)
+                                     -> Result<T, TleError> {
+        self.field(first, last).parse().map_err(|_| self.error(first, last, field))
+    }
+
+    /// Reads a number with an implied `0.` in front, like `0006703` for 0.0006703.
+    fn decimal(&self, first: usize, last: usize, field: &'static str) -> Result<f64, TleError> {
+        let digits = self.field(first, last);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(self.error(first, last, field));
+        }
+        format!("0.{}", digits).parse().map_err(|_| self.error(first, last, field))
+    }
+
+    /// Reads a number with an implied decimal point and a power of ten,
+    /// like `-11606-4` for -0.11606e-4.
+    fn exponent(&self, first: usize, last: usize, field: &'static str) -> Result<f64, TleError> {
+        let text = self.field(first, last);
+        let (sign, rest) = match text.as_bytes().first() {
+            Some(&b'-') => ("-", &text[1..]),
+            Some(&b'+') => ("", &text[1..]),
+            _ => ("", text),
+        };
+        let split = rest.rfind(['-', '+']).filter(|&i| i > 0);
+        let result = match split {
+            Some(i) if rest[..i].bytes().all(|b| b.is_ascii_digit()) => {
+                format!("{}0.{}e{}", sign, &rest[..i], &rest[i..]).parse()
+            }
+            _ => return Err(self.error(first, last, field)),
+        };
+        result.map_err(|_| self.error(first, last, field))
+    }
+}
+
+/// Adds up the digits of `text`, counting minus signs as 1.
+fn checksum(text: &str) -> u8 {
+    let sum: u32 = text.bytes()
+        .map(|b| match b {
+            b'0'..=b'9' => (b - b'0') as u32,
+            b'-' => 1,
+            _ => 0,
+        })
+        .sum();
+    (sum % 10) as u8
+}
+
+/// Counts the days from the start of 2000 to the start of `year`
+/// (which has to be between 1901 and 2099).
+fn days_to_year(year: u32) -> f64 {
+    let years = year as i32 - 2000;
+    let leap_days = (years + 3).div_euclid(4);
+    (365 * years + leap_days) as f64
+}
@@ -0,0 +1,217 @@
+//! The preset bodies' orientations, putting their positions together, patched conics, and J2
+//! drift.
+
+extern crate nalgebra as na;
+extern crate orbit_sim;
+
+use std::f64::consts::PI;
+
+use na::Vector3;
+
+use orbit_sim::bodies::{self, Leg, ReferencePlane, EARTH, PRESETS, SUN};
+use orbit_sim::orbits::{PlanarTrajectory, Plane, Trajectory};
+
+const MOON: usize = 4;
+const JUPITER: usize = 8;
+const IO: usize = 9;
+const TAU: f64 = 2.0 * PI;
+
+/// Computes the angle (deg) between two directions.
+fn angle(a: &Vector3<f64>, b: &Vector3<f64>) -> f64 {
+    a.angle(b).to_degrees()
+}
+
+/// The planets' and the Moon's poles should be tilted from their orbits by their axial tilts.
+/// (The other moons' nodes are made up, so their orbits aren't really anywhere in particular.)
+#[test]
+fn axial_tilts() {
+    for (i, body) in PRESETS.iter().enumerate() {
+        let orbit = match body.orbit {
+            Some(orbit) if orbit.reference == ReferencePlane::Ecliptic => orbit,
+            _ => continue,
+        };
+        let normal = PRESETS[orbit.parent].equator() * orbit.to_parent(&PRESETS) *
+                     orbit.trajectory.normal();
+        let tilt = angle(&body.pole, &normal);
+        assert!((tilt - body.axial_tilt.to_degrees()).abs() < 1.0,
+                "{} ({}) is tilted {}° instead of {}°",
+                body.name,
+                i,
+                tilt,
+                body.axial_tilt.to_degrees());
+    }
+}
+
+#[test]
+fn earth_is_in_the_ecliptic() {
+    assert_eq!(PRESETS[EARTH].equator(), na::Rotation3::identity());
+    for &time in &[0.0, 1e7, 2e7] {
+        let pos = bodies::relative_position(&PRESETS, SUN, EARTH, time);
+        let pos = bodies::ecliptic().inverse() * PRESETS[SUN].equator() * pos;
+        assert!(pos.z.abs() < 1e-6 * pos.norm(), "{:?} at {} s", pos, time);
+    }
+}
+
+/// The Moon's orbit is 5.145° from the ecliptic,
+/// which is 23.44° from Earth's equator that its position is relative to.
+#[test]
+fn moon_is_tilted_from_the_equator() {
+    let (pos, vel) = bodies::state(&PRESETS, MOON, 0.0);
+    let inclination = angle(&pos.cross(&vel), &Vector3::z());
+    assert!(inclination > 23.44 - 5.145 && inclination < 23.44 + 5.145,
+            "{}°",
+            inclination);
+    let relative = bodies::relative_position(&PRESETS, EARTH, MOON, 0.0);
+    assert!((relative - pos).norm() < 1e-6, "{:?} and {:?}", relative, pos);
+}
+
+/// Io's orbit is on Jupiter's equator, not the ecliptic.
+#[test]
+fn io_is_on_jupiters_equator() {
+    let (pos, vel) = bodies::state(&PRESETS, IO, 0.0);
+    assert!(angle(&pos.cross(&vel), &Vector3::z()) < 0.1);
+
+    let from_io = bodies::relative_position(&PRESETS, IO, JUPITER, 0.0);
+    let back = bodies::change_of_axes(&PRESETS, IO, JUPITER) * from_io;
+    assert!((back + pos).norm() < 1e-6, "{:?} and {:?}", back, pos);
+}
+
+/// Where the craft on `leg` is at `time`, relative to the Sun (and its equator).
+fn from_sun(leg: &Leg, time: f64) -> Vector3<f64> {
+    bodies::relative_position(&PRESETS, SUN, leg.body, time) +
+    bodies::change_of_axes(&PRESETS, leg.body, SUN) * leg.state(&PRESETS, time).0
+}
+
+/// Checks that each leg starts where and when the one before it ended, going the same way.
+fn assert_continuous(legs: &[Leg]) {
+    for pair in legs.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let time = b.start_time;
+        assert_eq!(a.end_time, time);
+        let (pos_a, pos_b) = (from_sun(a, time), from_sun(b, time));
+        assert!((pos_a - pos_b).norm() < 1e-3,
+                "jumped from {:?} to {:?} going from {} to {}",
+                pos_a,
+                pos_b,
+                a.body,
+                b.body);
+        // (the velocities, from how far the craft goes in a second either side)
+        let vel_a = pos_a - from_sun(a, time - 1.0);
+        let vel_b = from_sun(b, time + 1.0) - pos_b;
+        assert!((vel_a - vel_b).norm() < 1e-4,
+                "went from {:?} to {:?} km/s going from {} to {}",
+                vel_a,
+                vel_b,
+                a.body,
+                b.body);
+    }
+}
+
+fn departure(lon_asc_node: f64, inclination: f64, arg_peri: f64, eccentr: f64) -> Trajectory {
+    Trajectory {
+        p: Plane {
+            lon_asc_node,
+            inclination,
+            arg_peri,
+        },
+        t: PlanarTrajectory {
+            periapsis: PRESETS[EARTH].radius + 200.0,
+            eccentr,
+            mean_anom0: 0.0,
+            mean_anom1: TAU,
+        },
+    }
+}
+
+const DAY: f64 = 86400.0; // s
+
+#[test]
+fn escape_to_the_sun() {
+    let traj = departure(0.0, 0.0, 0.0, 1.5);
+    let legs = bodies::propagate_patched(&PRESETS, EARTH, &traj, 0.0, 30.0 * DAY);
+    let bodies: Vec<usize> = legs.iter().map(|leg| leg.body).collect();
+    assert_eq!(bodies, [EARTH, SUN]);
+    assert_eq!(legs[1].end_time, 30.0 * DAY);
+
+    let soi = PRESETS[EARTH].soi_radius(&PRESETS).unwrap();
+    let exit = legs[0].state(&PRESETS, legs[0].end_time).0.norm();
+    assert!((exit - soi).abs() < 1e-6 * soi, "left at {} km instead of {}", exit, soi);
+    assert_continuous(&legs);
+}
+
+#[test]
+fn lunar_flyby() {
+    let traj = departure(2.18369, 2.73514, 0.94461, 0.98319);
+    let legs = bodies::propagate_patched(&PRESETS, EARTH, &traj, 0.0, 30.0 * DAY);
+    let bodies: Vec<usize> = legs.iter().map(|leg| leg.body).collect();
+    assert_eq!(bodies, [EARTH, MOON, EARTH]);
+    for leg in &legs {
+        assert!(leg.end_time > leg.start_time, "{:?}", leg);
+    }
+
+    let soi = PRESETS[MOON].soi_radius(&PRESETS).unwrap();
+    for &(leg, time) in &[(&legs[1], legs[1].start_time), (&legs[1], legs[1].end_time)] {
+        let r = leg.state(&PRESETS, time).0.norm();
+        assert!((r - soi).abs() < 1e-6 * soi, "crossed at {} km instead of {}", r, soi);
+    }
+    assert_continuous(&legs);
+}
+
+/// The craft comes into the Moon's sphere of influence a hair outside it after rounding,
+/// which once made it bounce between the Moon and Earth with legs that took no time at all.
+#[test]
+fn entering_on_the_edge() {
+    let mut traj = departure(0.4246055888570359, 0.3512835350227004, 4.055026032977952,
+                             0.9664865269100936);
+    traj.t.periapsis = 6578.0;
+    let legs = bodies::propagate_patched(&PRESETS, EARTH, &traj, 0.0, 30.0 * DAY);
+    let bodies: Vec<usize> = legs.iter().map(|leg| leg.body).collect();
+    assert_eq!(bodies, [EARTH, MOON, EARTH]);
+    for leg in &legs {
+        assert!(leg.end_time > leg.start_time, "{:?}", leg);
+    }
+    assert_continuous(&legs);
+}
+
+/// A sun-synchronous orbit's plane turns once a year, to keep up with the Sun.
+/// At 700 km, that takes an inclination of about 98.19°.
+#[test]
+fn j2_drift_sun_synchronous() {
+    let earth = &PRESETS[EARTH];
+    let a = earth.radius + 700.0;
+    let traj = Trajectory {
+        p: Plane {
+            lon_asc_node: 0.5,
+            inclination: 98.19f64.to_radians(),
+            arg_peri: 1.0,
+        },
+        t: PlanarTrajectory {
+            periapsis: a,
+            eccentr: 0.0,
+            mean_anom0: 0.0,
+            mean_anom1: TAU,
+        },
+    };
+    let dt = 86400.0;
+    let drifted = earth.j2_drift(&traj, dt);
+    assert_eq!(drifted.p.inclination, traj.p.inclination);
+    assert_eq!(drifted.t.periapsis, a);
+
+    let year = 365.2422 * 86400.0;
+    let node_rate = (drifted.p.lon_asc_node - traj.p.lon_asc_node) / dt;
+    assert!((node_rate - TAU / year).abs() < 2e-3 * TAU / year,
+            "the node moves {} rad/s instead of {}",
+            node_rate,
+            TAU / year);
+
+    // and the periapsis goes backwards about 3.1° a day
+    let n = (earth.mu / a.powi(3)).sqrt();
+    let cos_i = traj.p.inclination.cos();
+    let expected = 0.75 * earth.j2 * (earth.radius / a).powi(2) * n * (5.0 * cos_i * cos_i - 1.0);
+    let peri_rate = (drifted.p.arg_peri - traj.p.arg_peri) / dt;
+    assert!((peri_rate - expected).abs() < 1e-9 * expected.abs(),
+            "the periapsis moves {} rad/s instead of {}",
+            peri_rate,
+            expected);
+    assert!((peri_rate.to_degrees() * 86400.0 + 3.11).abs() < 0.01);
+}
@@ -0,0 +1,271 @@
+//! Round trips through OPMs and OEMs, and the examples from CCSDS 502.0-B-2.
+
+extern crate orbit_sim;
+
+use orbit_sim::bodies::{wrap_angle, EARTH, PRESETS};
+use orbit_sim::ccsds::{self, CcsdsError, Message, Metadata, Oem, Opm};
+use orbit_sim::orbits::{PlanarTrajectory, Plane, Trajectory};
+
+const POSITION_TOLERANCE: f64 = 1e-5; // km
+const VELOCITY_TOLERANCE: f64 = 1e-8; // km/s
+
+fn molniya() -> Trajectory {
+    Trajectory {
+        p: Plane {
+            lon_asc_node: 1.2,
+            inclination: 63.4f64.to_radians(),
+            arg_peri: -1.5,
+        },
+        t: PlanarTrajectory {
+            periapsis: 7000.0,
+            eccentr: 0.72,
+            mean_anom0: 0.3,
+            mean_anom1: 0.3 + std::f64::consts::PI * 2.0,
+        },
+    }
+}
+
+fn assert_same_trajectory(a: &Trajectory, b: &Trajectory) {
+    assert!((a.t.periapsis - b.t.periapsis).abs() < 1e-4, "{:?} != {:?}", a, b);
+    assert!((a.t.eccentr - b.t.eccentr).abs() < 1e-9, "{:?} != {:?}", a, b);
+    let angles = [(a.p.lon_asc_node, b.p.lon_asc_node),
+                  (a.p.inclination, b.p.inclination),
+                  (a.p.arg_peri, b.p.arg_peri),
+                  (a.t.mean_anom0, b.t.mean_anom0)];
+    for &(x, y) in &angles {
+        assert!(wrap_angle(x - y).abs() < 1e-8, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn epochs() {
+    assert_eq!(ccsds::format_epoch(0.0), "2000-01-01T12:00:00.000000");
+    assert_eq!(ccsds::format_epoch(-43200.5), "1999-12-31T23:59:59.500000");
+    assert_eq!(ccsds::parse_epoch("2000-01-01T12:00:00"), Some(0.0));
+    assert_eq!(ccsds::parse_epoch("2000-001T12:00:00Z"), Some(0.0));
+    assert_eq!(ccsds::parse_epoch("2000-03-01T12:00:00"), Some(60.0 * 86400.0));
+    assert_eq!(ccsds::parse_epoch("2000-13-01T12:00:00"), None);
+    assert_eq!(ccsds::parse_epoch("2000-01-01T12:00"), None);
+    // days that don't exist
+    assert_eq!(ccsds::parse_epoch("2000-02-30T12:00:00"), None);
+    assert_eq!(ccsds::parse_epoch("2001-02-29T12:00:00"), None);
+    assert_eq!(ccsds::parse_epoch("2001-02-31T12:00:00"), None);
+    assert_eq!(ccsds::parse_epoch("2001-04-31T12:00:00"), None);
+    assert_eq!(ccsds::parse_epoch("2001-366T12:00:00"), None);
+    assert_eq!(ccsds::parse_epoch("1900-02-29T12:00:00"), None);
+    // and leap days that do
+    assert_eq!(ccsds::parse_epoch("2000-02-29T12:00:00"), Some(59.0 * 86400.0));
+    assert_eq!(ccsds::parse_epoch("2000-366T12:00:00"), Some(365.0 * 86400.0));
+    assert_eq!(ccsds::parse_epoch("2000-12-31T12:00:00"), Some(365.0 * 86400.0));
+    for &date in &[-1e9, -123456.789, 3.25, 275185540.104192, 3e9] {
+        let text = ccsds::format_epoch(date);
+        let back = ccsds::parse_epoch(&text).unwrap();
+        assert!((back - date).abs() < 1e-6, "{} became {} and then {}", date, text, back);
+    }
+}
+
+#[test]
+fn opm_round_trip() {
+    let mu = PRESETS[EARTH].mu;
+    let traj = molniya();
+    let epoch = 1000.0;
+    let meta = Metadata {
+        object_name: "MOLNIYA 1-93".to_owned(),
+        object_id: "2004-005A".to_owned(),
+        ..Metadata::default()
+    };
+    let opm = Opm::from_trajectory(meta, &traj, mu, epoch, 25000.0);
+    let text = opm.to_kvn();
+    let read = Opm::parse(&text).unwrap();
+    assert_eq!(read.header, opm.header);
+    assert_eq!(read.meta, opm.meta);
+    assert_eq!(read.gm, Some(mu));
+    assert!((read.state.epoch - opm.state.epoch).abs() < 1e-6);
+    assert!((read.state.position - opm.state.position).norm() < POSITION_TOLERANCE);
+    assert!((read.state.velocity - opm.state.velocity).norm() < VELOCITY_TOLERANCE);
+    assert_same_trajectory(&read.trajectory(mu, epoch), &traj);
+    assert_eq!(Message::parse(&text), Ok(Message::Opm(read)));
+}
+
+#[test]
+fn oem_round_trip() {
+    let mu = PRESETS[EARTH].mu;
+    let traj = molniya();
+    let period = traj.t.period(mu).unwrap();
+    let dates: Vec<f64> = (0..50).map(|i| i as f64 * period / 49.0).collect();
+    let oem = Oem::from_trajectory(Metadata::default(), &traj, mu, 0.0, &dates);
+    let text = oem.to_kvn();
+    let read = Oem::parse(&text).unwrap();
+    assert_eq!(read.segments.len(), 1);
+    let (expected, actual) = (&oem.segments[0], &read.segments[0]);
+    assert_eq!(actual.meta, expected.meta);
+    assert_eq!(actual.states.len(), expected.states.len());
+    for (a, b) in actual.states.iter().zip(&expected.states) {
+        assert!((a.epoch - b.epoch).abs() < 1e-6);
+        assert!((a.position - b.position).norm() < POSITION_TOLERANCE);
+        assert!((a.velocity - b.velocity).norm() < VELOCITY_TOLERANCE);
+    }
+    assert_same_trajectory(&actual.trajectory(mu, 0.0).unwrap(), &traj);
+    // the last state is a whole period later, back where it started
+    let last = expected.states.last().unwrap();
+    assert!((last.position - expected.states[0].position).norm() < 1e-6);
+}
+
+const EXAMPLE_OPM: &str = "CCSDS_OPM_VERS = 2.0
+COMMENT Generated by GSOC, R. Kiehling
+COMMENT Current intermediate orbit IO2 and maneuver planning data
+CREATION_DATE = 2000-06-03T05:33:00.000
+ORIGINATOR = GSOC
+OBJECT_NAME = EUTELSAT W4
+OBJECT_ID = 2000-028A
+CENTER_NAME = EARTH
+REF_FRAME = TOD
+TIME_SYSTEM = UTC
+COMMENT State Vector
+EPOCH = 2006-06-03T00:00:00.000
+X = 6655.9942 [km]
+Y = -40218.5751 [km]
+Z = -82.9177 [km]
+X_DOT = 3.11548208 [km/s]
+Y_DOT = 0.47042605 [km/s]
+Z_DOT = -0.00101495 [km/s]
+COMMENT Keplerian elements
+SEMI_MAJOR_AXIS = 41399.5123 [km]
+ECCENTRICITY = 0.020842611
+INCLINATION = 0.117746 [deg]
+RA_OF_ASC_NODE = 17.604721 [deg]
+ARG_OF_PERICENTER = 218.242943 [deg]
+TRUE_ANOMALY = 41.922339 [deg]
+GM = 398600.4415 [km**3/s**2]
+COMMENT Spacecraft parameters
+MASS = 1913.000 [kg]
+SOLAR_RAD_AREA = 10.000 [m**2]
+SOLAR_RAD_COEFF = 1.300
+DRAG_AREA = 10.000 [m**2]
+DRAG_COEFF = 2.300
+COMMENT 2 planned maneuvers
+MAN_EPOCH_IGNITION = 2000-06-03T09:00:34.1
+MAN_DURATION = 132.60 [s]
+MAN_DELTA_MASS = -18.418 [kg]
+MAN_REF_FRAME = EME2000
+MAN_DV_1 = -0.02325700 [km/s]
+MAN_DV_2 = 0.01683160 [km/s]
+MAN_DV_3 = -0.00893444 [km/s]
+MAN_EPOCH_IGNITION = 2000-06-05T18:59:21.0
+MAN_DURATION = 0.00 [s]
+MAN_DELTA_MASS = -1.469 [kg]
+MAN_REF_FRAME = RTN
+MAN_DV_1 = 0.00101500 [km/s]
+MAN_DV_2 = -0.00187300 [km/s]
+MAN_DV_3 = 0.00000000 [km/s]
+";
+
+#[test]
+fn example_opm() {
+    let opm = Opm::parse(EXAMPLE_OPM).unwrap();
+    assert_eq!(opm.header.originator, "GSOC");
+    assert_eq!(opm.meta.object_name, "EUTELSAT W4");
+    assert_eq!(opm.meta.ref_frame, "TOD");
+    assert_eq!(opm.state.epoch, ccsds::parse_epoch("2006-06-03T00:00:00").unwrap());
+    let gm = opm.gm.unwrap();
+    assert_eq!(gm, 398600.4415);
+    // the state vector should agree with the elements
+    let traj = opm.trajectory(gm, opm.state.epoch);
+    let semi_major_axis = traj.t.periapsis / (1.0 - traj.t.eccentr);
+    assert!((semi_major_axis - 41399.5123).abs() < 0.01, "{:?}", traj);
+    assert!((traj.t.eccentr - 0.020842611).abs() < 1e-6, "{:?}", traj);
+    assert!((traj.p.inclination.to_degrees() - 0.117746).abs() < 1e-5, "{:?}", traj);
+    assert!((traj.p.lon_asc_node.to_degrees() - 17.604721).abs() < 1e-3, "{:?}", traj);
+}
+
+const EXAMPLE_OEM: &str = "CCSDS_OEM_VERS = 2.0
+CREATION_DATE = 1996-11-04T17:22:31
+ORIGINATOR = NASA/JPL
+
+META_START
+OBJECT_NAME = MARS GLOBAL SURVEYOR
+OBJECT_ID = 1996-062A
+CENTER_NAME = MARS BARYCENTER
+REF_FRAME = EME2000
+TIME_SYSTEM = UTC
+START_TIME = 1996-12-18T12:00:00.331
+USEABLE_START_TIME = 1996-12-18T12:10:00.331
+USEABLE_STOP_TIME = 1996-12-28T21:23:00.331
+STOP_TIME = 1996-12-28T21:28:00.331
+INTERPOLATION = HERMITE
+INTERPOLATION_DEGREE = 7
+META_STOP
+
+COMMENT This file was produced by M.R. Somebody, MSOO NAV/JPL, 1996NOV 04. It is
+COMMENT to be used for DSN scheduling purposes only.
+1996-12-18T12:00:00.331 2789.619 -280.045 -1746.755 4.73372 -2.49586 -1.04195
+1996-12-18T12:01:00.331 2783.419 -308.143 -1877.071 5.18604 -2.42124 -1.99608
+1996-12-18T12:02:00.331 2776.033 -336.859 -2008.682 5.63678 -2.33951 -1.94687
+1996-12-28T21:28:00.331 -3881.024 563.959 -682.773 -3.28827 -3.66735 1.63861
+
+META_START
+OBJECT_NAME = MARS GLOBAL SURVEYOR
+OBJECT_ID = 1996-062A
+CENTER_NAME = MARS BARYCENTER
+REF_FRAME = EME2000
+TIME_SYSTEM = UTC
+START_TIME = 1996-12-28T21:29:07.267
+STOP_TIME = 1996-12-30T01:28:02.267
+META_STOP
+
+1996-12-28T21:29:07.267 -2432.166 -063.042 1742.754 7.33702 -3.495867 -1.041945
+1996-12-28T21:59:02.267 -2445.234 -878.141 1873.073 1.86043 -3.421256 -0.996366
+1996-12-29T01:28:02.267 -2458.079 -683.858 2007.684 6.36786 -3.339563 -0.946654
+
+COVARIANCE_START
+EPOCH = 1996-12-28T21:29:07.267
+COV_REF_FRAME = EME2000
+3.3313494e-04
+4.6189273e-04 6.7824216e-04
+-3.0700078e-04 -4.2212341e-04 3.2319319e-04
+-3.3493650e-07 -4.6860842e-07 2.4849495e-07 4.2960228e-10
+-2.2118325e-07 -2.8641868e-07 1.7980986e-07 2.6088992e-10 1.7675147e-10
+-3.0413460e-07 -4.9894969e-07 3.5403109e-07 1.8692631e-10 1.0088625e-10 6.2244443e-10
+COVARIANCE_STOP
+";
+
+#[test]
+fn example_oem() {
+    let oem = match Message::parse(EXAMPLE_OEM).unwrap() {
+        Message::Oem(oem) => oem,
+        Message::Opm(_) => panic!("the example OEM was read as an OPM"),
+    };
+    assert_eq!(oem.header.originator, "NASA/JPL");
+    assert_eq!(oem.segments.len(), 2);
+    assert_eq!(oem.segments[0].meta.center_name, "MARS BARYCENTER");
+    assert_eq!(oem.segments[0].states.len(), 4);
+    assert_eq!(oem.segments[1].states.len(), 3);
+    let first = oem.segments[0].states[0];
+    assert_eq!(first.epoch, ccsds::parse_epoch("1996-12-18T12:00:00.331").unwrap());
+    assert_eq!(first.position.x, 2789.619);
+    assert_eq!(first.velocity.z, -1.04195);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(Opm::parse(EXAMPLE_OEM),
+               Err(CcsdsError::WrongMessage {
+                   line: 1,
+                   expected: "CCSDS_OPM_VERS",
+               }));
+    let no_epoch = EXAMPLE_OPM.replace("EPOCH = 2006", "EPOCHS = 2006");
+    assert_eq!(Opm::parse(&no_epoch), Err(CcsdsError::Missing { keyword: "EPOCH" }));
+    let meters = EXAMPLE_OPM.replace("Y = -40218.5751 [km]", "Y = -40218575.1 [m]");
+    match Opm::parse(&meters) {
+        Err(CcsdsError::BadValue { line: 14, .. }) => {}
+        other => panic!("{:?}", other),
+    }
+    let short_row = EXAMPLE_OEM.replace(" -1.04195\n", "\n");
+    match Oem::parse(&short_row) {
+        Err(CcsdsError::BadValue { line: 21, .. }) => {}
+        other => panic!("{:?}", other),
+    }
+    let unfinished = EXAMPLE_OEM.replace("COVARIANCE_STOP\n", "");
+    assert_eq!(Oem::parse(&unfinished),
+               Err(CcsdsError::Missing { keyword: "COVARIANCE_STOP" }));
+}
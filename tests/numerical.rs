@@ -0,0 +1,167 @@
+//! The integrators against what has to stay the same in the two-body problem, and against the
+//! Kepler solution.
+
+extern crate nalgebra as na;
+extern crate orbit_sim;
+
+use na::Vector3;
+
+use orbit_sim::numerical::{self, Integrator, PointMass, PropagationError, StateVector};
+use orbit_sim::orbits::{PlanarTrajectory, Plane, Trajectory};
+
+const MU: f64 = 398600.4418; // km³/s²
+
+fn orbit() -> Trajectory {
+    Trajectory {
+        p: Plane {
+            lon_asc_node: 0.4,
+            inclination: 0.9,
+            arg_peri: 1.2,
+        },
+        t: PlanarTrajectory {
+            periapsis: 7000.0,
+            eccentr: 0.1,
+            mean_anom0: 0.0,
+            mean_anom1: 2.0 * std::f64::consts::PI,
+        },
+    }
+}
+
+fn initial() -> StateVector {
+    let (pos, vel) = orbit().to_state(MU, 0.0);
+    StateVector {
+        time: 0.0,
+        position: pos,
+        velocity: vel,
+    }
+}
+
+fn energy(s: &StateVector) -> f64 {
+    s.velocity.norm_squared() / 2.0 - MU / s.position.norm()
+}
+
+fn angular_momentum(s: &StateVector) -> Vector3<f64> {
+    s.position.cross(&s.velocity)
+}
+
+/// Flies three revolutions and checks that the energy and angular momentum stay within the given
+/// relative tolerances the whole way.
+fn check_conservation(integrator: Integrator, energy_tolerance: f64, momentum_tolerance: f64) {
+    let end_time = 3.0 * orbit().t.period(MU).unwrap();
+    let history = numerical::propagate(&PointMass { mu: MU }, integrator, initial(), end_time)
+        .unwrap();
+    assert_eq!(history.last().unwrap().time, end_time);
+    let (e0, h0) = (energy(&initial()), angular_momentum(&initial()));
+    for s in &history {
+        let e = energy(s);
+        assert!(((e - e0) / e0).abs() < energy_tolerance,
+                "{:?}: energy is {} km²/s² instead of {} at {} s",
+                integrator,
+                e,
+                e0,
+                s.time);
+        let h = angular_momentum(s);
+        assert!((h - h0).norm() < momentum_tolerance * h0.norm(),
+                "{:?}: angular momentum is {:?} km²/s instead of {:?} at {} s",
+                integrator,
+                h,
+                h0,
+                s.time);
+    }
+}
+
+#[test]
+fn rk4_conserves_energy_and_momentum() {
+    check_conservation(Integrator::Rk4 { step: 10.0 }, 1e-10, 1e-10);
+}
+
+#[test]
+fn dormand_prince_conserves_energy_and_momentum() {
+    check_conservation(Integrator::DormandPrince { tolerance: 1e-12 }, 1e-10, 1e-10);
+}
+
+/// Leapfrog's energy wobbles, but its angular momentum only changes by rounding errors.
+#[test]
+fn leapfrog_conserves_energy_and_momentum() {
+    check_conservation(Integrator::Leapfrog { step: 10.0 }, 2e-5, 1e-12);
+}
+
+#[test]
+fn leapfrog_matches_kepler() {
+    let traj = orbit();
+    let end_time = traj.t.period(MU).unwrap();
+    let history = numerical::propagate(&PointMass { mu: MU },
+                                       Integrator::Leapfrog { step: 1.0 },
+                                       initial(),
+                                       end_time)
+        .unwrap();
+    for s in &history {
+        let expected = traj.propagate(MU, 0.0, s.time).position;
+        assert!((s.position - expected).norm() < 0.05,
+                "at {} s, got to {:?} instead of {:?}",
+                s.time,
+                s.position,
+                expected);
+    }
+}
+
+#[test]
+fn backwards() {
+    let end_time = -1000.0;
+    let forces = PointMass { mu: MU };
+    let expected = orbit().propagate(MU, 0.0, end_time).position;
+    for &integrator in &[Integrator::Rk4 { step: 10.0 },
+                         Integrator::DormandPrince { tolerance: 1e-12 },
+                         Integrator::Leapfrog { step: 1.0 }] {
+        let history = numerical::propagate(&forces, integrator, initial(), end_time).unwrap();
+        let last = history.last().unwrap();
+        assert_eq!(last.time, end_time);
+        assert!((last.position - expected).norm() < 0.05, "{:?}", integrator);
+    }
+}
+
+/// There aren't any steps to take.
+#[test]
+fn no_time() {
+    let forces = PointMass { mu: MU };
+    for &integrator in &[Integrator::Rk4 { step: 10.0 },
+                         Integrator::DormandPrince { tolerance: 1e-9 },
+                         Integrator::Leapfrog { step: 10.0 }] {
+        let history = numerical::propagate(&forces, integrator, initial(), 0.0).unwrap();
+        assert_eq!(history, vec![initial()]);
+    }
+}
+
+/// Gravity is infinite at the center of the planet, so there's no step small enough.
+#[test]
+fn dormand_prince_gives_up() {
+    let integrator = Integrator::DormandPrince { tolerance: 1e-9 };
+    let start = StateVector { position: Vector3::zeros(), ..initial() };
+    let err = numerical::propagate(&PointMass { mu: MU }, integrator, start, 1000.0)
+        .unwrap_err();
+    match err {
+        PropagationError::StepTooSmall(_) => {}
+        _ => panic!("{}", err),
+    }
+    assert_eq!(err.history(), &[start]);
+}
+
+/// A step of zero would take forever, and a negative or NaN one would be one giant step.
+#[test]
+fn invalid_step() {
+    let forces = PointMass { mu: MU };
+    for &size in &[0.0, -10.0, f64::NAN, f64::INFINITY] {
+        for &integrator in &[Integrator::Rk4 { step: size },
+                             Integrator::DormandPrince { tolerance: size },
+                             Integrator::Leapfrog { step: size }] {
+            for &end_time in &[1000.0, 0.0] {
+                match numerical::propagate(&forces, integrator, initial(), end_time) {
+                    Err(PropagationError::InvalidStep(ref history)) => {
+                        assert_eq!(history, &[initial()])
+                    }
+                    result => panic!("{:?}: {:?}", integrator, result),
+                }
+            }
+        }
+    }
+}
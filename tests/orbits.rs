@@ -0,0 +1,213 @@
+//! The Kepler solvers and the conversions between trajectories and state vectors.
+
+extern crate nalgebra as na;
+extern crate orbit_sim;
+
+use std::f64::consts::PI;
+
+use na::Vector3;
+
+use orbit_sim::orbits::{self, KeplerError, PlanarTrajectory, Plane, Trajectory};
+
+const MU: f64 = 398600.4418; // km³/s²
+const PERIAPSIS: f64 = 7000.0; // km
+const TAU: f64 = 2.0 * PI;
+
+/// Where a trajectory with the given eccentricity is at true anomaly `true_anom`, for
+/// `universal_kepler`: the distance, radial velocity, and reciprocal of the semi-major axis.
+fn start(ecc: f64, true_anom: f64) -> (f64, f64, f64) {
+    let p = PERIAPSIS * (1.0 + ecc);
+    let r0 = p / (1.0 + ecc * true_anom.cos());
+    let vr0 = (MU / p).sqrt() * ecc * true_anom.sin();
+    (r0, vr0, (1.0 - ecc) / PERIAPSIS)
+}
+
+/// Computes the universal anomaly `dt` seconds after true anomaly `true_anom` with whichever of
+/// the other solvers fits the eccentricity.
+fn expected_chi(ecc: f64, true_anom: f64, dt: f64) -> f64 {
+    let a = PERIAPSIS / (1.0 - ecc);
+    if ecc < 1.0 {
+        let (s, c) = (true_anom / 2.0).sin_cos();
+        let e0 = 2.0 * ((1.0 - ecc).sqrt() * s).atan2((1.0 + ecc).sqrt() * c);
+        let mean_anom = orbits::kepler(ecc, e0) + (MU / a.powi(3)).sqrt() * dt;
+        let revs = (mean_anom / TAU).round();
+        let e = orbits::approx_inv_kepler(ecc, mean_anom - revs * TAU) + revs * TAU;
+        a.sqrt() * (e - e0)
+    } else if ecc == 1.0 {
+        let d0 = (true_anom / 2.0).tan();
+        let mean_anom = orbits::barker(d0) + (MU / (2.0 * PERIAPSIS.powi(3))).sqrt() * dt;
+        (2.0 * PERIAPSIS).sqrt() * (orbits::inv_barker(mean_anom) - d0)
+    } else {
+        let h0 = 2.0 * (((ecc - 1.0) / (ecc + 1.0)).sqrt() * (true_anom / 2.0).tan()).atanh();
+        let mean_anom = orbits::kepler_hyp(ecc, h0) + (MU / (-a).powi(3)).sqrt() * dt;
+        (-a).sqrt() * (orbits::approx_inv_kepler_hyp(ecc, mean_anom) - h0)
+    }
+}
+
+/// Checks that `chi` solves the universal Kepler's equation.
+fn assert_solves(r0: f64, vr0: f64, alpha: f64, dt: f64, chi: f64) {
+    let z = alpha * chi * chi;
+    let (c, s) = (orbits::stumpff_c(z), orbits::stumpff_s(z));
+    let t = (r0 * vr0 / MU.sqrt() * chi * chi * c + (1.0 - alpha * r0) * chi.powi(3) * s +
+             r0 * chi) / MU.sqrt();
+    assert!((t - dt).abs() <= 1e-9 * dt.abs().max(1.0),
+            "χ = {} is {} s instead of {} s (r0 = {}, vr0 = {}, alpha = {})",
+            chi,
+            t,
+            dt,
+            r0,
+            vr0,
+            alpha);
+}
+
+const TRUE_ANOMS: [f64; 5] = [-1.8, -0.5, 0.0, 1.0, 1.8]; // rad
+const TIMES: [f64; 7] = [-1e5, -100.0, 0.0, 1.0, 100.0, 1e4, 1e6]; // s
+
+#[test]
+fn universal_kepler_matches_the_other_solvers() {
+    // (eccentricity, relative tolerance), sweeping through the parabola; within 1e-6 of it the
+    // reference is Barker's equation, which is only nearly right unless it's exactly parabolic
+    let cases = [(0.0f64, 1e-9),
+                 (0.5, 1e-9),
+                 (0.9, 1e-9),
+                 (0.99, 1e-9),
+                 (0.999, 1e-9),
+                 (1.0 - 1e-9, 1e-7),
+                 (1.0 - 1e-12, 1e-9),
+                 (1.0, 1e-9),
+                 (1.0 + 1e-12, 1e-9),
+                 (1.0 + 1e-9, 1e-7),
+                 (1.001, 1e-9),
+                 (1.01, 1e-9),
+                 (1.5, 1e-9),
+                 (3.0, 1e-9)];
+    for &(ecc, tolerance) in &cases {
+        let reference = if (ecc - 1.0).abs() < 1e-6 { 1.0 } else { ecc };
+        for &true_anom in &TRUE_ANOMS {
+            for &dt in &TIMES {
+                let (r0, vr0, alpha) = start(ecc, true_anom);
+                let chi = orbits::universal_kepler(MU, r0, vr0, alpha, dt).unwrap();
+                let expected = expected_chi(reference, true_anom, dt);
+                assert!((chi - expected).abs() <= tolerance * expected.abs().max(1.0),
+                        "e = {}, ν0 = {}, dt = {}: χ is {} instead of {}",
+                        ecc,
+                        true_anom,
+                        dt,
+                        chi,
+                        expected);
+            }
+        }
+    }
+}
+
+/// The semi-major axis is practically infinite, so the elliptic and hyperbolic initial guesses
+/// are useless.
+#[test]
+fn universal_kepler_almost_parabolic() {
+    for &alpha in &[-1e-19, 0.0, 1e-19, -1e-12, 1e-12] {
+        for &true_anom in &TRUE_ANOMS {
+            for &dt in &TIMES {
+                let (r0, vr0, _) = start(1.0, true_anom);
+                let chi = orbits::universal_kepler(MU, r0, vr0, alpha, dt).unwrap();
+                assert_solves(r0, vr0, alpha, dt, chi);
+            }
+        }
+    }
+}
+
+/// States on a parabola from `to_state`, whose energy comes out as rounding error instead of 0.
+#[test]
+fn universal_kepler_escape_velocity() {
+    let traj = Trajectory {
+        p: Plane {
+            lon_asc_node: 0.3,
+            inclination: 0.5,
+            arg_peri: 2.0,
+        },
+        t: PlanarTrajectory {
+            periapsis: PERIAPSIS,
+            eccentr: 1.0,
+            mean_anom0: -10.0,
+            mean_anom1: 10.0,
+        },
+    };
+    for &mean_anom in &[-3.0, -0.1, 0.7, 2.0] {
+        let (pos, vel) = traj.to_state(MU, mean_anom);
+        let r0 = pos.norm();
+        let vr0 = pos.dot(&vel) / r0;
+        let alpha = 2.0 / r0 - vel.norm_squared() / MU;
+        for &dt in &TIMES {
+            let chi = orbits::universal_kepler(MU, r0, vr0, alpha, dt).unwrap();
+            assert_solves(r0, vr0, alpha, dt, chi);
+        }
+    }
+}
+
+#[test]
+fn universal_kepler_invalid() {
+    assert_eq!(orbits::universal_kepler(MU, PERIAPSIS, 0.0, 0.0, f64::NAN),
+               Err(KeplerError::InvalidArgument));
+}
+
+fn assert_close(actual: Vector3<f64>, expected: Vector3<f64>, tolerance: f64, what: &str) {
+    assert!((actual - expected).norm() <= tolerance * expected.norm(),
+            "{} is {:?} instead of {:?}",
+            what,
+            actual,
+            expected);
+}
+
+/// Converts states to trajectories and back, and checks that the trajectory stays the same where
+/// its elements are well defined.
+#[test]
+fn state_round_trip() {
+    // equatorial, polar, retrograde, and retrograde equatorial
+    let inclinations = [0.0, 90.0, 150.0, 180.0];
+    for &ecc in &[0.0, 0.5, 1.0, 3.0] {
+        for &inclination in &inclinations {
+            let traj = Trajectory {
+                p: Plane {
+                    lon_asc_node: 1.0,
+                    inclination: f64::to_radians(inclination),
+                    arg_peri: -2.0,
+                },
+                t: PlanarTrajectory {
+                    periapsis: PERIAPSIS,
+                    eccentr: ecc,
+                    mean_anom0: 0.0,
+                    mean_anom1: TAU,
+                },
+            };
+            for &mean_anom in &[-1.5, 0.0, 0.3, 1.5] {
+                let what = format!("e = {}, i = {}°, M = {}", ecc, inclination, mean_anom);
+                let (pos, vel) = traj.to_state(MU, mean_anom);
+                let (traj2, mean_anom2) = Trajectory::from_state(MU, pos, vel);
+                assert!((traj2.t.eccentr - ecc).abs() < 1e-12, "{}: e = {}", what, traj2.t.eccentr);
+                assert!((traj2.t.periapsis - PERIAPSIS).abs() < 1e-12 * PERIAPSIS,
+                        "{}: periapsis is {} km",
+                        what,
+                        traj2.t.periapsis);
+                assert!((traj2.p.inclination - traj.p.inclination).abs() < 1e-12,
+                        "{}: inclination is {}",
+                        what,
+                        traj2.p.inclination);
+                if inclination != 0.0 && inclination != 180.0 {
+                    assert!((traj2.p.lon_asc_node - traj.p.lon_asc_node).abs() < 1e-12,
+                            "{}: longitude of the ascending node is {}",
+                            what,
+                            traj2.p.lon_asc_node);
+                }
+
+                // the elements can move around for equatorial and circular orbits, but the
+                // state a little later can't
+                let (pos2, vel2) = traj2.to_state(MU, mean_anom2);
+                assert_close(pos2, pos, 1e-12, &format!("{}: position", what));
+                assert_close(vel2, vel, 1e-12, &format!("{}: velocity", what));
+                let (pos, vel) = traj.to_state(MU, mean_anom + 0.4);
+                let (pos2, vel2) = traj2.to_state(MU, mean_anom2 + 0.4);
+                assert_close(pos2, pos, 1e-12, &format!("{}: later position", what));
+                assert_close(vel2, vel, 1e-12, &format!("{}: later velocity", what));
+            }
+        }
+    }
+}
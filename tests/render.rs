@@ -0,0 +1,190 @@
+//! Draws trajectories around a planet with `orbit-sim --output` and checks how opaque they are
+//! where they pass in front of, behind, and through the planet.
+
+#![cfg(feature = "gui")]
+
+extern crate cairo;
+
+use std::env;
+use std::fs::{self, File};
+use std::process::{self, Command};
+
+use cairo::ImageSurface;
+
+const SIZE: i32 = 400; // px
+const ZOOM: f64 = 0.015; // px/km
+/// Venus, which doesn't have any moons to get in the way.
+const RADIUS: f64 = 6051.8; // km
+
+// how opaque the trajectory should be (the renderer's `INTERNAL_ALPHA` and `OCCLUDED_ALPHA`)
+const VISIBLE: f64 = 1.0;
+const INSIDE: f64 = 1.0 / 3.0;
+const BEHIND: f64 = 1.0 / 5.0;
+const TOLERANCE: f64 = 2.0 / 255.0;
+
+/// A rendered picture, as cairo's native-endian ARGB pixels.
+struct Picture {
+    stride: usize,
+    data: Vec<u8>,
+}
+
+impl Picture {
+    /// Finds how opaque the trajectory is at a point on the screen (in km from the middle,
+    /// with y up). The trajectory is pure red and nothing else drawn under it has any red in it,
+    /// so that's just how red the pixel is.
+    fn alpha_at(&self, x: f64, y: f64) -> f64 {
+        let column = (SIZE as f64 / 2.0 + x * ZOOM).floor() as usize;
+        let row = (SIZE as f64 / 2.0 - y * ZOOM).floor() as usize;
+        let i = row * self.stride + column * 4;
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.data[i..i + 4]);
+        let red = u32::from_ne_bytes(bytes) >> 16 & 0xff;
+        red as f64 / 255.0
+    }
+}
+
+/// Draws one red trajectory with its ascending node on the x-axis around Venus, seen from `eye`
+/// (latitude and longitude in degrees).
+fn render(name: &str,
+          periapsis: f64,
+          eccentricity: f64,
+          inclination: f64,
+          eye: (f64, f64))
+          -> Picture {
+    let dir = env::temp_dir();
+    let scenario = dir.join(format!("orbit-sim-render-{}-{}.json", name, process::id()));
+    let output = dir.join(format!("orbit-sim-render-{}-{}.png", name, process::id()));
+    fs::write(&scenario,
+              format!(r#"{{
+  "body": "Venus",
+  "objects": [{{
+    "name": "{}",
+    "color": [1.0, 0.0, 0.0],
+    "visible": true,
+    "trajectory": {{
+      "plane": {{"lon_asc_node_rad": 0.0, "inclination_rad": {}, "arg_peri_rad": 0.0}},
+      "shape": {{"periapsis_km": {}, "eccentricity": {}, "mean_anom0_rad": 0.0,
+                "mean_anom1_rad": 6.283185307179586}}
+    }}
+  }}],
+  "ground_track_revs": 0.0,
+  "graticule_spacing_rad": 0.0
+}}"#,
+                      name,
+                      inclination.to_radians(),
+                      periapsis,
+                      eccentricity))
+        .unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_orbit-sim"))
+        .arg("--scenario")
+        .arg(&scenario)
+        .arg("--eye")
+        .arg(format!("{},{}", eye.0, eye.1))
+        .arg("--zoom")
+        .arg(ZOOM.to_string())
+        .arg("--size")
+        .arg(format!("{}x{}", SIZE, SIZE))
+        .arg("--output")
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert!(status.success(), "orbit-sim failed for {}", name);
+
+    let mut image = ImageSurface::create_from_png(&mut File::open(&output).unwrap()).unwrap();
+    let pixels = image.get_data().unwrap().to_vec();
+    fs::remove_file(&scenario).unwrap();
+    fs::remove_file(&output).unwrap();
+    Picture {
+        stride: image.get_stride() as usize,
+        data: pixels,
+    }
+}
+
+fn check(picture: &Picture, x: f64, y: f64, expected: f64, what: &str) {
+    let alpha = picture.alpha_at(x, y);
+    assert!((alpha - expected).abs() < TOLERANCE,
+            "{} at ({}, {}) km: alpha is {} instead of {}",
+            what,
+            x,
+            y,
+            alpha,
+            expected);
+}
+
+/// Looks at a circular orbit twice as wide as the planet from 20° above or below its plane,
+/// from the side where the middle of the far half ends up above the planet's center on the
+/// screen (or below it if `far_side_up` is false).
+fn check_front_and_behind(inclination: f64, eye_lat: f64, far_side_up: bool) {
+    let name = format!("front-and-behind-{}", inclination);
+    let picture = render(&name, 2.0 * RADIUS, 0.0, inclination, (eye_lat, 90.0));
+    let far_side = if far_side_up { 1.0 } else { -1.0 } * 2.0 * RADIUS * 20f64.to_radians().sin();
+    check(&picture, 0.0, -far_side, VISIBLE, "in front of the planet");
+    check(&picture, 0.0, far_side, BEHIND, "behind the planet");
+    let beside = 30f64.to_radians();
+    check(&picture,
+          2.0 * RADIUS * beside.cos(),
+          far_side * beside.sin(),
+          VISIBLE,
+          "beside the planet");
+}
+
+#[test]
+fn front_and_behind() {
+    check_front_and_behind(0.0, 20.0, true);
+}
+
+#[test]
+fn front_and_behind_inclined() {
+    check_front_and_behind(50.0, -30.0, true);
+    check_front_and_behind(90.0, -70.0, true);
+    check_front_and_behind(160.0, 0.0, false);
+}
+
+/// When the orbit is edge-on, the near half is drawn right over the far half.
+#[test]
+fn edge_on() {
+    let picture = render("edge-on", 2.0 * RADIUS, 0.0, 0.0, (0.0, 90.0));
+    check(&picture, 0.5 * RADIUS, 0.0, VISIBLE, "in front of the planet");
+    check(&picture, -0.5 * RADIUS, 0.0, VISIBLE, "in front of the planet");
+    check(&picture, 1.5 * RADIUS, 0.0, VISIBLE, "beside the planet");
+}
+
+/// An orbit that dips under the surface, seen from above.
+#[test]
+fn inside() {
+    let (periapsis, apoapsis) = (0.5 * RADIUS, 3.0 * RADIUS);
+    let eccentricity = (apoapsis - periapsis) / (apoapsis + periapsis);
+    let picture = render("inside", periapsis, eccentricity, 0.0, (90.0, 90.0));
+    let semi_latus_rectum = periapsis * (1.0 + eccentricity);
+    let at = |true_anom: f64| {
+        let r = semi_latus_rectum / (1.0 + eccentricity * true_anom.cos());
+        (r * true_anom.cos(), r * true_anom.sin())
+    };
+    for &(true_anom, expected, what) in &[(90.0, INSIDE, "under the surface"),
+                                          (-45.0, INSIDE, "under the surface"),
+                                          (120.0, VISIBLE, "above the surface"),
+                                          (-120.0, VISIBLE, "above the surface")] {
+        let (x, y) = at(f64::to_radians(true_anom));
+        check(&picture, x, y, expected, what);
+    }
+}
+
+/// Everything on the command line is read before the scenario is loaded.
+#[test]
+fn help_with_a_missing_scenario() {
+    let output = Command::new(env!("CARGO_BIN_EXE_orbit-sim"))
+        .args(["--scenario", "there-is-no-such-scenario.json", "--help"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Usage: orbit-sim"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_orbit-sim"))
+        .args(["--scenario", "there-is-no-such-scenario.json", "--time", "soon"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not a number: soon"),
+            "{}",
+            String::from_utf8_lossy(&output.stderr));
+}
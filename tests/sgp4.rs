@@ -0,0 +1,167 @@
+//! Checks SGP4 against the reference output in Vallado et al., "Revisiting Spacetrack Report #3"
+//! (2006), from `SGP4-VER.TLE` and `tcppver.out`.
+
+extern crate orbit_sim;
+
+use std::f64::consts::PI;
+
+use orbit_sim::bodies::{wrap_angle, EARTH, PRESETS};
+use orbit_sim::sgp4::{self, Sgp4};
+use orbit_sim::tle::Tle;
+
+const POSITION_TOLERANCE: f64 = 1e-6; // km
+const VELOCITY_TOLERANCE: f64 = 1e-9; // km/s
+const TAU: f64 = 2.0 * PI;
+
+/// Minutes since epoch, then the position (km) and velocity (km/s).
+type Row = (f64, [f64; 3], [f64; 3]);
+
+fn setup(line1: &str, line2: &str) -> Sgp4 {
+    Sgp4::new(&Tle::parse(line1, line2).unwrap()).unwrap()
+}
+
+fn check(sat: &Sgp4, rows: &[Row]) {
+    for &(minutes, pos, vel) in rows {
+        let (r, v) = sat.propagate(minutes * 60.0).unwrap();
+        for i in 0..3 {
+            assert!((r[i] - pos[i]).abs() < POSITION_TOLERANCE,
+                    "position at {} min: {:?} != {:?}",
+                    minutes,
+                    r,
+                    pos);
+            assert!((v[i] - vel[i]).abs() < VELOCITY_TOLERANCE,
+                    "velocity at {} min: {:?} != {:?}",
+                    minutes,
+                    v,
+                    vel);
+        }
+    }
+}
+
+#[rustfmt::skip]
+const VANGUARD: [Row; 13] = [
+    (0.0,    [7022.46529266, -1400.08296755, 0.03995155],
+             [1.893841015, 6.405893759, 4.534807250]),
+    (360.0,  [-7154.03120202, -3783.17682504, -3536.19412294],
+             [4.741887409, -4.151817765, -2.093935425]),
+    (720.0,  [-7134.59340119, 6531.68641334, 3260.27186483],
+             [-4.113793027, -2.911922039, -2.557327851]),
+    (1080.0, [5568.53901181, 4492.06992591, 3863.87641983],
+             [-4.209106476, 5.159719888, 2.744852980]),
+    (1440.0, [-938.55923943, -6268.18748831, -4294.02924751],
+             [7.536105209, -0.427127707, 0.989878080]),
+    (1800.0, [-9680.56121728, 2802.47771354, 124.10688038],
+             [-0.905874102, -4.659467970, -3.227347517]),
+    (2160.0, [190.19796988, 7746.96653614, 5110.00675412],
+             [-6.112325142, 1.527008184, -0.139152358]),
+    (2520.0, [5579.55640116, -3995.61396789, -1518.82108966],
+             [4.767927483, 5.123185301, 4.276837355]),
+    (2880.0, [-8650.73082219, -1914.93811525, -3007.03603443],
+             [3.067165127, -4.828384068, -2.515322836]),
+    (3240.0, [-5429.79204164, 7574.36493792, 3747.39305236],
+             [-4.999442110, -1.800561422, -2.229392830]),
+    (3600.0, [6759.04583722, 2001.58198220, 2783.55192533],
+             [-2.180993947, 6.402085603, 3.644723952]),
+    (3960.0, [-3791.44531559, -5712.95617894, -4533.48630714],
+             [6.668817493, -2.516382327, -0.082384354]),
+    (4320.0, [-9060.47373569, 4658.70952502, 813.68673153],
+             [-2.232832783, -4.110453490, -3.157345433]),
+];
+
+#[test]
+fn near_earth() {
+    let sat = setup("1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753",
+                    "2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667");
+    assert!(!sat.is_deep_space());
+    check(&sat, &VANGUARD);
+}
+
+#[test]
+fn near_earth_low_perigee() {
+    let sat = setup("1 06251U 62025E   06176.82412014  .00008885  00000-0  12808-3 0  3985",
+                    "2 06251  58.0579  54.0425 0030035 139.1568 221.1854 15.56387291  6774");
+    assert!(!sat.is_deep_space());
+    check(&sat,
+          &[(0.0,
+             [3988.31022699, 5498.96657235, 0.90055879],
+             [-3.290032738, 2.357652820, 6.496623475])]);
+}
+
+#[test]
+fn deep_space_half_day_resonance() {
+    let sat = setup("1 08195U 75081A   06176.33215444  .00000099  00000-0  11873-3 0   813",
+                    "2 08195  64.1586 279.0717 6877146 264.7651  20.2257  2.00491383225656");
+    assert!(sat.is_deep_space());
+    let (r, _) = sat.propagate(0.0).unwrap();
+    let expected = [2349.89483350, -14785.93811562, 0.02119378];
+    for i in 0..3 {
+        assert!((r[i] - expected[i]).abs() < POSITION_TOLERANCE,
+                "{:?} != {:?}",
+                r,
+                expected);
+    }
+    // (the later rows of tcppver.out aren't here, so the rest is only checked against itself)
+    check_resonant(&sat, 2.00491383);
+}
+
+/// A made-up geostationary satellite, which has the one-day resonance.
+#[test]
+fn deep_space_synchronous() {
+    let tle = Tle::parse("1 99999U 06001A   06176.50000000  .00000000  00000-0  00000-0 0   819",
+                         "2 99999   0.0500  90.0000 0002000 100.0000 170.0000  1.00273791    13")
+        .unwrap();
+    let sat = Sgp4::new(&tle).unwrap();
+    assert!(sat.is_deep_space());
+    check_resonant(&sat, 1.00273791);
+
+    // it stays over the same place, which is where its elements put it at epoch
+    let earth = &PRESETS[EARTH];
+    let expected = (90.0f64 + 100.0 + 170.0).to_radians() - sgp4::gmst(tle.epoch);
+    for k in 0..25 {
+        let time = k as f64 * 7200.0;
+        let (r, _) = sat.propagate(time).unwrap();
+        let (lat, lon) = earth.subpoint(&r, sgp4::gmst(tle.epoch + time));
+        assert!(lat.to_degrees().abs() < 0.1, "latitude is {}° at {} s", lat.to_degrees(), time);
+        let off = wrap_angle(lon - expected).to_degrees();
+        assert!(off.abs() < 0.1, "longitude is {}° off at {} s", off, time);
+    }
+}
+
+/// Flies `sat` for two days, checking that its velocity is how fast its position changes and that
+/// it stays at the semi-major axis that goes with `rev_per_day`. (SGP4 leaves some of the short
+/// periodic terms out of the velocity, so it's only close: Vanguard's is off by up to 2e-4 of it
+/// too.)
+fn check_resonant(sat: &Sgp4, rev_per_day: f64) {
+    let mu = PRESETS[EARTH].mu;
+    let a = (mu / (rev_per_day * TAU / 86400.0).powi(2)).cbrt();
+    for k in 0..25 {
+        let time = k as f64 * 7200.0;
+        let (r, v) = sat.propagate(time).unwrap();
+        let (before, _) = sat.propagate(time - 0.5).unwrap();
+        let (after, _) = sat.propagate(time + 0.5).unwrap();
+        let rate = after - before;
+        assert!((rate - v).norm() < 5e-4 * v.norm(),
+                "at {} s, moving at {:?} km/s but the velocity is {:?}",
+                time,
+                rate,
+                v);
+        let a_now = 1.0 / (2.0 / r.norm() - v.norm_squared() / mu);
+        assert!((a_now - a).abs() < 1e-3 * a,
+                "semi-major axis is {} km at {} s instead of about {}",
+                a_now,
+                time,
+                a);
+    }
+}
+
+/// SGP4's positions are in the same frame as the TLE's Keplerian trajectory,
+/// so they should only be apart by the perturbations at epoch.
+#[test]
+fn same_frame_as_trajectory() {
+    let tle = Tle::parse("1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753",
+                         "2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667")
+        .unwrap();
+    let (r, _) = Sgp4::new(&tle).unwrap().propagate(0.0).unwrap();
+    let kepler = tle.trajectory().propagate(PRESETS[EARTH].mu, 0.0, 0.0).position;
+    assert!((r - kepler).norm() < 20.0, "{:?} is far from {:?}", r, kepler);
+}
@@ -0,0 +1,199 @@
+//! Reading TLEs: the fields with implied decimal points, the two-digit years, files with several
+//! satellites, and what happens when a line is wrong.
+
+extern crate orbit_sim;
+
+use std::f64::consts::PI;
+
+use orbit_sim::tle::{self, Tle, TleError};
+
+const ISS_1: &str = "1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927";
+const ISS_2: &str = "2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537";
+const VANGUARD_1: &str = "1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753";
+const VANGUARD_2: &str = "2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667";
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Replaces the last digit of `line` with the right checksum, after it's been edited.
+fn fix_checksum(line: &str) -> String {
+    let body = &line[..68];
+    let sum: u32 = body.bytes()
+        .map(|b| match b {
+            b'0'..=b'9' => (b - b'0') as u32,
+            b'-' => 1,
+            _ => 0,
+        })
+        .sum();
+    format!("{}{}", body, sum % 10)
+}
+
+fn assert_close(actual: f64, expected: f64, what: &str) {
+    assert!((actual - expected).abs() <= 1e-12 * expected.abs(),
+            "{} is {} instead of {}",
+            what,
+            actual,
+            expected);
+}
+
+#[test]
+fn fields() {
+    let tle = Tle::parse(ISS_1, ISS_2).unwrap();
+    assert_eq!(tle.name, None);
+    assert_eq!(tle.catalog_number, 25544);
+    assert_eq!(tle.classification, 'U');
+    assert_eq!(tle.intl_designator, "98067A");
+    assert_eq!(tle.object_id(), Some("1998-067A".to_owned()));
+    assert_eq!(tle.element_set, 292);
+    assert_eq!(tle.rev_number, 56353);
+
+    // 2000 to 2008 has two leap days, and J2000 is noon on the first day
+    let days = 8.0 * 365.0 + 2.0 + 264.51782528 - 1.5;
+    assert_close(tle.epoch, days * SECONDS_PER_DAY, "epoch");
+
+    let rev_per_day = 2.0 * PI / SECONDS_PER_DAY;
+    assert_close(tle.mean_motion, 15.72125391 * rev_per_day, "mean motion");
+    assert_close(tle.mean_motion_dot,
+                 2.0 * -0.00002182 * rev_per_day / SECONDS_PER_DAY,
+                 "mean motion derivative");
+    assert_close(tle.inclination, 51.6416f64.to_radians(), "inclination");
+    assert_close(tle.lon_asc_node, 247.4627f64.to_radians(), "longitude of the ascending node");
+    assert_close(tle.arg_peri, 130.5360f64.to_radians(), "argument of perigee");
+    assert_close(tle.mean_anom, 325.0288f64.to_radians(), "mean anomaly");
+}
+
+/// The eccentricity has an implied `0.` in front, and the drag term and second derivative also
+/// have a power of ten at the end.
+#[test]
+fn implied_decimal_points() {
+    let tle = Tle::parse(ISS_1, ISS_2).unwrap();
+    assert_close(tle.eccentr, 0.0006703, "eccentricity");
+    assert_close(tle.bstar, -0.11606e-4, "drag term");
+    assert_eq!(tle.mean_motion_ddot, 0.0);
+
+    let tle = Tle::parse(VANGUARD_1, VANGUARD_2).unwrap();
+    assert_close(tle.eccentr, 0.1859667, "eccentricity");
+    assert_close(tle.bstar, 0.28098e-4, "drag term");
+
+    let line1 = fix_checksum(&ISS_1.replace(" 00000-0 -11606-4", "-12345+1 +11606-4"));
+    let tle = Tle::parse(&line1, ISS_2).unwrap();
+    let rev_per_day3 = 2.0 * PI / SECONDS_PER_DAY.powi(3);
+    assert_close(tle.mean_motion_ddot,
+                 6.0 * -0.12345e1 * rev_per_day3,
+                 "mean motion second derivative");
+    assert_close(tle.bstar, 0.11606e-4, "drag term");
+
+    let line1 = fix_checksum(&ISS_1.replace("-11606-4", "-11606 4"));
+    match Tle::parse(&line1, ISS_2) {
+        Err(TleError::BadField { line: 1, field: "drag term", .. }) => {}
+        result => panic!("{:?}", result),
+    }
+}
+
+/// Two-digit years from 57 on are in the 1900s, since Sputnik was launched in 1957.
+#[test]
+fn two_digit_years() {
+    let days_to_1957 = -43.0 * 365.0 - 10.0;
+    let days_to_2056 = 56.0 * 365.0 + 14.0;
+    let cases = [("57001.00000000", days_to_1957),
+                 ("56001.00000000", days_to_2056),
+                 ("99001.00000000", -365.0),
+                 ("00001.00000000", 0.0)];
+    for &(epoch, days) in &cases {
+        let line1 = fix_checksum(&ISS_1.replace("08264.51782528", epoch));
+        let tle = Tle::parse(&line1, ISS_2).unwrap();
+        assert_eq!(tle.epoch, (days - 0.5) * SECONDS_PER_DAY, "{}", epoch);
+    }
+
+    // the launch year in the designator too
+    let tle = Tle::parse(VANGUARD_1, VANGUARD_2).unwrap();
+    assert_eq!(tle.object_id(), Some("1958-002B".to_owned()));
+}
+
+#[test]
+fn bad_checksum() {
+    let line2 = ISS_2.replace("563537", "563538");
+    assert_eq!(Tle::parse(ISS_1, &line2),
+               Err(TleError::BadChecksum {
+                   line: 2,
+                   expected: 7,
+                   actual: 8,
+               }));
+}
+
+#[test]
+fn wrong_length() {
+    assert_eq!(Tle::parse(&ISS_1[..60], ISS_2),
+               Err(TleError::WrongLength {
+                   line: 1,
+                   length: 60,
+               }));
+    // trailing spaces don't count
+    let line2 = format!("{}   ", ISS_2);
+    assert!(Tle::parse(ISS_1, &line2).is_ok());
+}
+
+#[test]
+fn catalog_mismatch() {
+    assert_eq!(Tle::parse(ISS_1, VANGUARD_2),
+               Err(TleError::CatalogMismatch { line: 2 }));
+}
+
+#[test]
+fn wrong_line_number() {
+    assert_eq!(Tle::parse(ISS_2, ISS_1),
+               Err(TleError::WrongLineNumber {
+                   line: 1,
+                   expected: 1,
+               }));
+}
+
+#[test]
+fn parse_all_with_names() {
+    let text = format!("ISS (ZARYA)\n{}\n{}\n\n0 VANGUARD 1\n{}\n{}\n",
+                       ISS_1,
+                       ISS_2,
+                       VANGUARD_1,
+                       VANGUARD_2);
+    let tles = tle::parse_all(&text).unwrap();
+    assert_eq!(tles.len(), 2);
+    assert_eq!(tles[0].name, Some("ISS (ZARYA)".to_owned()));
+    assert_eq!(tles[0].catalog_number, 25544);
+    assert_eq!(tles[1].name, Some("VANGUARD 1".to_owned()));
+    assert_eq!(tles[1].catalog_number, 5);
+}
+
+#[test]
+fn parse_all_without_names() {
+    let text = format!("{}\r\n{}\r\n{}\r\n{}\r\n", ISS_1, ISS_2, VANGUARD_1, VANGUARD_2);
+    let tles = tle::parse_all(&text).unwrap();
+    assert_eq!(tles.len(), 2);
+    assert_eq!(tles[0], Tle::parse(ISS_1, ISS_2).unwrap());
+    assert_eq!(tles[1], Tle::parse(VANGUARD_1, VANGUARD_2).unwrap());
+
+    assert_eq!(tle::parse_all(""), Ok(Vec::new()));
+}
+
+/// The errors point at the line in the whole text.
+#[test]
+fn parse_all_errors() {
+    let text = format!("ISS (ZARYA)\n{}\n{}\n{}\n", ISS_1, ISS_2, VANGUARD_1);
+    assert_eq!(tle::parse_all(&text), Err(TleError::MissingLine { line: 5 }));
+
+    let text = format!("ISS (ZARYA)\n{}", ISS_1);
+    assert_eq!(tle::parse_all(&text), Err(TleError::MissingLine { line: 3 }));
+
+    let text = format!("{}\n{}\nVANGUARD 1", ISS_1, ISS_2);
+    assert_eq!(tle::parse_all(&text), Err(TleError::MissingLine { line: 4 }));
+
+    let text = format!("{}\n\n{}\n{}\n", VANGUARD_2, ISS_1, ISS_2);
+    assert_eq!(tle::parse_all(&text),
+               Err(TleError::WrongLineNumber {
+                   line: 1,
+                   expected: 1,
+               }));
+
+    let text = format!("{}\n{}\n\nISS\n{}\n{}\n", VANGUARD_1, VANGUARD_2, ISS_1, VANGUARD_2);
+    let err = tle::parse_all(&text).unwrap_err();
+    assert_eq!(err, TleError::CatalogMismatch { line: 6 });
+    assert_eq!(err.to_string(), "line 6: catalog number doesn't match the previous line");
+}
@@ -0,0 +1,171 @@
+//! Hohmann and bi-elliptic transfers between circular orbits, checked against the vis-viva
+//! equation, and Lambert's problem, checked by flying the transfers it finds.
+
+extern crate nalgebra as na;
+extern crate orbit_sim;
+
+use std::f64::consts::PI;
+
+use na::Vector3;
+
+use orbit_sim::orbits::{self, LambertError, PlanarTrajectory, Plane, Trajectory};
+
+const MU: f64 = 398600.4418; // km³/s²
+const DELTA_V_TOLERANCE: f64 = 1e-9; // km/s
+const POSITION_TOLERANCE: f64 = 1e-6; // km
+const VELOCITY_TOLERANCE: f64 = 1e-9; // km/s
+
+fn circular(radius: f64) -> Trajectory {
+    Trajectory {
+        p: Plane::default(),
+        t: PlanarTrajectory {
+            periapsis: radius,
+            eccentr: 0.0,
+            mean_anom0: 0.0,
+            mean_anom1: 2.0 * PI,
+        },
+    }
+}
+
+/// Computes the speed (km/s) at distance `r` (km) on an orbit with semi-major axis `a` (km).
+fn vis_viva(r: f64, a: f64) -> f64 {
+    (MU * (2.0 / r - 1.0 / a)).sqrt()
+}
+
+/// Computes half of the period (s) of an orbit with semi-major axis `a` (km).
+fn half_period(a: f64) -> f64 {
+    PI * (a.powi(3) / MU).sqrt()
+}
+
+fn assert_burns(actual: &[f64], expected: &[f64]) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() < DELTA_V_TOLERANCE,
+                "burns are {:?} km/s instead of {:?} km/s",
+                actual,
+                expected);
+    }
+}
+
+#[test]
+fn hohmann_leo_to_geo() {
+    let (r1, r2) = (6578.0, 42164.0);
+    let transfer = orbits::hohmann(MU, &circular(r1), &circular(r2)).unwrap();
+    let a = (r1 + r2) / 2.0;
+    assert_burns(&transfer.burns,
+                 &[vis_viva(r1, a) - vis_viva(r1, r1), vis_viva(r2, r2) - vis_viva(r2, a)]);
+    assert!((transfer.total_delta_v() - 3.93).abs() < 0.005,
+            "{} km/s",
+            transfer.total_delta_v());
+    assert!((transfer.time - half_period(a)).abs() < 1e-6);
+
+    let leg = transfer.legs[0];
+    assert!((leg.t.periapsis - r1).abs() < 1e-9);
+    assert!((leg.t.apoapsis().unwrap().norm() - r2).abs() < 1e-6);
+}
+
+/// Going down takes as much delta-v as going up.
+#[test]
+fn hohmann_down() {
+    let up = orbits::hohmann(MU, &circular(6578.0), &circular(42164.0)).unwrap();
+    let down = orbits::hohmann(MU, &circular(42164.0), &circular(6578.0)).unwrap();
+    assert_burns(&down.burns, &[up.burns[1], up.burns[0]]);
+    assert!((down.time - up.time).abs() < 1e-6);
+}
+
+/// Past a ratio of about 11.94 between the radii, a bi-elliptic transfer that goes out far
+/// enough takes less delta-v than a Hohmann transfer.
+#[test]
+fn bi_elliptic_beats_hohmann() {
+    let r1 = 6578.0;
+    let r2 = 15.0 * r1;
+    let rb = 60.0 * r1;
+    let hohmann = orbits::hohmann(MU, &circular(r1), &circular(r2)).unwrap();
+    let bi_elliptic = orbits::bi_elliptic(MU, &circular(r1), &circular(r2), rb).unwrap();
+
+    let (a1, a2) = ((r1 + rb) / 2.0, (r2 + rb) / 2.0);
+    assert_burns(&bi_elliptic.burns,
+                 &[vis_viva(r1, a1) - vis_viva(r1, r1),
+                   vis_viva(rb, a2) - vis_viva(rb, a1),
+                   vis_viva(r2, a2) - vis_viva(r2, r2)]);
+    assert!((bi_elliptic.time - half_period(a1) - half_period(a2)).abs() < 1e-6);
+    assert!(bi_elliptic.total_delta_v() < hohmann.total_delta_v() - 0.05,
+            "bi-elliptic: {} km/s, Hohmann: {} km/s",
+            bi_elliptic.total_delta_v(),
+            hohmann.total_delta_v());
+
+    // but not when the radii are closer together
+    let r2 = 5.0 * r1;
+    let hohmann = orbits::hohmann(MU, &circular(r1), &circular(r2)).unwrap();
+    let bi_elliptic = orbits::bi_elliptic(MU, &circular(r1), &circular(r2), rb).unwrap();
+    assert!(bi_elliptic.total_delta_v() > hohmann.total_delta_v());
+}
+
+fn lambert_ends() -> (Vector3<f64>, Vector3<f64>) {
+    (Vector3::new(7000.0, 0.0, 0.0), Vector3::new(-2000.0, 8500.0, 1500.0))
+}
+
+/// Flies each solution from the start for `tof` seconds, starting from its velocity at departure,
+/// and checks that it gets to the end.
+fn check_lambert(tof: f64, prograde: bool, revs: u32, solutions: usize) {
+    let (pos1, pos2) = lambert_ends();
+    let found = orbits::lambert(MU, pos1, pos2, tof, prograde, revs).unwrap();
+    assert_eq!(found.len(), solutions);
+    for solution in &found {
+        let what = format!("{} s, prograde: {}, {} revolutions", tof, prograde, revs);
+        let (traj, mean_anom) = Trajectory::from_state(MU, pos1, solution.vel1);
+        let mean_anom = mean_anom + traj.t.mean_motion(MU) * tof;
+        let (pos, vel) = traj.to_state(MU, mean_anom);
+        assert!((pos - pos2).norm() < POSITION_TOLERANCE,
+                "{}: got to {:?} instead of {:?}",
+                what,
+                pos,
+                pos2);
+        assert!((vel - solution.vel2).norm() < VELOCITY_TOLERANCE,
+                "{}: arrived at {:?} instead of {:?}",
+                what,
+                vel,
+                solution.vel2);
+        assert_eq!(traj.normal().z > 0.0, prograde, "{}: went the wrong way", what);
+
+        // the trajectory that comes with it covers the same part
+        let t = solution.trajectory.t;
+        let (pos, _) = solution.trajectory.to_state(MU, t.mean_anom1);
+        assert!((pos - pos2).norm() < POSITION_TOLERANCE, "{}: ends at {:?}", what, pos);
+        if t.eccentr < 1.0 {
+            let revs_flown = (t.mean_anom1 - t.mean_anom0) / (2.0 * PI);
+            assert!(revs_flown > revs as f64 && revs_flown < (revs + 1) as f64,
+                    "{}: made {} revolutions",
+                    what,
+                    revs_flown);
+        }
+    }
+}
+
+#[test]
+fn lambert_no_revolutions() {
+    check_lambert(3000.0, true, 0, 1);
+    // on a hyperbola
+    check_lambert(300.0, true, 0, 1);
+}
+
+#[test]
+fn lambert_retrograde() {
+    check_lambert(3000.0, false, 0, 1);
+    check_lambert(30000.0, false, 1, 2);
+}
+
+#[test]
+fn lambert_revolutions() {
+    check_lambert(15000.0, true, 1, 2);
+    check_lambert(25000.0, true, 2, 2);
+}
+
+#[test]
+fn lambert_no_solution() {
+    let (pos1, pos2) = lambert_ends();
+    assert_eq!(orbits::lambert(MU, pos1, pos2, 3000.0, true, 2).unwrap_err(),
+               LambertError::NoSolution);
+    assert_eq!(orbits::lambert(MU, pos1, 2.0 * pos1, 3000.0, true, 0).unwrap_err(),
+               LambertError::InvalidArgument);
+}